@@ -5,6 +5,11 @@ use std::{
     path::Path,
 };
 
+/// Languages are only compiled in if the cargo feature of the same name is enabled
+fn enabled(language: &str) -> bool {
+    env::var_os(format!("CARGO_FEATURE_{}", language.to_uppercase())).is_some()
+}
+
 fn main() -> std::io::Result<()> {
     let languages = &[
         "json",
@@ -33,51 +38,45 @@ fn main() -> std::io::Result<()> {
         r#"
         use std::collections::HashMap;
         use once_cell::sync::Lazy;
-        use tree_sitter_highlight::HighlightConfiguration;
 
-        pub static HI_CFGS: Lazy<HashMap<&'static str, HighlightConfiguration>> = Lazy::new(|| {{
-            let mut configs = HashMap::new();
+        pub static SOURCES: Lazy<HashMap<&'static str, crate::Sources>> = Lazy::new(|| {{
+            #[allow(unused_mut)]
+            let mut sources = HashMap::new();
     "#
     )?;
 
-    write!(
-        out_file,
-        r#"
-            configs.insert("javascript", {{
-                let mut cfg = HighlightConfiguration::new(
-                    tree_sitter_javascript::LANGUAGE.into(),
-                    "javascript",
-                    include_str!("{nvim_treesitter_queries}/ecma/highlights.scm"),
-                    include_str!("{nvim_treesitter_queries}/ecma/injections.scm"),
-                    include_str!("{nvim_treesitter_queries}/ecma/locals.scm"),
-                ).expect("Could not load language javascript");
-                cfg.configure(crate::HIGHLIGHT_NAMES);
-                cfg
+    if enabled("javascript") {
+        write!(
+            out_file,
+            r#"
+            sources.insert("javascript", crate::Sources {{
+                language: tree_sitter_javascript::LANGUAGE.into(),
+                highlights: include_str!("{nvim_treesitter_queries}/ecma/highlights.scm"),
+                injections: include_str!("{nvim_treesitter_queries}/ecma/injections.scm"),
+                locals: include_str!("{nvim_treesitter_queries}/ecma/locals.scm"),
             }});
         "#
-    )?;
+        )?;
+    }
 
-    write!(
-        out_file,
-        r#"
-            configs.insert("asm", {{
-                let mut cfg = HighlightConfiguration::new(
-                    tree_sitter_asm::LANGUAGE.into(),
-                    "asm",
-                    include_str!("{nvim_treesitter_queries}/asm/highlights.scm"),
-                    "",
-                    include_str!("{nvim_treesitter_queries}/asm/injections.scm"),
-                ).expect("Could not load language asm");
-                cfg.configure(crate::HIGHLIGHT_NAMES);
-                cfg
+    if enabled("asm") {
+        write!(
+            out_file,
+            r#"
+            sources.insert("asm", crate::Sources {{
+                language: tree_sitter_asm::LANGUAGE.into(),
+                highlights: include_str!("{nvim_treesitter_queries}/asm/highlights.scm"),
+                injections: "",
+                locals: include_str!("{nvim_treesitter_queries}/asm/injections.scm"),
             }});
         "#
-    )?;
+        )?;
+    }
 
     let mut alternate_module = HashMap::new();
     alternate_module.insert("toml", "toml_ng");
 
-    for language in languages {
+    for language in languages.iter().filter(|l| enabled(l)) {
         let injections = if Path::new(nvim_treesitter_queries)
             .join(format!("{language}/injections.scm"))
             .exists()
@@ -92,22 +91,17 @@ fn main() -> std::io::Result<()> {
         write!(
             out_file,
             r#"
-            configs.insert("{language}", {{
-                let mut cfg = HighlightConfiguration::new(
-                    tree_sitter_{module}::LANGUAGE.into(),
-                    "{language}",
-                    include_str!("{nvim_treesitter_queries}/{language}/highlights.scm"),
-                    {injections},
-                    include_str!("{nvim_treesitter_queries}/{language}/locals.scm"),
-                ).expect("Could not load language {language}");
-                cfg.configure(crate::HIGHLIGHT_NAMES);
-                cfg
+            sources.insert("{language}", crate::Sources {{
+                language: tree_sitter_{module}::LANGUAGE.into(),
+                highlights: include_str!("{nvim_treesitter_queries}/{language}/highlights.scm"),
+                injections: {injections},
+                locals: include_str!("{nvim_treesitter_queries}/{language}/locals.scm"),
             }});
         "#
         )?;
     }
 
-    writeln!(out_file, "configs }});")?;
+    writeln!(out_file, "sources }});")?;
 
     Ok(())
 }
@@ -1,6 +1,18 @@
-use std::{collections::HashMap, io, mem};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    io::{self, Write},
+    ops::RangeInclusive,
+    path::Path,
+};
 
-use tree_sitter_highlight::{HighlightConfiguration, Highlighter, HtmlRenderer};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+use tree_sitter_highlight::{
+    Highlight, HighlightConfiguration, HighlightEvent, Highlighter, HtmlRenderer,
+};
 
 pub const HIGHLIGHT_NAMES: &[&str] = &[
     "annotation",
@@ -105,7 +117,216 @@ pub const HIGHLIGHT_NAMES: &[&str] = &[
     "none",
 ];
 
-pub struct Theme(pub HashMap<&'static str, String>);
+/// Mapping from highlight names to the style applied to them
+pub struct Theme(pub HashMap<&'static str, Style>);
+
+/// Color and font of a highlight group
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Style {
+    pub color: Option<String>,
+    pub background: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl Style {
+    /// Style only setting the foreground `color`
+    pub fn fg(color: impl Into<String>) -> Self {
+        Self {
+            color: Some(color.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn bold(self) -> Self {
+        Self { bold: true, ..self }
+    }
+
+    pub fn italic(self) -> Self {
+        Self {
+            italic: true,
+            ..self
+        }
+    }
+
+    pub fn underline(self) -> Self {
+        Self {
+            underline: true,
+            ..self
+        }
+    }
+
+    /// CSS properties set by the style, with their value
+    fn declarations(&self) -> Vec<(&'static str, &str)> {
+        let mut declarations = Vec::new();
+        if let Some(color) = &self.color {
+            declarations.push(("color", color.as_str()));
+        }
+        if let Some(background) = &self.background {
+            declarations.push(("background-color", background.as_str()));
+        }
+        if self.bold {
+            declarations.push(("font-weight", "bold"));
+        }
+        if self.italic {
+            declarations.push(("font-style", "italic"));
+        }
+        if self.underline {
+            declarations.push(("text-decoration", "underline"));
+        }
+        declarations
+    }
+
+    /// CSS declarations applying the style, `None` if it does not change anything
+    pub fn to_css(&self) -> Option<String> {
+        let declarations = self.declarations();
+        (!declarations.is_empty()).then(|| {
+            declarations
+                .iter()
+                .map(|(property, value)| format!("{property}: {value}"))
+                .collect::<Vec<_>>()
+                .join("; ")
+        })
+    }
+}
+
+/// Style of a highlight group in a theme file
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum ThemeEntry {
+    Color(String),
+    Style(Style),
+}
+
+impl Theme {
+    /// Load a theme from a TOML file mapping highlight names to colors or styles.
+    ///
+    /// ```toml
+    /// keyword = "#d183e8"
+    /// "function.call" = { color = "#74b2ff" }
+    /// "markup.heading" = { color = "#74b2ff", bold = true }
+    /// comment = { color = "#949494", italic = true }
+    /// ```
+    ///
+    /// Highlight names can also be prefixed by `@` as in Neovim.
+    pub fn from_toml(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_toml_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml_str(content: &str) -> Result<Self, Error> {
+        let entries: HashMap<String, ThemeEntry> = toml::from_str(content)?;
+
+        let mut styles = HashMap::new();
+        for (name, entry) in entries {
+            let name = name.strip_prefix('@').unwrap_or(&name);
+            let highlight = HIGHLIGHT_NAMES
+                .iter()
+                .find(|&&highlight| highlight == name)
+                .ok_or_else(|| Error::UnknownHighlight(name.to_owned()))?;
+
+            let style = match entry {
+                ThemeEntry::Color(color) => Style::fg(color),
+                ThemeEntry::Style(style) => style,
+            };
+
+            if style != Style::default() {
+                styles.insert(*highlight, style);
+            }
+        }
+
+        Ok(Self(styles))
+    }
+
+    /// Serialize the theme as a stylesheet, to be used with [`Output::Classes`]
+    pub fn to_css(&self) -> String {
+        let mut groups: Vec<_> = self.0.iter().collect();
+        groups.sort_unstable_by_key(|&(name, _)| name);
+
+        groups
+            .into_iter()
+            .filter_map(|(name, style)| {
+                Some(format!(
+                    ".{} {{ {}; }}\n",
+                    class_name(name),
+                    style.to_css()?
+                ))
+            })
+            .collect()
+    }
+
+    /// Serialize the theme as a stylesheet following the color scheme preferred by the reader, to
+    /// be used with [`Output::Classes`]: this theme is the light one, and `dark` is used with
+    /// `prefers-color-scheme: dark`.
+    ///
+    /// Each theme sets a CSS custom property for each property of a highlight group
+    /// (`--hl-keyword-color`), which is applied by the class of the group.
+    pub fn to_dual_css(&self, dark: &Theme) -> String {
+        // Properties set by either theme, for each highlight group
+        let mut properties: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for (&name, style) in self.0.iter().chain(&dark.0) {
+            properties.entry(name).or_default().extend(
+                style
+                    .declarations()
+                    .into_iter()
+                    .map(|(property, _)| property),
+            );
+        }
+
+        let variable = |name: &str, property: &str| format!("--{}-{property}", class_name(name));
+        let variables = |theme: &Theme, indent: &str| {
+            let mut css = String::new();
+            for (&name, group_properties) in &properties {
+                let declarations = theme
+                    .0
+                    .get(name)
+                    .map(Style::declarations)
+                    .unwrap_or_default();
+                for &property in group_properties {
+                    // A variable set to `initial` is invalid, the property is then not applied
+                    let value = declarations
+                        .iter()
+                        .find(|&&(set, _)| set == property)
+                        .map_or("initial", |&(_, value)| value);
+                    css.push_str(&format!("{indent}{}: {value};\n", variable(name, property)));
+                }
+            }
+            css
+        };
+
+        let mut css = format!(
+            ":root {{\n{}}}\n\n@media (prefers-color-scheme: dark) {{\n\t:root {{\n{}\t}}\n}}\n\n",
+            variables(self, "\t"),
+            variables(dark, "\t\t"),
+        );
+        for (&name, group_properties) in &properties {
+            let declarations = group_properties
+                .iter()
+                .map(|&property| format!("{property}: var({})", variable(name, property)))
+                .collect::<Vec<_>>()
+                .join("; ");
+            css.push_str(&format!(".{} {{ {declarations}; }}\n", class_name(name)));
+        }
+        css
+    }
+}
+
+/// Class used for a highlight name in [`Output::Classes`] mode (`function.call` is `hl-function-call`)
+pub fn class_name(highlight: &str) -> String {
+    format!("hl-{}", highlight.replace('.', "-"))
+}
+
+/// How highlighted spans are styled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Output {
+    /// Colors from the theme are set in a `style` attribute on each span
+    #[default]
+    Inline,
+    /// Each span has a class derived from its highlight name (see [`class_name`]), the theme can
+    /// be applied with [`Theme::to_css`], or [`Theme::to_dual_css`] for a light and a dark theme
+    Classes,
+}
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -113,20 +334,49 @@ pub enum Error {
     Io(#[from] io::Error),
     #[error("Could not highlight input due to tree sitter error")]
     TreeSitter(#[from] tree_sitter_highlight::Error),
+    #[error("Invalid query for language {0}")]
+    Query(String, #[source] tree_sitter::QueryError),
+    #[error("Invalid theme file")]
+    Toml(#[from] toml::de::Error),
+    #[error("Theme references unknown highlight `{0}`")]
+    UnknownHighlight(String),
+    #[error("Unknown builtin language `{0}`")]
+    UnknownLanguage(String),
+}
+
+/// Problem found while rendering code, that did not prevent it from being rendered
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Diagnostic {
+    /// The language is not registered, the code was written without highlighting
+    UnknownLanguage(String),
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::UnknownLanguage(language) => {
+                write!(f, "`{language}` was not recognized, skipping highlight")
+            }
+        }
+    }
 }
 
 pub mod theme {
     use once_cell::sync::Lazy;
     use std::collections::HashMap;
 
-    pub struct Color(pub HashMap<&'static str, &'static str>);
+    use super::Style;
+
+    /// Style of each highlight group of a builtin theme
+    pub struct Color(pub HashMap<&'static str, Style>);
 
     impl From<&Color> for super::Theme {
-        fn from(Color(colors): &Color) -> Self {
+        fn from(Color(styles): &Color) -> Self {
             super::Theme(
-                colors
+                styles
                     .iter()
-                    .map(|(&k, v)| (k, format!(r#"style="color: {v}""#)))
+                    .filter(|(_, style)| **style != Style::default())
+                    .map(|(&k, style)| (k, style.clone()))
                     .collect(),
             )
         }
@@ -214,15 +464,17 @@ pub mod theme {
     enum Group {
         Link(&'static str),
         Color(&'static str),
+        Style(Style),
         None,
     }
 
-    fn resolve_group(groups: &HashMap<&'static str, Group>, group: &str) -> Option<&'static str> {
+    fn resolve_group(groups: &HashMap<&'static str, Group>, group: &str) -> Option<Style> {
         let mut group = groups.get(group).unwrap();
         loop {
             match group {
                 Group::Link(l) => group = groups.get(l).unwrap(),
-                Group::Color(s) => return Some(s),
+                Group::Color(s) => return Some(Style::fg(*s)),
+                Group::Style(style) => return Some(style.clone()),
                 Group::None => return None,
             }
         }
@@ -232,15 +484,16 @@ pub mod theme {
         use tokio_night::*;
         use Group::Color as S;
         use Group::Link as L;
+        use Group::Style as F;
 
         let mut groups = HashMap::new();
         set_many!(groups;
             // Neovim groups
-            "Comment" => S(COMMENT),
+            "Comment" => F(Style::fg(COMMENT).italic()),
             "DiffAdd" => Group::None, // bg, darken
             "DiffChange" => Group::None, // bg, darken
             "DiffDelete" => Group::None, // bg, darken
-            "Title" => S(BLUE), // bold
+            "Title" => F(Style::fg(BLUE).bold()),
 
             "Constant" => S(ORANGE),
             "String" => S(GREEN),
@@ -340,7 +593,7 @@ pub mod theme {
             "@variable.parameter.builtin" => S(YELLOW), // lighten
 
             // Keywords
-            "@keyword" => S(PURPLE),
+            "@keyword" => F(Style::fg(PURPLE).italic()),
             "@keyword.function" => S(MAGENTA),
 
             "@label" => S(BLUE),
@@ -406,8 +659,8 @@ pub mod theme {
             );
 
             match resolve_group(&groups, &format!("@{group}")) {
-                Some(color) => {
-                    colors.insert(group, color);
+                Some(style) => {
+                    colors.insert(group, style);
                 }
                 _ => ignore.push(group),
             }
@@ -423,92 +676,371 @@ pub mod theme {
     });
 
     static MOONFLY_COLORS: Lazy<Color> = Lazy::new(|| {
-        let mut colors = HashMap::new();
-        set_many!(colors;
-            "annotation" => moonfly::VIOLET,
-            "attribute" => moonfly::SKY,
-            "constant" => moonfly::TURQUOISE,
-            "constant.builtin" => moonfly::GREEN,
-            "constant.macro" => moonfly::VIOLET,
-            "constructor" => moonfly::EMERALD,
-            "function.builtin" => moonfly::SKY,
-            "function.macro" => moonfly::SKY,
-            "include" => moonfly::CRANBERRY,
-            "keyword.operator" => moonfly::VIOLET,
-            "namespace" => moonfly::TURQUOISE,
-            "parameter" => moonfly::WHITE,
-            "punctuation.special" => moonfly::CRANBERRY,
-            "symbol" => moonfly::PURPLE,
-            "tag" => moonfly::BLUE,
-            "tag.delimiter" => moonfly::LIME,
-            "variable.builtin" => moonfly::LIME,
-            "string" => moonfly::KHAKI,
-            "number" => moonfly::ORANGE,
-            "label" => moonfly::TURQUOISE,
-            "boolean" => moonfly::CORAL,
-            "character" => moonfly::PURPLE,
-            "character.special" => moonfly::CRANBERRY,
-            "comment" => moonfly::GREY246,
-            "conditional" => moonfly::VIOLET,
-            "keyword.conditional" => moonfly::VIOLET,
-            "debug" => moonfly::CRANBERRY,
-            "define" => moonfly::CRANBERRY,
-            "error" => moonfly::RED,
-            "exception" => moonfly::CRIMSON,
-            "field" => moonfly::TURQUOISE,
-            "float" => moonfly::ORANGE,
-            "function" => moonfly::SKY,
-            "function.call" => moonfly::SKY,
-            "keyword" => moonfly::VIOLET,
-            "keyword.function" => moonfly::VIOLET,
-            "keyword.return" => moonfly::VIOLET,
-            "method" => moonfly::SKY,
-            "method.call" => moonfly::SKY,
-            "operator" => moonfly::CRANBERRY,
-            "parameter.reference" => moonfly::WHITE,
-            "preproc" => moonfly::CRANBERRY,
-            "property" => moonfly::TURQUOISE,
-            "punctuation.delimiter" => moonfly::WHITE,
-            "punctuation.bracket" => moonfly::WHITE,
-            "repeat" => moonfly::VIOLET,
-            "storageclass" => moonfly::CORAL,
-            "string.regex" => moonfly::KHAKI,
-            "string.escape" => moonfly::CRANBERRY,
-            "string.special" => moonfly::CRANBERRY,
-            "tag.attribute" => moonfly::TURQUOISE,
-            "title" => moonfly::ORANGE,
-            "text.literal" => moonfly::KHAKI,
-            "text.math" => moonfly::CRANBERRY,
-            "text.reference" => moonfly::ORANGE,
-            "text.environment" => moonfly::CRANBERRY,
-            "text.environment.name" => moonfly::EMERALD,
-            "text.note" => moonfly::CRANBERRY,
-            "type" => moonfly::EMERALD,
-            "type.builtin" => moonfly::EMERALD,
-            "type.qualifier" => moonfly::EMERALD,
-            "type.definition" => moonfly::EMERALD,
+        use Group::Color as S;
+        use Group::Style as F;
+
+        let mut groups = HashMap::new();
+        set_many!(groups;
+            "comment" => F(Style::fg(moonfly::GREY246).italic()),
+            "annotation" => S(moonfly::VIOLET),
+            "attribute" => S(moonfly::SKY),
+            "constant" => S(moonfly::TURQUOISE),
+            "constant.builtin" => S(moonfly::GREEN),
+            "constant.macro" => S(moonfly::VIOLET),
+            "constructor" => S(moonfly::EMERALD),
+            "function.builtin" => S(moonfly::SKY),
+            "function.macro" => S(moonfly::SKY),
+            "include" => S(moonfly::CRANBERRY),
+            "keyword.operator" => S(moonfly::VIOLET),
+            "namespace" => S(moonfly::TURQUOISE),
+            "parameter" => S(moonfly::WHITE),
+            "punctuation.special" => S(moonfly::CRANBERRY),
+            "symbol" => S(moonfly::PURPLE),
+            "tag" => S(moonfly::BLUE),
+            "tag.delimiter" => S(moonfly::LIME),
+            "variable.builtin" => S(moonfly::LIME),
+            "string" => S(moonfly::KHAKI),
+            "number" => S(moonfly::ORANGE),
+            "label" => S(moonfly::TURQUOISE),
+            "boolean" => S(moonfly::CORAL),
+            "character" => S(moonfly::PURPLE),
+            "character.special" => S(moonfly::CRANBERRY),
+            "conditional" => S(moonfly::VIOLET),
+            "keyword.conditional" => S(moonfly::VIOLET),
+            "debug" => S(moonfly::CRANBERRY),
+            "define" => S(moonfly::CRANBERRY),
+            "error" => S(moonfly::RED),
+            "exception" => S(moonfly::CRIMSON),
+            "field" => S(moonfly::TURQUOISE),
+            "float" => S(moonfly::ORANGE),
+            "function" => S(moonfly::SKY),
+            "function.call" => S(moonfly::SKY),
+            "keyword" => S(moonfly::VIOLET),
+            "keyword.function" => S(moonfly::VIOLET),
+            "keyword.return" => S(moonfly::VIOLET),
+            "method" => S(moonfly::SKY),
+            "method.call" => S(moonfly::SKY),
+            "operator" => S(moonfly::CRANBERRY),
+            "parameter.reference" => S(moonfly::WHITE),
+            "preproc" => S(moonfly::CRANBERRY),
+            "property" => S(moonfly::TURQUOISE),
+            "punctuation.delimiter" => S(moonfly::WHITE),
+            "punctuation.bracket" => S(moonfly::WHITE),
+            "repeat" => S(moonfly::VIOLET),
+            "storageclass" => S(moonfly::CORAL),
+            "string.regex" => S(moonfly::KHAKI),
+            "string.escape" => S(moonfly::CRANBERRY),
+            "string.special" => S(moonfly::CRANBERRY),
+            "tag.attribute" => S(moonfly::TURQUOISE),
+            "title" => S(moonfly::ORANGE),
+            "text.literal" => S(moonfly::KHAKI),
+            "text.math" => S(moonfly::CRANBERRY),
+            "text.reference" => S(moonfly::ORANGE),
+            "text.environment" => S(moonfly::CRANBERRY),
+            "text.environment.name" => S(moonfly::EMERALD),
+            "text.note" => S(moonfly::CRANBERRY),
+            "type" => S(moonfly::EMERALD),
+            "type.builtin" => S(moonfly::EMERALD),
+            "type.qualifier" => S(moonfly::EMERALD),
+            "type.definition" => S(moonfly::EMERALD),
         );
-        Color(colors)
+
+        let styles = groups
+            .keys()
+            .filter_map(|&name| Some((name, resolve_group(&groups, name)?)))
+            .collect();
+        Color(styles)
     });
 
     pub static MOONFLY: Lazy<super::Theme> = Lazy::new(|| (&*MOONFLY_COLORS).into());
     pub static TOKYO_NIGHT: Lazy<super::Theme> = Lazy::new(|| (&*TOKYO_NIGHT_COLORS).into());
+
+    /// Names of the builtin themes, usable with [`builtin`]
+    pub const BUILTIN: &[&str] = &["moonfly", "tokyo-night"];
+
+    /// Find a builtin theme by name
+    pub fn builtin(name: &str) -> Option<&'static super::Theme> {
+        match name {
+            "moonfly" => Some(&*MOONFLY),
+            "tokyo-night" => Some(&*TOKYO_NIGHT),
+            _ => None,
+        }
+    }
+}
+
+/// Grammar and queries of a builtin language
+struct Sources {
+    language: tree_sitter::Language,
+    highlights: &'static str,
+    injections: &'static str,
+    locals: &'static str,
 }
 
 mod hi_cfg {
     include!(concat!(env!("OUT_DIR"), "/ts_config.rs"));
 }
 
+static HI_CFGS: Lazy<HashMap<&'static str, HighlightConfiguration>> = Lazy::new(|| {
+    hi_cfg::SOURCES
+        .iter()
+        .map(|(&name, sources)| {
+            let mut cfg = HighlightConfiguration::new(
+                sources.language.clone(),
+                name,
+                sources.highlights,
+                sources.injections,
+                sources.locals,
+            )
+            .unwrap_or_else(|e| panic!("Could not load language {name}: {e}"));
+            cfg.configure(HIGHLIGHT_NAMES);
+            (name, cfg)
+        })
+        .collect()
+});
+
+/// A language to register at runtime in a [`SyntaxConfig`]
+pub struct LanguageDef {
+    pub name: String,
+    pub language: tree_sitter::Language,
+    pub highlights: String,
+    pub injections: String,
+    pub locals: String,
+}
+
+/// Common names of the languages in the info strings of code blocks, by canonical name
+const BUILTIN_ALIASES: &[(&str, &str)] = &[
+    ("js", "javascript"),
+    ("mjs", "javascript"),
+    ("cjs", "javascript"),
+    ("rs", "rust"),
+    ("yml", "yaml"),
+    ("ld", "linkerscript"),
+    ("dts", "devicetree"),
+    ("dtsi", "devicetree"),
+    ("s", "asm"),
+    ("sh", "bash"),
+    ("shell", "bash"),
+    ("zsh", "bash"),
+];
+
+/// Names of the languages compiled in the crate (selected through cargo features)
+pub fn builtin_languages() -> impl Iterator<Item = &'static str> {
+    hi_cfg::SOURCES.keys().copied()
+}
+
 pub struct SyntaxConfig<'t> {
     configs: &'static HashMap<&'static str, HighlightConfiguration>,
+    /// Languages registered at runtime, they take precedence over the builtin ones
+    runtime: HashMap<String, HighlightConfiguration>,
+    /// Canonical name of the languages by alias (`js` for `javascript`)
+    aliases: HashMap<String, String>,
     theme: &'t Theme,
+    output: Output,
+    /// Attributes of the span for each highlight
+    attributes: Vec<String>,
 }
 
 impl<'t> SyntaxConfig<'t> {
     pub fn new(theme: &'t Theme) -> Self {
+        Self::with_output(theme, Output::Inline)
+    }
+
+    pub fn with_output(theme: &'t Theme, output: Output) -> Self {
+        let attributes = HIGHLIGHT_NAMES
+            .iter()
+            .map(|&name| match output {
+                Output::Inline => theme
+                    .0
+                    .get(name)
+                    .and_then(Style::to_css)
+                    .map(|style| format!(r#"style="{style}""#))
+                    .unwrap_or_default(),
+                Output::Classes => format!(r#"class="{}""#, class_name(name)),
+            })
+            .collect();
+
         Self {
-            configs: &*hi_cfg::HI_CFGS,
+            configs: &*HI_CFGS,
+            runtime: HashMap::new(),
+            aliases: BUILTIN_ALIASES
+                .iter()
+                .map(|&(alias, name)| (alias.to_owned(), name.to_owned()))
+                .collect(),
             theme,
+            output,
+            attributes,
+        }
+    }
+
+    /// Register additional languages, replacing builtin languages of the same name
+    pub fn with_languages(mut self, languages: &[LanguageDef]) -> Result<Self, Error> {
+        for def in languages {
+            let mut cfg = HighlightConfiguration::new(
+                def.language.clone(),
+                &def.name,
+                &def.highlights,
+                &def.injections,
+                &def.locals,
+            )
+            .map_err(|e| Error::Query(def.name.clone(), e))?;
+            cfg.configure(HIGHLIGHT_NAMES);
+
+            self.runtime.insert(def.name.clone(), cfg);
+        }
+
+        Ok(self)
+    }
+
+    /// Replace the highlights query of the builtin language `name`.
+    ///
+    /// If the query starts with a `; extends` line (like in nvim-treesitter), it extends the
+    /// builtin query instead, its patterns taking precedence over the builtin ones.
+    pub fn with_highlights(mut self, name: &str, highlights: &str) -> Result<Self, Error> {
+        let sources = hi_cfg::SOURCES
+            .get(name)
+            .ok_or_else(|| Error::UnknownLanguage(name.to_owned()))?;
+
+        let extends = highlights
+            .lines()
+            .next()
+            .is_some_and(|line| line.trim_start_matches(';').trim() == "extends");
+        // The first pattern matching a node gives its highlight
+        let highlights = match extends {
+            true => Cow::Owned(format!("{highlights}\n{}", sources.highlights)),
+            false => Cow::Borrowed(highlights),
+        };
+
+        let mut cfg = HighlightConfiguration::new(
+            sources.language.clone(),
+            name,
+            &highlights,
+            sources.injections,
+            sources.locals,
+        )
+        .map_err(|e| Error::Query(name.to_owned(), e))?;
+        cfg.configure(HIGHLIGHT_NAMES);
+
+        self.runtime.insert(name.to_owned(), cfg);
+        Ok(self)
+    }
+
+    /// Register additional aliases of languages (`("shell", "bash")`), replacing the builtin
+    /// aliases of the same name
+    pub fn with_aliases<'a>(
+        mut self,
+        aliases: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Self {
+        self.aliases.extend(
+            aliases
+                .into_iter()
+                .map(|(alias, name)| (alias.to_owned(), name.to_owned())),
+        );
+        self
+    }
+
+    fn language(&self, name: &str) -> Option<&HighlightConfiguration> {
+        let registered = |name: &str| self.runtime.get(name).or_else(|| self.configs.get(name));
+        registered(name).or_else(|| registered(self.aliases.get(name)?))
+    }
+
+    /// Whether the code in the language `name` is highlighted
+    pub fn has_language(&self, name: &str) -> bool {
+        match diff_base(name) {
+            Some(base) => base.is_empty() || self.language(base).is_some(),
+            None => self.language(name).is_some(),
+        }
+    }
+
+    pub fn theme(&self) -> &Theme {
+        self.theme
+    }
+
+    pub fn output(&self) -> Output {
+        self.output
+    }
+}
+
+/// Options for [`Renderer::render_lines`]
+#[derive(Debug, Default, Clone)]
+pub struct LineOptions {
+    /// Prefix each line with its number, in a `<span class="line-number">`
+    pub numbers: bool,
+    /// Lines (starting at 1) to emphasize with the `highlighted` class
+    pub highlighted: Vec<RangeInclusive<usize>>,
+}
+
+/// Language of the lines of a diff (`diff-rust` is a diff of rust code), empty for a plain `diff`
+fn diff_base(language: &str) -> Option<&str> {
+    match language.strip_prefix("diff")? {
+        "" => Some(""),
+        rest => rest.strip_prefix('-'),
+    }
+}
+
+fn escape_into(escaped: &mut Vec<u8>, text: &str) {
+    escaped.reserve(text.len());
+    for b in text.bytes() {
+        match b {
+            b'&' => escaped.extend_from_slice(b"&amp;"),
+            b'<' => escaped.extend_from_slice(b"&lt;"),
+            b'>' => escaped.extend_from_slice(b"&gt;"),
+            b'"' => escaped.extend_from_slice(b"&quot;"),
+            b'\'' => escaped.extend_from_slice(b"&#39;"),
+            _ => escaped.push(b),
+        }
+    }
+}
+
+/// Piece of code with the same highlight, given by [`Renderer::spans`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span<'t> {
+    pub text: &'t str,
+    /// Innermost highlight name of the text (one of [`HIGHLIGHT_NAMES`]), `None` for plain text
+    pub highlight: Option<&'static str>,
+}
+
+type Events<'r> =
+    Box<dyn Iterator<Item = Result<HighlightEvent, tree_sitter_highlight::Error>> + 'r>;
+
+/// Iterator over the [`Span`]s of some code
+pub struct Spans<'r> {
+    text: &'r str,
+    /// Events of the highlighter, `None` if the language is unknown
+    events: Option<Events<'r>>,
+    /// Highlights started and not ended yet, the innermost one last
+    stack: Vec<Highlight>,
+}
+
+impl<'r> Iterator for Spans<'r> {
+    type Item = Result<Span<'r>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Some(events) = &mut self.events else {
+            let text = std::mem::take(&mut self.text);
+            return (!text.is_empty()).then_some(Ok(Span {
+                text,
+                highlight: None,
+            }));
+        };
+
+        loop {
+            match events.next()? {
+                Err(e) => return Some(Err(e.into())),
+                Ok(HighlightEvent::HighlightStart(highlight)) => self.stack.push(highlight),
+                Ok(HighlightEvent::HighlightEnd) => {
+                    self.stack.pop();
+                }
+                Ok(HighlightEvent::Source { start, end }) if start < end => {
+                    return Some(Ok(Span {
+                        text: &self.text[start..end],
+                        highlight: self
+                            .stack
+                            .last()
+                            .map(|highlight| HIGHLIGHT_NAMES[highlight.0]),
+                    }))
+                }
+                Ok(HighlightEvent::Source { .. }) => (),
+            }
         }
     }
 }
@@ -517,6 +1049,7 @@ pub struct Renderer<'a> {
     config: &'a SyntaxConfig<'a>,
     highlighter: Highlighter,
     ts_render: HtmlRenderer,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl<'a> Renderer<'a> {
@@ -525,33 +1058,184 @@ impl<'a> Renderer<'a> {
             config,
             highlighter: Highlighter::new(),
             ts_render: HtmlRenderer::new(),
+            diagnostics: Vec::new(),
         }
     }
 
+    /// Problems found since the last call, by the renders that still succeeded
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
     pub fn render(&mut self, language: &str, text: &str) -> Result<Vec<u8>, Error> {
-        let events = match self.config.configs.get(language) {
+        let mut output = Vec::new();
+        self.render_to(language, text, &mut output)?;
+        Ok(output)
+    }
+
+    /// Same as [`Renderer::render`], writing directly in `output`
+    pub fn render_to(
+        &mut self,
+        language: &str,
+        text: &str,
+        output: &mut impl Write,
+    ) -> Result<(), Error> {
+        let rendered = self.render_ref(language, text)?;
+        output.write_all(rendered)?;
+        Ok(())
+    }
+
+    /// Highlight the code as [`Span`]s, for callers writing their own markup.
+    ///
+    /// Code in an unknown language is a single span of plain text.
+    pub fn spans<'r>(&'r mut self, language: &str, text: &'r str) -> Result<Spans<'r>, Error> {
+        let config = self.config;
+        let events = match config.language(language) {
             None => {
-                println!("[WARNING] `{language}` was not recognized, skipping highlight");
-                return Ok(text.as_bytes().into());
+                self.diagnostics
+                    .push(Diagnostic::UnknownLanguage(language.to_owned()));
+                None
+            }
+            Some(cfg) => {
+                let events =
+                    self.highlighter
+                        .highlight(cfg, text.as_bytes(), None, move |injected| {
+                            config.language(injected)
+                        })?;
+                Some(Box::new(events) as Events)
             }
-            Some(cfg) => self
-                .highlighter
-                .highlight(cfg, text.as_bytes(), None, |_| None)?,
         };
 
+        Ok(Spans {
+            text,
+            events,
+            stack: Vec::new(),
+        })
+    }
+
+    /// Render the code, the result borrows the buffer of the renderer that is reused between
+    /// calls
+    fn render_ref(&mut self, language: &str, text: &str) -> Result<&[u8], Error> {
+        let config = self.config;
         self.ts_render.reset();
 
-        self.ts_render
-            .render(events, text.as_bytes(), &|hi| match self
-                .config
-                .theme
-                .0
-                .get(HIGHLIGHT_NAMES[hi.0])
-            {
-                Some(style) => style.as_bytes(),
-                None => "".as_bytes(),
-            })?;
+        let events = match config.language(language) {
+            None => {
+                self.diagnostics
+                    .push(Diagnostic::UnknownLanguage(language.to_owned()));
+                escape_into(&mut self.ts_render.html, text);
+                return Ok(&self.ts_render.html);
+            }
+            // Injected languages (e.g. javascript in HTML) are resolved against the same set of
+            // languages, unknown injected languages are left unhighlighted
+            Some(cfg) => {
+                self.highlighter
+                    .highlight(cfg, text.as_bytes(), None, move |injected| {
+                        config.language(injected)
+                    })?
+            }
+        };
 
-        Ok(mem::take(&mut self.ts_render.html))
+        self.ts_render.render(events, text.as_bytes(), &|hi| {
+            config.attributes[hi.0].as_bytes()
+        })?;
+
+        Ok(&self.ts_render.html)
     }
+
+    /// Render the code, wrapping each line in a `<span class="newline">`.
+    ///
+    /// In a `diff` (or `diff-<language>` to highlight the code of the diff), the lines starting
+    /// with `+` or `-` also get the `added` or `removed` class.
+    pub fn render_lines(
+        &mut self,
+        language: &str,
+        text: &str,
+        options: &LineOptions,
+    ) -> Result<Vec<u8>, Error> {
+        let mut output = Vec::new();
+        self.render_lines_to(language, text, options, &mut output)?;
+        Ok(output)
+    }
+
+    /// Same as [`Renderer::render_lines`], writing directly in `output` to avoid buffering the
+    /// whole code block twice
+    pub fn render_lines_to(
+        &mut self,
+        language: &str,
+        text: &str,
+        options: &LineOptions,
+        output: &mut impl Write,
+    ) -> Result<(), Error> {
+        let Some(base) = diff_base(language) else {
+            let rendered = self.render_ref(language, text)?;
+            return write_lines(rendered, &[], options, output);
+        };
+
+        // The markers are removed from the code, so that the rest of the lines is highlighted
+        let mut markers = Vec::new();
+        let mut code = String::with_capacity(text.len());
+        for line in text.lines() {
+            let (marker, rest) = match line.as_bytes().first() {
+                Some(b'+' | b'-' | b' ') => line.split_at(1),
+                _ => ("", line),
+            };
+            markers.push(marker);
+            code.push_str(rest);
+            code.push('\n');
+        }
+
+        if base.is_empty() {
+            let mut escaped = Vec::new();
+            escape_into(&mut escaped, &code);
+            write_lines(&escaped, &markers, options, output)
+        } else {
+            let rendered = self.render_ref(base, &code)?;
+            write_lines(rendered, &markers, options, output)
+        }
+    }
+}
+
+/// Write the `rendered` lines of a code block, each prefixed by its diff marker if any
+fn write_lines(
+    rendered: &[u8],
+    markers: &[&str],
+    options: &LineOptions,
+    output: &mut impl Write,
+) -> Result<(), Error> {
+    if rendered.is_empty() {
+        return Ok(());
+    }
+
+    let rendered = rendered.strip_suffix(b"\n").unwrap_or(rendered);
+
+    for (line, content) in rendered.split(|&b| b == b'\n').enumerate() {
+        let marker = markers.get(line).copied().unwrap_or_default();
+        let line = line + 1;
+
+        output.write_all(br#"<span class="newline"#)?;
+        match marker {
+            "+" => output.write_all(b" added")?,
+            "-" => output.write_all(b" removed")?,
+            _ => (),
+        }
+        if options
+            .highlighted
+            .iter()
+            .any(|range| range.contains(&line))
+        {
+            output.write_all(b" highlighted")?;
+        }
+        output.write_all(b"\">")?;
+
+        if options.numbers {
+            write!(output, r#"<span class="line-number">{line}</span>"#)?;
+        }
+
+        output.write_all(marker.as_bytes())?;
+        output.write_all(content)?;
+        output.write_all(b"\n</span>")?;
+    }
+
+    Ok(())
 }
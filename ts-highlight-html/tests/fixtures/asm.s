@@ -0,0 +1,6 @@
+    .globl _start
+    .text
+_start:
+    mov $60, %rax       # exit
+    xor %rdi, %rdi
+    syscall
@@ -0,0 +1,11 @@
+// Reload the page when the server asks for it
+const ws = new WebSocket("ws://localhost:4111");
+ws.onmessage = function (event) {
+  if (event.data === "refresh" && !document.hidden) {
+    document.location.reload();
+  }
+};
+export default class Counter {
+  #count = 0;
+  increment() { return ++this.#count; }
+}
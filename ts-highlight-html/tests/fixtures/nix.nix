@@ -0,0 +1,12 @@
+{ pkgs ? import <nixpkgs> { } }:
+let
+  name = "verin";
+in
+pkgs.mkShell {
+  # Development shell
+  inherit name;
+  nativeBuildInputs = with pkgs; [ cargo rustc ];
+  shellHook = ''
+    echo "Entering ${name}"
+  '';
+}
@@ -0,0 +1,12 @@
+use std::collections::HashMap;
+
+/// Count the words of `text`
+#[inline]
+pub fn count<'a>(text: &'a str) -> HashMap<&'a str, usize> {
+    let mut counts = HashMap::new();
+    for word in text.split_whitespace() {
+        *counts.entry(word).or_insert(0) += 1; // "quoted" & <escaped>
+    }
+    println!("{} words", counts.len());
+    counts
+}
@@ -0,0 +1,159 @@
+//! Snapshots of the highlighting of the fixtures, for each builtin language and theme.
+//!
+//! Changes to a grammar, a query or a theme show up as snapshot diffs, to be reviewed with
+//! `cargo insta review`.
+
+use std::path::Path;
+
+use ts_highlight_html::{
+    builtin_languages, theme, LineOptions, Output, Renderer, Span, SyntaxConfig, Theme,
+};
+
+/// Fixtures are named after their language, with any extension
+fn language(fixture: &Path) -> &str {
+    fixture
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .expect("invalid fixture name")
+}
+
+#[test]
+fn every_language_has_a_fixture() {
+    let fixtures: Vec<_> =
+        std::fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures"))
+            .expect("could not read fixtures")
+            .map(|entry| entry.expect("could not read fixture").path())
+            .collect();
+
+    for builtin in builtin_languages() {
+        assert!(
+            fixtures.iter().any(|fixture| language(fixture) == builtin),
+            "no fixture for `{builtin}`"
+        );
+    }
+}
+
+#[test]
+fn highlight() {
+    insta::glob!("fixtures/*", |path| {
+        let language = language(path);
+        // The language is not compiled in
+        if !builtin_languages().any(|builtin| builtin == language) {
+            return;
+        }
+
+        let code = std::fs::read_to_string(path).expect("could not read fixture");
+
+        for &name in theme::BUILTIN {
+            let theme = theme::builtin(name).expect("missing builtin theme");
+            let config = SyntaxConfig::new(theme);
+            let html = Renderer::new(&config)
+                .render(language, &code)
+                .expect("could not highlight");
+
+            insta::assert_snapshot!(
+                format!("inline-{name}"),
+                String::from_utf8(html).expect("highlight is not UTF-8")
+            );
+        }
+
+        // Classes do not depend on the theme, render the lines as in code blocks instead
+        let config = SyntaxConfig::with_output(&theme::TOKYO_NIGHT, Output::Classes);
+        let options = LineOptions {
+            numbers: true,
+            highlighted: vec![2..=3],
+        };
+        let html = Renderer::new(&config)
+            .render_lines(language, &code, &options)
+            .expect("could not highlight");
+
+        insta::assert_snapshot!(
+            "classes",
+            String::from_utf8(html).expect("highlight is not UTF-8")
+        );
+    });
+}
+
+#[test]
+fn spans_cover_the_code() {
+    if !builtin_languages().any(|builtin| builtin == "rust") {
+        return;
+    }
+    let config = SyntaxConfig::new(&theme::TOKYO_NIGHT);
+    let mut renderer = Renderer::new(&config);
+
+    let code = "fn main() {\n    let s = \"é\";\n}\n";
+    let spans = renderer
+        .spans("rust", code)
+        .expect("could not highlight")
+        .collect::<Result<Vec<_>, _>>()
+        .expect("could not highlight");
+    assert_eq!(spans.iter().map(|span| span.text).collect::<String>(), code);
+    assert!(spans.iter().any(|span| span.highlight.is_some()));
+
+    let spans = renderer
+        .spans("brainfuck", "+.")
+        .expect("could not highlight")
+        .collect::<Result<Vec<_>, _>>()
+        .expect("could not highlight");
+    assert_eq!(
+        spans,
+        [Span {
+            text: "+.",
+            highlight: None
+        }]
+    );
+}
+
+#[test]
+fn theme_stylesheets() {
+    for &name in theme::BUILTIN {
+        let theme = theme::builtin(name).expect("missing builtin theme");
+        insta::assert_snapshot!(format!("css-{name}"), theme.to_css());
+    }
+}
+
+#[test]
+fn dual_theme_stylesheet() {
+    let light = Theme::from_toml_str(
+        r##"
+        keyword = { color = "#000000", bold = true }
+        comment = "#555555"
+        "##,
+    )
+    .expect("invalid light theme");
+    let dark = Theme::from_toml_str(
+        r##"
+        keyword = "#ffffff"
+        string = { color = "#00ff00", italic = true }
+        "##,
+    )
+    .expect("invalid dark theme");
+
+    // The properties set by only one of the themes are reset by the other one
+    assert_eq!(
+        light.to_dual_css(&dark),
+        r#":root {
+	--hl-comment-color: #555555;
+	--hl-keyword-color: #000000;
+	--hl-keyword-font-weight: bold;
+	--hl-string-color: initial;
+	--hl-string-font-style: initial;
+}
+
+@media (prefers-color-scheme: dark) {
+	:root {
+		--hl-comment-color: initial;
+		--hl-keyword-color: #ffffff;
+		--hl-keyword-font-weight: initial;
+		--hl-string-color: #00ff00;
+		--hl-string-font-style: italic;
+	}
+}
+
+.hl-comment { color: var(--hl-comment-color); }
+.hl-keyword { color: var(--hl-keyword-color); font-weight: var(--hl-keyword-font-weight); }
+.hl-string { color: var(--hl-string-color); font-style: var(--hl-string-font-style); }
+"#
+    );
+}
@@ -0,0 +1,26 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ts_highlight_html::{theme, SyntaxConfig};
+use verin::{
+    html::{MathOutput, RenderOptions},
+    html_check::check_markdown,
+};
+
+fuzz_target!(|input: (bool, bool, bool, &str)| {
+    let (heading_anchors, sections, math_source, markdown) = input;
+    let math = match math_source {
+        true => MathOutput::Source,
+        false => MathOutput::MathMl,
+    };
+    check_markdown(
+        markdown,
+        &SyntaxConfig::new(&theme::TOKYO_NIGHT),
+        RenderOptions {
+            heading_anchors,
+            sections,
+            math,
+            ..Default::default()
+        },
+    );
+});
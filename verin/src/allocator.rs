@@ -0,0 +1,225 @@
+//! Global allocator, counting the allocations to profile the builds.
+//!
+//! The underlying allocator can be changed with the `mimalloc` or `jemalloc` features, mimalloc
+//! being used if both are enabled. The allocations are only counted once a [`Profiler`] is enabled.
+
+use std::{
+    alloc::{GlobalAlloc, Layout},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use crate::shutdown;
+
+#[cfg(feature = "mimalloc")]
+static INNER: mimalloc::MiMalloc = mimalloc::MiMalloc;
+// mimalloc takes precedence when both features are enabled (as with `--all-features`)
+#[cfg(all(feature = "jemalloc", not(feature = "mimalloc")))]
+static INNER: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+#[cfg(not(any(feature = "mimalloc", feature = "jemalloc")))]
+static INNER: std::alloc::System = std::alloc::System;
+
+/// Whether the allocations are counted, set by [`Profiler::new`]
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+fn record_alloc(size: usize) {
+    if !RECORDING.load(Ordering::Relaxed) {
+        return;
+    }
+
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ALLOCATED.fetch_add(size, Ordering::Relaxed);
+    let current = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK.fetch_max(current, Ordering::Relaxed);
+}
+
+fn record_dealloc(size: usize) {
+    if !RECORDING.load(Ordering::Relaxed) {
+        return;
+    }
+
+    // The memory may have been allocated before the recording started
+    let _ = CURRENT.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+        Some(current.saturating_sub(size))
+    });
+}
+
+struct Counting;
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = INNER.alloc(layout);
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = INNER.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        INNER.dealloc(ptr, layout);
+        record_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = INNER.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            record_dealloc(layout.size());
+            record_alloc(new_size);
+        }
+        new
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Snapshot {
+    allocations: usize,
+    allocated: usize,
+}
+
+impl Snapshot {
+    fn now() -> Self {
+        Self {
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            allocated: ALLOCATED.load(Ordering::Relaxed),
+        }
+    }
+}
+
+struct Phase {
+    name: &'static str,
+    allocations: usize,
+    allocated: usize,
+    peak: usize,
+    duration: std::time::Duration,
+}
+
+/// Record the allocations made during each phase of a build, each phase also being a `tracing`
+/// span of the flamegraph
+pub struct Profiler {
+    enabled: bool,
+    phases: Vec<Phase>,
+    /// Name and span of the running phase
+    current: Option<(&'static str, tracing::span::EnteredSpan)>,
+    last: Snapshot,
+    start: Instant,
+}
+
+impl Profiler {
+    pub fn new(enabled: bool) -> Self {
+        RECORDING.store(enabled, Ordering::Relaxed);
+        PEAK.store(CURRENT.load(Ordering::Relaxed), Ordering::Relaxed);
+
+        Self {
+            enabled,
+            phases: Vec::new(),
+            current: None,
+            last: Snapshot::now(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Start the phase `name`, ending the running one.
+    ///
+    /// Fails if the build was interrupted, so that it stops between two phases.
+    pub fn phase(&mut self, name: &'static str) -> color_eyre::Result<()> {
+        self.end_phase();
+        shutdown::check()?;
+        self.current = Some((name, tracing::info_span!("phase", name).entered()));
+        Ok(())
+    }
+
+    fn end_phase(&mut self) {
+        let Some((name, span)) = self.current.take() else {
+            return;
+        };
+        drop(span);
+        if !self.enabled {
+            return;
+        }
+
+        let now = Snapshot::now();
+        self.phases.push(Phase {
+            name,
+            allocations: now.allocations - self.last.allocations,
+            allocated: now.allocated - self.last.allocated,
+            peak: PEAK.swap(CURRENT.load(Ordering::Relaxed), Ordering::Relaxed),
+            duration: self.start.elapsed(),
+        });
+
+        self.last = now;
+        self.start = Instant::now();
+    }
+
+    /// End the running phase, and print the profile
+    pub fn report(&mut self) {
+        self.end_phase();
+        if !self.enabled {
+            return;
+        }
+
+        println!("Allocation profile:");
+        println!(
+            "  {:<12} {:>12} {:>14} {:>14} {:>10}",
+            "phase", "allocations", "allocated", "peak heap", "time"
+        );
+        for phase in &self.phases {
+            println!(
+                "  {:<12} {:>12} {:>14} {:>14} {:>8}ms",
+                phase.name,
+                phase.allocations,
+                human_size(phase.allocated),
+                human_size(phase.peak),
+                phase.duration.as_millis(),
+            );
+        }
+
+        match peak_rss() {
+            Some(rss) => println!("Peak RSS: {}", human_size(rss)),
+            None => println!("Peak RSS: unavailable on this platform"),
+        }
+    }
+}
+
+fn human_size(bytes: usize) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024. && unit < UNITS.len() - 1 {
+        size /= 1024.;
+        unit += 1;
+    }
+
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Peak resident set size of the process, in bytes
+fn peak_rss() -> Option<usize> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kib: usize = line
+        .trim_start_matches("VmHWM:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+
+    Some(kib * 1024)
+}
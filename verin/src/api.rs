@@ -0,0 +1,325 @@
+//! JSON-RPC server exposing the site to editors.
+//!
+//! Messages use the framing of the Language Server Protocol (a `Content-Length` header followed by
+//! the JSON payload) on stdin/stdout.
+
+use std::{
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{eyre::Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use ts_highlight_html::SyntaxConfig;
+
+use crate::{
+    parse_article, permalink, read_sources, render_markdown, slug::slugify, url::SiteUrl, Config,
+    Metadata,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Deserialize, Debug)]
+struct Request {
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+struct SiteArticle {
+    /// Path of the source, relative to the input directory
+    source: PathBuf,
+    /// Path of the page, relative to the output directory
+    page: PathBuf,
+    metadata: Metadata,
+}
+
+/// In memory model of the site
+struct Site {
+    input: PathBuf,
+    config: Config,
+    articles: Vec<SiteArticle>,
+}
+
+impl Site {
+    fn load(input: &Path) -> Result<Self> {
+        let config = Config::load(input)?;
+
+        let articles = read_sources(input)?
+            .into_iter()
+            .map(|(source, content)| {
+                let (metadata, _) = parse_article(&content)
+                    .with_context(|| format!("Could not parse {}", source.display()))?;
+                let page = permalink::article_path(&config, &source, &metadata)?;
+                Ok(SiteArticle {
+                    source,
+                    page,
+                    metadata,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            input: input.to_owned(),
+            config,
+            articles,
+        })
+    }
+
+    fn describe(&self, article: &SiteArticle) -> Value {
+        json!({
+            "title": article.metadata.title,
+            "date": article.metadata.date.to_string(),
+            "summary": article.metadata.summary,
+            "tags": article.metadata.tags,
+            "draft": article.metadata.draft,
+            "source": self.input.join(&article.source),
+            "url": SiteUrl::from_page(&article.page),
+        })
+    }
+
+    /// Find the article designated by a wiki-link, either by its file name or by its title
+    fn resolve(&self, name: &str) -> Option<&SiteArticle> {
+        let slug = slugify(name);
+
+        self.articles
+            .iter()
+            .find(|article| article.source.with_extension("") == Path::new(name))
+            .or_else(|| {
+                self.articles.iter().find(|article| {
+                    article.source.file_stem().and_then(|s| s.to_str()) == Some(name)
+                        || slugify(&article.metadata.title) == slug
+                })
+            })
+    }
+}
+
+fn error_response(id: Value, code: i64, message: impl std::fmt::Display) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message.to_string() },
+    })
+}
+
+/// Read the next message, or the error response to send back if it is not a valid request.
+///
+/// Returns `None` once the input is closed.
+fn read_message(input: &mut impl BufRead) -> Result<Option<Result<Request, Value>>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = Some(value.trim().parse::<usize>());
+            }
+        }
+    }
+
+    let length = match length {
+        Some(Ok(length)) => length,
+        Some(Err(e)) => {
+            let error = error_response(
+                Value::Null,
+                PARSE_ERROR,
+                format!("Invalid Content-Length: {e}"),
+            );
+            return Ok(Some(Err(error)));
+        }
+        None => {
+            let error = error_response(Value::Null, PARSE_ERROR, "Missing Content-Length header");
+            return Ok(Some(Err(error)));
+        }
+    };
+    let mut payload = vec![0; length];
+    input.read_exact(&mut payload)?;
+
+    let message: Value = match serde_json::from_slice(&payload) {
+        Ok(message) => message,
+        Err(e) => return Ok(Some(Err(error_response(Value::Null, PARSE_ERROR, e)))),
+    };
+    let id = message.get("id").cloned().unwrap_or(Value::Null);
+    Ok(Some(
+        serde_json::from_value(message).map_err(|e| error_response(id, INVALID_REQUEST, e)),
+    ))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> Result<()> {
+    let payload = serde_json::to_vec(message)?;
+    write!(output, "Content-Length: {}\r\n\r\n", payload.len())?;
+    output.write_all(&payload)?;
+    output.flush()?;
+    Ok(())
+}
+
+fn string_param<'a>(params: &'a Value, name: &str) -> Result<&'a str, (i64, String)> {
+    params
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| (INVALID_PARAMS, format!("missing string parameter `{name}`")))
+}
+
+fn handle(
+    site: &mut Site,
+    syntax_conf: &SyntaxConfig,
+    request: &Request,
+) -> Result<Value, (i64, String)> {
+    match request.method.as_str() {
+        "articles/list" => Ok(Value::Array(
+            site.articles
+                .iter()
+                .map(|article| site.describe(article))
+                .collect(),
+        )),
+        "articles/resolve" => {
+            let name = string_param(&request.params, "name")?;
+            Ok(site
+                .resolve(name)
+                .map(|article| site.describe(article))
+                .unwrap_or(Value::Null))
+        }
+        "articles/preview" => {
+            let text = string_param(&request.params, "text")?;
+            let (metadata, body) =
+                parse_article(text).map_err(|e| (INVALID_PARAMS, format!("{e:#}")))?;
+            let (content, rendered) =
+                render_markdown(body, syntax_conf, metadata.render_options(&site.config))
+                    .map_err(|e| (INTERNAL_ERROR, format!("{e:#}")))?;
+
+            Ok(json!({
+                "title": metadata.title,
+                "content": content,
+                "headers": rendered.headers,
+                "warnings": rendered
+                    .diagnostics()
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+            }))
+        }
+        "site/reload" => {
+            *site = Site::load(&site.input).map_err(|e| (INTERNAL_ERROR, format!("{e:#}")))?;
+            Ok(Value::Null)
+        }
+        "site/config" => Ok(json!({
+            "name": site.config.name,
+        })),
+        method => Err((METHOD_NOT_FOUND, format!("unknown method `{method}`"))),
+    }
+}
+
+/// Serve requests on stdin until the `exit` notification is received or stdin is closed
+pub fn serve(input: &Path) -> Result<()> {
+    let mut site = Site::load(input)?;
+    let syntax_conf = site.config.highlight.syntax(input)?;
+
+    let mut stdin = std::io::stdin().lock();
+    let mut stdout = std::io::stdout().lock();
+
+    while let Some(message) = read_message(&mut stdin)? {
+        let request = match message {
+            Ok(request) => request,
+            Err(error) => {
+                write_message(&mut stdout, &error)?;
+                continue;
+            }
+        };
+
+        if request.method == "exit" {
+            break;
+        }
+
+        let result = handle(&mut site, &syntax_conf, &request);
+
+        // Notifications don't get a response
+        let Some(id) = request.id else {
+            continue;
+        };
+
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, message),
+        };
+
+        write_message(&mut stdout, &response)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(input: &str) -> Vec<Result<Request, Value>> {
+        let mut input = input.as_bytes();
+        std::iter::from_fn(|| read_message(&mut input).unwrap()).collect()
+    }
+
+    fn frame(payload: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{payload}", payload.len())
+    }
+
+    fn error_code(message: &Result<Request, Value>) -> &Value {
+        &message.as_ref().unwrap_err()["error"]["code"]
+    }
+
+    #[test]
+    fn requests() {
+        let messages = read(&format!(
+            "{}{}",
+            frame(r#"{"jsonrpc":"2.0","id":1,"method":"articles/list"}"#),
+            frame(r#"{"jsonrpc":"2.0","method":"exit"}"#),
+        ));
+
+        assert_eq!(messages.len(), 2);
+        let request = messages[0].as_ref().unwrap();
+        assert_eq!(request.id, Some(json!(1)));
+        assert_eq!(request.method, "articles/list");
+        assert_eq!(messages[1].as_ref().unwrap().id, None);
+    }
+
+    #[test]
+    fn invalid_messages_are_answered() {
+        let messages = read(&format!(
+            "{}{}{}",
+            frame("{not json"),
+            frame(r#"{"jsonrpc":"2.0","id":2,"params":{}}"#),
+            frame(r#"{"jsonrpc":"2.0","id":3,"method":"site/config"}"#),
+        ));
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(error_code(&messages[0]), &json!(PARSE_ERROR));
+        assert_eq!(error_code(&messages[1]), &json!(INVALID_REQUEST));
+        assert_eq!(messages[1].as_ref().unwrap_err()["id"], json!(2));
+        assert_eq!(messages[2].as_ref().unwrap().method, "site/config");
+    }
+
+    #[test]
+    fn invalid_length() {
+        let messages = read(&format!(
+            "Content-Length: many\r\n\r\n{}",
+            frame(r#"{"jsonrpc":"2.0","id":1,"method":"site/config"}"#)
+        ));
+
+        assert_eq!(error_code(&messages[0]), &json!(PARSE_ERROR));
+        assert_eq!(messages[1].as_ref().unwrap().method, "site/config");
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::OpenOptions,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{eyre::Context, Result};
+use glob::glob;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{links, url::SiteUrl};
+
+/// Number of characters of the hash inserted in the file names
+const FINGERPRINT_LEN: usize = 8;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct AssetsConfig {
+    /// Remove the assets that are not referenced by any page from the output
+    #[serde(default)]
+    pub prune_unused: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Asset {
+    /// Fingerprinted URL of the asset
+    pub url: String,
+    pub size: u64,
+    /// Hex encoded SHA-256 of the asset
+    pub hash: String,
+}
+
+/// Mapping from the logical name of an asset (its path in the `assets` directory) to its
+/// fingerprinted version
+pub type Manifest = BTreeMap<String, Asset>;
+
+/// Name of the file at `path` with the start of its `hash` (`style.0123abcd.css`)
+pub fn fingerprinted(path: &Path, hash: &str) -> PathBuf {
+    let fingerprint = &hash[..FINGERPRINT_LEN];
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(ext)) => path.with_file_name(format!(
+            "{}.{fingerprint}.{}",
+            stem.to_string_lossy(),
+            ext.to_string_lossy()
+        )),
+        _ => path.with_file_name(format!(
+            "{}.{fingerprint}",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        )),
+    }
+}
+
+/// Copy all the files in `<input>/assets` to `<output>/assets` with a fingerprint in their name
+///
+/// The manifest is also written to `<output>/assets.json`
+pub fn process(input: &Path, output: &Path) -> Result<Manifest> {
+    let source = input.join("assets");
+    let mut manifest = Manifest::new();
+
+    for entry in glob(&source.join("**/*").to_string_lossy())? {
+        let entry = entry?;
+        if !entry.is_file() {
+            continue;
+        }
+
+        let relative = entry
+            .strip_prefix(&source)
+            .context("asset is not in the asset directory")?;
+
+        let content = std::fs::read(&entry)
+            .with_context(|| format!("Could not read asset {}", entry.display()))?;
+        let hash = format!("{:x}", Sha256::digest(&content));
+
+        let destination = Path::new("assets").join(fingerprinted(relative, &hash));
+        if let Some(parent) = output.join(&destination).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(output.join(&destination), &content)
+            .with_context(|| format!("Could not write asset {}", destination.display()))?;
+
+        manifest.insert(
+            links::url_path(relative),
+            Asset {
+                url: SiteUrl::from_path(&destination).to_string(),
+                size: content.len() as u64,
+                hash,
+            },
+        );
+    }
+
+    write_manifest(output, &manifest)?;
+
+    Ok(manifest)
+}
+
+fn write_manifest(output: &Path, manifest: &Manifest) -> Result<()> {
+    let file = BufWriter::new(
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(output.join("assets.json"))
+            .context("Could not open asset manifest")?,
+    );
+    serde_json::to_writer_pretty(file, manifest)?;
+    Ok(())
+}
+
+/// Path of the `asset` in the output directory, normalized to be compared with the references
+fn output_path(output: &Path, asset: &Asset) -> Option<PathBuf> {
+    links::resolve_local(output, output, &asset.url).map(|path| links::normalize(&path))
+}
+
+/// Logical names of the assets of the `manifest` that are not referenced by any of the `pages`.
+///
+/// The stylesheets and scripts of the assets are searched as well, an asset being referenced if
+/// its file name (fingerprinted or not) appears in them, as they may load fonts or images.
+pub fn unused(output: &Path, pages: &[PathBuf], manifest: &Manifest) -> Result<Vec<String>> {
+    let mut referenced = HashSet::new();
+    for page in pages {
+        let content = std::fs::read_to_string(page)
+            .with_context(|| format!("Could not read page {}", page.display()))?;
+        referenced.extend(
+            links::references(&content)
+                .into_iter()
+                .filter_map(|url| links::resolve_local(output, page, url))
+                .map(|path| links::normalize(&path)),
+        );
+    }
+
+    let mut sources = Vec::new();
+    for (name, asset) in manifest {
+        if name.ends_with(".css") || name.ends_with(".js") {
+            let Some(path) = output_path(output, asset) else {
+                continue;
+            };
+            sources.push(
+                std::fs::read_to_string(&path)
+                    .with_context(|| format!("Could not read asset {name}"))?,
+            );
+        }
+    }
+
+    let file_name = |url: &str| url.rsplit('/').next().unwrap_or_default().to_owned();
+    Ok(manifest
+        .iter()
+        .filter(|(name, asset)| {
+            if output_path(output, asset).is_some_and(|path| referenced.contains(&path)) {
+                return false;
+            }
+
+            let names = [file_name(name), file_name(&asset.url)];
+            !sources
+                .iter()
+                .any(|source| names.iter().any(|name| source.contains(name.as_str())))
+        })
+        .map(|(name, _)| name.clone())
+        .collect())
+}
+
+/// Remove the `unused` assets from the output and from the `manifest`, rewriting `assets.json`
+pub fn prune(output: &Path, manifest: &mut Manifest, unused: &[String]) -> Result<()> {
+    for name in unused {
+        let Some(asset) = manifest.remove(name) else {
+            continue;
+        };
+        if let Some(path) = output_path(output, &asset) {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Could not remove asset {}", path.display()))?;
+        }
+    }
+
+    write_manifest(output, manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprints() {
+        let hash = "0123abcd4567";
+        assert_eq!(
+            fingerprinted(Path::new("css/style.css"), hash),
+            Path::new("css/style.0123abcd.css")
+        );
+        assert_eq!(
+            fingerprinted(Path::new("archive.tar.gz"), hash),
+            Path::new("archive.tar.0123abcd.gz")
+        );
+        assert_eq!(
+            fingerprinted(Path::new("LICENSE"), hash),
+            Path::new("LICENSE.0123abcd")
+        );
+        assert_eq!(
+            fingerprinted(Path::new(".nojekyll"), hash),
+            Path::new(".nojekyll.0123abcd")
+        );
+    }
+}
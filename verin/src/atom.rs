@@ -0,0 +1,170 @@
+use std::{cmp::Reverse, fs::OpenOptions, io::BufWriter, path::Path};
+
+use atom_syndication::{Category, Entry, Feed, FixedDateTime, Link, Person, Text};
+use chrono::NaiveDate;
+use color_eyre::{eyre::Context, Result};
+
+use crate::{url::SiteUrl, Article, ChannelData, Config};
+
+fn timestamp(date: NaiveDate) -> FixedDateTime {
+    date.and_time(Default::default()).and_utc().fixed_offset()
+}
+
+/// Atom 1.0 feed of the `articles`, using the metadata of the RSS channel
+fn feed(channel: &ChannelData, config: &Config, articles: &[Article]) -> Result<Feed> {
+    let mut entries = articles
+        .iter()
+        .map(|article| {
+            let date = timestamp(article.metadata.date(&config.date)?);
+            let updated = timestamp(article.metadata.updated(&config.date)?);
+            let url = SiteUrl::from_page(&article.path).with_base(&channel.link);
+            let link = article
+                .metadata
+                .canonical_url
+                .clone()
+                .unwrap_or_else(|| url.clone());
+
+            Ok(Entry {
+                title: Text::plain(article.metadata.title.clone()),
+                id: url,
+                updated,
+                published: Some(date),
+                summary: Some(Text::plain(article.metadata.summary.clone())),
+                links: vec![Link {
+                    href: link,
+                    ..Default::default()
+                }],
+                categories: article
+                    .metadata
+                    .tags
+                    .iter()
+                    .map(|tag| Category {
+                        term: tag.clone(),
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    entries.sort_unstable_by_key(|entry| Reverse(entry.updated));
+
+    Ok(Feed {
+        title: Text::plain(channel.title.clone()),
+        id: SiteUrl::root().with_base(&channel.link),
+        updated: entries
+            .first()
+            .map(|entry| entry.updated)
+            .unwrap_or_else(|| chrono::Utc::now().fixed_offset()),
+        authors: vec![Person {
+            name: channel
+                .managing_editor
+                .clone()
+                .unwrap_or_else(|| config.name.clone()),
+            ..Default::default()
+        }],
+        links: vec![
+            Link {
+                href: SiteUrl::root().with_base(&channel.link),
+                ..Default::default()
+            },
+            Link {
+                href: SiteUrl::root().join("atom.xml").with_base(&channel.link),
+                rel: "self".into(),
+                mime_type: Some("application/atom+xml".into()),
+                ..Default::default()
+            },
+        ],
+        subtitle: Some(Text::plain(channel.description.trim().to_owned())),
+        rights: channel.copyright.clone().map(Text::plain),
+        lang: channel.language.clone(),
+        entries,
+        ..Default::default()
+    })
+}
+
+/// Write an Atom 1.0 feed of the `articles` in `atom.xml`, using the metadata of the RSS channel
+pub fn write(
+    output: &Path,
+    channel: &ChannelData,
+    config: &Config,
+    articles: &[Article],
+) -> Result<()> {
+    let file = BufWriter::new(
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(output.join("atom.xml"))
+            .context("Could not open atom feed")?,
+    );
+    feed(channel, config, articles)?.write_to(file)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn article(path: &str, metadata: &str) -> Article {
+        Article {
+            metadata: toml::from_str(&format!(
+                "title = \"{path}\"\npage = \"article\"\nsummary = \"\"\ntags = [\"rust\"]\n{metadata}"
+            ))
+            .unwrap(),
+            path: path.into(),
+            rendered: Default::default(),
+        }
+    }
+
+    #[test]
+    fn entries() {
+        let config: Config = toml::from_str(
+            "name = \"Test\"\n[date]\ninput = \"%Y-%m-%d\"\noutput = \"%Y-%m-%d\"\n",
+        )
+        .unwrap();
+        let channel: ChannelData = toml::from_str(
+            "title = \"Blog\"\nlink = \"https://example.org/\"\ndescription = \"Posts \"\n",
+        )
+        .unwrap();
+        let articles = [
+            article(
+                "old.html",
+                "date = \"2023-01-01\"\nupdated = \"2024-06-01\"",
+            ),
+            article(
+                "new.html",
+                "date = \"2024-01-01\"\ncanonical_url = \"https://other.org/new\"",
+            ),
+        ];
+
+        let feed = feed(&channel, &config, &articles).unwrap();
+        assert_eq!(feed.id, "https://example.org/");
+        assert_eq!(feed.authors[0].name, "Test");
+        assert_eq!(feed.links[1].href, "https://example.org/atom.xml");
+        assert_eq!(feed.subtitle.unwrap().value, "Posts");
+        assert_eq!(
+            feed.updated,
+            timestamp(NaiveDate::from_ymd_opt(2024, 6, 1).unwrap())
+        );
+
+        // The recently updated articles come first
+        let entries: Vec<_> = feed
+            .entries
+            .iter()
+            .map(|entry| (entry.id.as_str(), entry.links[0].href.as_str()))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                (
+                    "https://example.org/old.html",
+                    "https://example.org/old.html"
+                ),
+                ("https://example.org/new.html", "https://other.org/new"),
+            ]
+        );
+        assert_eq!(feed.entries[0].categories[0].term, "rust");
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+use serde::Deserialize;
+
+use crate::links;
+
+#[derive(Deserialize, Debug)]
+pub struct BudgetConfig {
+    /// Maximum size (in bytes) of the HTML of a page
+    #[serde(default)]
+    html: Option<u64>,
+    /// Maximum size (in bytes) of a page along with all the local assets it references
+    #[serde(default)]
+    total: Option<u64>,
+    /// Number of offenders to display when a budget is exceeded
+    #[serde(default = "default_offenders")]
+    offenders: usize,
+}
+
+fn default_offenders() -> usize {
+    5
+}
+
+struct PageSize<'a> {
+    page: &'a Path,
+    html: u64,
+    total: u64,
+}
+
+/// Check the generated `pages` against the configured budgets.
+///
+/// Returns an error if a budget is exceeded and `strict` is set.
+pub fn check(output: &Path, pages: &[PathBuf], config: &BudgetConfig, strict: bool) -> Result<()> {
+    let mut sizes = Vec::with_capacity(pages.len());
+
+    for page in pages {
+        let content = std::fs::read_to_string(page)
+            .with_context(|| format!("Could not read page {}", page.display()))?;
+
+        let assets: HashSet<_> = links::references(&content)
+            .into_iter()
+            .filter_map(|url| links::resolve_local(output, page, url))
+            .filter(|path| path.is_file() && path.extension() != Some(OsStr::new("html")))
+            .collect();
+
+        let html = content.len() as u64;
+        let assets: u64 = assets
+            .iter()
+            .map(|asset| asset.metadata().map(|m| m.len()).unwrap_or(0))
+            .sum();
+
+        sizes.push(PageSize {
+            page: page.strip_prefix(output).unwrap_or(page),
+            html,
+            total: html + assets,
+        });
+    }
+
+    let html = report(
+        "HTML",
+        config.html,
+        &mut sizes,
+        |s| s.html,
+        config.offenders,
+    );
+    let total = report(
+        "total",
+        config.total,
+        &mut sizes,
+        |s| s.total,
+        config.offenders,
+    );
+
+    if (html || total) && strict {
+        eyre::bail!("Size budget exceeded");
+    }
+
+    Ok(())
+}
+
+/// Print the heaviest pages exceeding `budget`, returns if the budget was exceeded
+fn report(
+    kind: &str,
+    budget: Option<u64>,
+    sizes: &mut [PageSize],
+    size: impl Fn(&PageSize) -> u64,
+    offenders: usize,
+) -> bool {
+    let Some(budget) = budget else {
+        return false;
+    };
+
+    sizes.sort_unstable_by_key(|s| std::cmp::Reverse(size(s)));
+
+    let exceeding = sizes.iter().take_while(|s| size(s) > budget).count();
+    if exceeding == 0 {
+        return false;
+    }
+
+    println!("[WARNING] {exceeding} page(s) exceed the {kind} budget of {budget} bytes:");
+    for page in sizes.iter().take(exceeding.min(offenders)) {
+        println!("  {}: {} bytes", page.page.display(), size(page));
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budgets() {
+        let mut sizes: Vec<_> = [
+            ("a.html", 100, 1000),
+            ("b.html", 300, 400),
+            ("c.html", 200, 2000),
+        ]
+        .into_iter()
+        .map(|(page, html, total)| PageSize {
+            page: Path::new(page),
+            html,
+            total,
+        })
+        .collect();
+
+        assert!(!report("HTML", None, &mut sizes, |s| s.html, 5));
+        assert!(!report("HTML", Some(300), &mut sizes, |s| s.html, 5));
+        assert!(report("HTML", Some(150), &mut sizes, |s| s.html, 5));
+        // The heaviest pages come first
+        let pages: Vec<_> = sizes.iter().map(|s| s.page).collect();
+        assert_eq!(pages, ["b.html", "c.html", "a.html"].map(Path::new));
+
+        assert!(report("total", Some(1500), &mut sizes, |s| s.total, 1));
+        assert_eq!(sizes[0].page, Path::new("c.html"));
+    }
+}
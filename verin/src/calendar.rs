@@ -0,0 +1,70 @@
+//! Editorial overview of the articles, printed by `verin calendar`.
+
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use color_eyre::{eyre::Context, Result};
+
+use crate::{parse_article, read_sources, Config};
+
+struct Entry {
+    date: NaiveDate,
+    title: String,
+    /// Path of the source, relative to the input directory
+    source: PathBuf,
+}
+
+fn print_section(name: &str, entries: &[Entry], shown: usize) {
+    if entries.len() > shown {
+        println!("{name} ({} of {}):", shown, entries.len());
+    } else {
+        println!("{name} ({}):", entries.len());
+    }
+
+    for entry in entries.iter().take(shown) {
+        println!(
+            "  {}  {} ({})",
+            entry.date,
+            entry.title,
+            entry.source.display()
+        );
+    }
+}
+
+/// Print the drafts, the articles scheduled after `today`, and the `recent` last published ones
+pub fn calendar(input: &Path, today: NaiveDate, recent: usize) -> Result<()> {
+    let config = Config::load(input)?;
+
+    let (mut drafts, mut scheduled, mut published) = (Vec::new(), Vec::new(), Vec::new());
+    for (source, content) in read_sources(input)? {
+        let (metadata, _) = parse_article(&content)
+            .with_context(|| format!("Could not parse {}", source.display()))?;
+        let entry = Entry {
+            date: metadata
+                .date(&config.date)
+                .with_context(|| format!("Invalid date in {}", source.display()))?,
+            title: metadata.title,
+            source,
+        };
+
+        if metadata.draft {
+            drafts.push(entry);
+        } else if entry.date > today {
+            scheduled.push(entry);
+        } else {
+            published.push(entry);
+        }
+    }
+
+    drafts.sort_unstable_by(|a, b| a.date.cmp(&b.date).then_with(|| a.source.cmp(&b.source)));
+    scheduled.sort_unstable_by(|a, b| a.date.cmp(&b.date).then_with(|| a.source.cmp(&b.source)));
+    published.sort_unstable_by(|a, b| b.date.cmp(&a.date).then_with(|| a.source.cmp(&b.source)));
+
+    print_section("Scheduled", &scheduled, scheduled.len());
+    println!();
+    print_section("Recently published", &published, recent);
+    println!();
+    print_section("Drafts", &drafts, drafts.len());
+
+    Ok(())
+}
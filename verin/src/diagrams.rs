@@ -0,0 +1,144 @@
+//! Diagrams written in the fenced code blocks of the articles (```` ```mermaid ```` and
+//! ```` ```dot ````).
+//!
+//! They are written in the wrappers expected by the client side libraries (`<pre class="mermaid">`
+//! for [Mermaid](https://mermaid.js.org)), or the Graphviz ones can be rendered to inline SVG at
+//! build time by running `dot`.
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+use pulldown_cmark::{CodeBlockKind, Event, Tag, TagEnd};
+use pulldown_cmark_escape::escape_html;
+use serde::Deserialize;
+use verin::html::CodeInfo;
+
+#[derive(Deserialize, Debug)]
+pub struct DiagramsConfig {
+    /// Render the `dot` blocks to SVG at build time, instead of leaving them to a script
+    #[serde(default)]
+    pub render_dot: bool,
+    /// Graphviz command used to render the `dot` blocks
+    #[serde(default = "default_dot_command")]
+    pub dot_command: String,
+}
+
+fn default_dot_command() -> String {
+    "dot".to_owned()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Mermaid,
+    Dot,
+}
+
+impl Kind {
+    /// Kind of the diagrams of the fenced code blocks with the `info` string
+    fn of(info: &str) -> Option<Self> {
+        match CodeInfo::parse(info).lang {
+            "mermaid" => Some(Kind::Mermaid),
+            "dot" | "graphviz" => Some(Kind::Dot),
+            _ => None,
+        }
+    }
+
+    fn class(self) -> &'static str {
+        match self {
+            Kind::Mermaid => "mermaid",
+            Kind::Dot => "dot",
+        }
+    }
+}
+
+/// Render the graph `source` to SVG with Graphviz
+fn render_dot(command: &str, source: &str) -> Result<String> {
+    let mut child = Command::new(command)
+        .arg("-Tsvg")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Could not run `{command}`"))?;
+
+    // The graph is written from another thread, as `dot` may fill its output before reading all
+    // of it
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let source = source.to_owned();
+    let writer = std::thread::spawn(move || stdin.write_all(source.as_bytes()));
+
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("Could not run `{command}`"))?;
+    writer
+        .join()
+        .map_err(|_| eyre::eyre!("Could not write the graph to `{command}`"))?
+        .with_context(|| format!("Could not write the graph to `{command}`"))?;
+    if !output.status.success() {
+        eyre::bail!(
+            "Invalid graph: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let svg = String::from_utf8(output.stdout).context("`dot` did not output UTF-8")?;
+    // The XML declaration and doctype are not allowed inside an HTML document
+    let start = svg.find("<svg").unwrap_or(0);
+    Ok(svg[start..].trim_end().to_owned())
+}
+
+impl DiagramsConfig {
+    /// HTML of the diagram `source`
+    fn html(&self, kind: Kind, source: &str) -> Result<String> {
+        if kind == Kind::Dot && self.render_dot {
+            let svg = render_dot(&self.dot_command, source)?;
+            return Ok(format!("<div class=\"diagram dot\">{svg}</div>\n"));
+        }
+
+        let mut html = format!("<pre class=\"{}\">", kind.class());
+        escape_html(&mut html, source).expect("writing to a string can't fail");
+        html.push_str("</pre>\n");
+        Ok(html)
+    }
+
+    /// Replace the fenced code blocks of diagrams in `events` by their HTML
+    pub fn render<'a>(&self, events: Vec<Event<'a>>) -> Result<Vec<Event<'a>>> {
+        let mut output = Vec::with_capacity(events.len());
+        // Kind and source of the diagram being read
+        let mut diagram: Option<(Kind, String)> = None;
+
+        for event in events {
+            if let Some((kind, source)) = &mut diagram {
+                match event {
+                    Event::Text(text) => source.push_str(&text),
+                    Event::End(TagEnd::CodeBlock) => {
+                        let html = self.html(*kind, source).with_context(|| {
+                            format!("Could not render a {} diagram", kind.class())
+                        })?;
+                        output.push(Event::Html(html.into()));
+                        diagram = None;
+                    }
+                    _ => (),
+                }
+                continue;
+            }
+
+            match event {
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))
+                    if Kind::of(&info).is_some() =>
+                {
+                    diagram = Kind::of(&info).map(|kind| (kind, String::new()));
+                }
+                event => output.push(event),
+            }
+        }
+
+        Ok(output)
+    }
+}
@@ -0,0 +1,291 @@
+//! Textual differences between two builds of the website, printed by `verin diff`.
+//!
+//! Only the text of the pages is compared, so that changes of the markup or of the fingerprints
+//! of the assets don't hide the changes of the content.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+use glob::glob;
+
+/// Number of unchanged words shown around each change
+const CONTEXT: usize = 5;
+
+/// Maximum size of the table used to compare the changed part of a page, larger changes are
+/// shown as a replacement of the whole part
+const MAX_TABLE: usize = 1 << 22;
+
+/// Pages of the website in `output`, relative to it
+fn pages(output: &Path) -> Result<BTreeSet<PathBuf>> {
+    if !output.is_dir() {
+        eyre::bail!("{} is not a directory", output.display());
+    }
+
+    let mut pages = BTreeSet::new();
+    for entry in glob(&output.join("**/*.html").to_string_lossy())? {
+        pages.insert(entry?.strip_prefix(output)?.to_owned());
+    }
+
+    Ok(pages)
+}
+
+/// Words of the text of a page, without the tags, scripts and styles
+fn words(html: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        words.extend(rest[..start].split_whitespace());
+        rest = &rest[start..];
+
+        let closing = if rest.starts_with("<!--") {
+            "-->"
+        } else if starts_with_tag(rest, "<script") {
+            "</script>"
+        } else if starts_with_tag(rest, "<style") {
+            "</style>"
+        } else {
+            ">"
+        };
+        rest = match rest.find(closing) {
+            Some(end) => &rest[end + closing.len()..],
+            None => "",
+        };
+    }
+    words.extend(rest.split_whitespace());
+
+    words
+}
+
+fn starts_with_tag(html: &str, tag: &str) -> bool {
+    html.get(..tag.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(tag))
+        && html[tag.len()..].starts_with(|c: char| c == '>' || c.is_ascii_whitespace())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Edit script from `old` to `new`, as the operation applied to each word
+fn edits<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Op, &'a str)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_mid, new_mid) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut edits: Vec<_> = old[..prefix].iter().map(|&w| (Op::Equal, w)).collect();
+
+    if (old_mid.len() + 1) * (new_mid.len() + 1) > MAX_TABLE {
+        edits.extend(old_mid.iter().map(|&w| (Op::Delete, w)));
+        edits.extend(new_mid.iter().map(|&w| (Op::Insert, w)));
+    } else {
+        // Length of the longest common subsequence of old_mid[i..] and new_mid[j..]
+        let width = new_mid.len() + 1;
+        let mut lcs = vec![0u32; (old_mid.len() + 1) * width];
+        for i in (0..old_mid.len()).rev() {
+            for j in (0..new_mid.len()).rev() {
+                lcs[i * width + j] = if old_mid[i] == new_mid[j] {
+                    lcs[(i + 1) * width + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < old_mid.len() || j < new_mid.len() {
+            if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+                edits.push((Op::Equal, old_mid[i]));
+                i += 1;
+                j += 1;
+            } else if j == new_mid.len()
+                || (i < old_mid.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+            {
+                edits.push((Op::Delete, old_mid[i]));
+                i += 1;
+            } else {
+                edits.push((Op::Insert, new_mid[j]));
+                j += 1;
+            }
+        }
+    }
+
+    edits.extend(old[old.len() - suffix..].iter().map(|&w| (Op::Equal, w)));
+    edits
+}
+
+/// Format the changes of `edits` in the style of `git diff --word-diff`, one line per group of
+/// close changes
+fn hunks(edits: &[(Op, &str)]) -> Vec<String> {
+    let changed: Vec<_> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, (op, _))| *op != Op::Equal)
+        .map(|(idx, _)| idx)
+        .collect();
+
+    // Ranges of edits to show, merging the changes whose context overlap
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for idx in changed {
+        let (start, end) = (
+            idx.saturating_sub(CONTEXT),
+            (idx + CONTEXT + 1).min(edits.len()),
+        );
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let mut parts = Vec::new();
+            if start > 0 {
+                parts.push("...".to_owned());
+            }
+            for run in edits[start..end].chunk_by(|(a, _), (b, _)| a == b) {
+                let text = run
+                    .iter()
+                    .map(|(_, word)| *word)
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                parts.push(match run[0].0 {
+                    Op::Equal => text,
+                    Op::Delete => format!("[-{text}-]"),
+                    Op::Insert => format!("{{+{text}+}}"),
+                });
+            }
+            if end < edits.len() {
+                parts.push("...".to_owned());
+            }
+            parts.join(" ")
+        })
+        .collect()
+}
+
+/// Print the pages added, removed and changed between the builds in `old` and `new`
+pub fn diff(old: &Path, new: &Path) -> Result<()> {
+    let (old_pages, new_pages) = (pages(old)?, pages(new)?);
+    let read = |output: &Path, page: &Path| {
+        let path = output.join(page);
+        std::fs::read_to_string(&path).with_context(|| format!("Could not read {}", path.display()))
+    };
+
+    let (mut added, mut removed, mut changed, mut unchanged) = (0, 0, 0, 0);
+    for page in old_pages.union(&new_pages) {
+        match (old_pages.contains(page), new_pages.contains(page)) {
+            (true, false) => {
+                println!("removed: {}", page.display());
+                removed += 1;
+            }
+            (false, true) => {
+                println!("added: {}", page.display());
+                added += 1;
+            }
+            _ => {
+                let (old_html, new_html) = (read(old, page)?, read(new, page)?);
+                let edits = edits(&words(&old_html), &words(&new_html));
+                let hunks = hunks(&edits);
+                if hunks.is_empty() {
+                    unchanged += 1;
+                    continue;
+                }
+
+                println!("changed: {}", page.display());
+                for hunk in hunks {
+                    println!("    {hunk}");
+                }
+                changed += 1;
+            }
+        }
+    }
+
+    println!("{added} page(s) added, {removed} removed, {changed} changed, {unchanged} unchanged");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(text: &str) -> Vec<&str> {
+        text.split_whitespace().collect()
+    }
+
+    #[test]
+    fn text_of_pages() {
+        let html = concat!(
+            "<p>Hello <b>world</b></p>\n<script>if (a < b) {}</script>",
+            "<!-- <p>comment</p> --><style>p > a {}</style><scripts>end</scripts>",
+        );
+        assert_eq!(words(html), ["Hello", "world", "end"]);
+    }
+
+    #[test]
+    fn edit_script() {
+        use Op::*;
+
+        assert_eq!(
+            edits(&split("a b c d"), &split("a x c d")),
+            [
+                (Equal, "a"),
+                (Delete, "b"),
+                (Insert, "x"),
+                (Equal, "c"),
+                (Equal, "d")
+            ]
+        );
+        assert_eq!(
+            edits(&split("a"), &split("a b")),
+            [(Equal, "a"), (Insert, "b")]
+        );
+        assert_eq!(
+            edits(&split("a b c"), &split("b c d")),
+            [(Delete, "a"), (Equal, "b"), (Equal, "c"), (Insert, "d")]
+        );
+        assert!(edits(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn hunks_of_changes() {
+        let old: Vec<_> = (0..20).map(|i| format!("w{i}")).collect();
+        let old: Vec<_> = old.iter().map(String::as_str).collect();
+
+        let mut new = old.clone();
+        new[10] = "x";
+        assert_eq!(
+            hunks(&edits(&old, &new)),
+            ["... w5 w6 w7 w8 w9 [-w10-] {+x+} w11 w12 w13 w14 w15 ..."]
+        );
+
+        let mut new = old.clone();
+        new[0] = "a";
+        new[19] = "b";
+        assert_eq!(
+            hunks(&edits(&old, &new)),
+            [
+                "[-w0-] {+a+} w1 w2 w3 w4 w5 ...",
+                "... w14 w15 w16 w17 w18 [-w19-] {+b+}",
+            ]
+        );
+    }
+}
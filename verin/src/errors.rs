@@ -0,0 +1,163 @@
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+use serde::Deserialize;
+
+use crate::url::SiteUrl;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct ErrorPagesConfig {
+    /// Template used for each status code, defaults to `not_found`, `gone` and `server_error`
+    #[serde(default)]
+    pages: Option<BTreeMap<String, String>>,
+    /// Paths that were removed from the site, served with a 410 status
+    #[serde(default)]
+    pub removed: Vec<String>,
+    /// Leave a stub page at the URL of the articles that were removed since the last build
+    #[serde(default)]
+    pub stubs: bool,
+    /// Hosts for which to generate the configuration serving the error pages
+    #[serde(default)]
+    pub hosts: Vec<Host>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Host {
+    Netlify,
+    Caddy,
+}
+
+const DEFAULT_PAGES: &[(u16, &str)] = &[(404, "not_found"), (410, "gone"), (500, "server_error")];
+
+pub struct ErrorPage<'a> {
+    pub status: u16,
+    pub template: &'a str,
+    /// The page is only generated if the template exists
+    pub optional: bool,
+}
+
+impl ErrorPage<'_> {
+    pub fn file_name(&self) -> String {
+        format!("{}.html", self.status)
+    }
+}
+
+impl ErrorPagesConfig {
+    pub fn pages(&self) -> Result<Vec<ErrorPage<'_>>> {
+        let Some(pages) = &self.pages else {
+            return Ok(DEFAULT_PAGES
+                .iter()
+                .map(|&(status, template)| ErrorPage {
+                    status,
+                    template,
+                    optional: true,
+                })
+                .collect());
+        };
+
+        pages
+            .iter()
+            .map(|(status, template)| {
+                let status: u16 = status
+                    .parse()
+                    .with_context(|| format!("Invalid status code for error page: {status}"))?;
+                if !(400..600).contains(&status) {
+                    eyre::bail!("Status code {status} is not an error");
+                }
+
+                Ok(ErrorPage {
+                    status,
+                    template,
+                    optional: false,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Write the configuration of the `hosts` serving the generated error pages (as status codes)
+///
+/// The `removed` paths are served with a 410 status, and the `aliases` (relative to the root of the
+/// website) are redirected to their target.
+pub fn write_host_config(
+    output: &Path,
+    config: &ErrorPagesConfig,
+    statuses: &[u16],
+    removed: &[String],
+    aliases: &BTreeMap<String, String>,
+) -> Result<()> {
+    for host in &config.hosts {
+        match host {
+            Host::Netlify => {
+                let mut redirects = String::new();
+                for (alias, target) in aliases {
+                    writeln!(
+                        redirects,
+                        "{} {} 301!",
+                        SiteUrl::parse(alias),
+                        SiteUrl::parse(target)
+                    )?;
+                }
+                if statuses.contains(&410) {
+                    for path in removed {
+                        writeln!(redirects, "{path} {} 410!", SiteUrl::parse("410.html"))?;
+                    }
+                }
+                if statuses.contains(&404) {
+                    writeln!(
+                        redirects,
+                        "{} {} 404",
+                        SiteUrl::parse("*"),
+                        SiteUrl::parse("404.html")
+                    )?;
+                }
+
+                std::fs::write(output.join("_redirects"), redirects)
+                    .context("Could not write netlify redirects")?;
+            }
+            Host::Caddy => {
+                let mut caddy = String::new();
+                for (alias, target) in aliases {
+                    writeln!(
+                        caddy,
+                        "redir {} {} permanent",
+                        SiteUrl::parse(alias),
+                        SiteUrl::parse(target)
+                    )?;
+                }
+                if !aliases.is_empty() {
+                    writeln!(caddy)?;
+                }
+                if !removed.is_empty() {
+                    writeln!(caddy, "@removed path {}", removed.join(" "))?;
+                    writeln!(caddy, "error @removed 410")?;
+                    writeln!(caddy)?;
+                }
+
+                writeln!(caddy, "handle_errors {{")?;
+                for status in statuses {
+                    writeln!(
+                        caddy,
+                        "\t@{status} expression `{{err.status_code}} == {status}`"
+                    )?;
+                    writeln!(
+                        caddy,
+                        "\trewrite @{status} {}",
+                        SiteUrl::parse(&format!("{status}.html"))
+                    )?;
+                }
+                writeln!(caddy, "\tfile_server")?;
+                writeln!(caddy, "}}")?;
+
+                std::fs::write(output.join("errors.caddy"), caddy)
+                    .context("Could not write caddy configuration")?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,332 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use liquid_core::{
+    parser::{FilterArguments, ParameterReflection},
+    runtime::Expression,
+    Error, Filter, FilterReflection, ParseFilter, Result, Runtime, Value, ValueView,
+};
+use ts_highlight_html::{Diagnostic, SyntaxConfig};
+
+use crate::{html, render_markdown, url::SiteUrl};
+
+/// Problems found by the `md` filter while rendering the templates, reported by the build like
+/// the ones of the articles
+#[derive(Clone, Default)]
+pub struct Diagnostics(Arc<Mutex<Vec<Diagnostic>>>);
+
+impl Diagnostics {
+    fn extend(&self, diagnostics: Vec<Diagnostic>) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(diagnostics);
+    }
+
+    /// Problems found since the last call
+    pub fn take(&self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.0.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// Liquid filter rendering a markdown string to HTML, like the body of articles
+///
+/// Usage: `{{ data.description | md }}`
+#[derive(Clone)]
+pub struct Markdown {
+    syntax_conf: Arc<SyntaxConfig<'static>>,
+    options: html::RenderOptions,
+    diagnostics: Diagnostics,
+}
+
+impl Markdown {
+    pub fn new(
+        syntax_conf: Arc<SyntaxConfig<'static>>,
+        options: html::RenderOptions,
+        diagnostics: Diagnostics,
+    ) -> Self {
+        Self {
+            syntax_conf,
+            options,
+            diagnostics,
+        }
+    }
+}
+
+impl FilterReflection for Markdown {
+    fn name(&self) -> &str {
+        "md"
+    }
+
+    fn description(&self) -> &str {
+        "Render a markdown string to HTML"
+    }
+
+    fn positional_parameters(&self) -> &'static [ParameterReflection] {
+        &[]
+    }
+
+    fn keyword_parameters(&self) -> &'static [ParameterReflection] {
+        &[]
+    }
+}
+
+impl ParseFilter for Markdown {
+    fn parse(&self, _arguments: FilterArguments) -> Result<Box<dyn Filter>> {
+        Ok(Box::new(MarkdownFilter {
+            syntax_conf: self.syntax_conf.clone(),
+            options: self.options,
+            diagnostics: self.diagnostics.clone(),
+        }))
+    }
+
+    fn reflection(&self) -> &dyn FilterReflection {
+        self
+    }
+}
+
+struct MarkdownFilter {
+    syntax_conf: Arc<SyntaxConfig<'static>>,
+    options: html::RenderOptions,
+    diagnostics: Diagnostics,
+}
+
+impl fmt::Debug for MarkdownFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MarkdownFilter").finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for MarkdownFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "md")
+    }
+}
+
+impl Filter for MarkdownFilter {
+    fn evaluate(&self, input: &dyn ValueView, _runtime: &dyn Runtime) -> Result<Value> {
+        if input.is_nil() {
+            return Ok(Value::scalar(""));
+        }
+
+        let content = markdown(
+            input.to_kstr().as_str(),
+            &self.syntax_conf,
+            self.options,
+            &self.diagnostics,
+        )
+        .map_err(|e| Error::with_msg(format!("{e:#}")))?;
+
+        Ok(Value::scalar(content))
+    }
+}
+
+fn markdown(
+    input: &str,
+    syntax_conf: &SyntaxConfig,
+    options: html::RenderOptions,
+    diagnostics: &Diagnostics,
+) -> color_eyre::Result<String> {
+    let (content, rendered) = render_markdown(input, syntax_conf, options)?;
+    diagnostics.extend(rendered.diagnostics());
+    Ok(content)
+}
+
+/// URLs with a scheme point outside of the website, and are kept as is by the URL filters
+fn is_external(url: &str) -> bool {
+    url.contains("://")
+}
+
+fn absolute_url(url: &str, base: &str) -> String {
+    if is_external(url) {
+        return url.to_owned();
+    }
+
+    SiteUrl::parse(url).with_base(base)
+}
+
+fn relative_url(url: &str, from: Option<&str>) -> String {
+    if is_external(url) {
+        return url.to_owned();
+    }
+
+    let url = SiteUrl::parse(url);
+    match from {
+        Some(from) => url.relative_to(&SiteUrl::parse(from)),
+        None => url.to_string(),
+    }
+}
+
+/// Liquid filter turning an URL from the root of the website into an absolute URL, using the
+/// `base_url` of the configuration
+///
+/// Usage: `{{ tag.url | absolute_url }}`
+#[derive(Clone)]
+pub struct AbsoluteUrl {
+    base: Option<String>,
+}
+
+impl AbsoluteUrl {
+    pub fn new(base: Option<String>) -> Self {
+        Self { base }
+    }
+}
+
+impl FilterReflection for AbsoluteUrl {
+    fn name(&self) -> &str {
+        "absolute_url"
+    }
+
+    fn description(&self) -> &str {
+        "Turn an URL from the root of the website into an absolute URL"
+    }
+
+    fn positional_parameters(&self) -> &'static [ParameterReflection] {
+        &[]
+    }
+
+    fn keyword_parameters(&self) -> &'static [ParameterReflection] {
+        &[]
+    }
+}
+
+impl ParseFilter for AbsoluteUrl {
+    fn parse(&self, _arguments: FilterArguments) -> Result<Box<dyn Filter>> {
+        let base = self.base.clone().ok_or_else(|| {
+            Error::with_msg("`absolute_url` requires a `base_url` in the configuration")
+        })?;
+
+        Ok(Box::new(AbsoluteUrlFilter { base }))
+    }
+
+    fn reflection(&self) -> &dyn FilterReflection {
+        self
+    }
+}
+
+#[derive(Debug)]
+struct AbsoluteUrlFilter {
+    base: String,
+}
+
+impl fmt::Display for AbsoluteUrlFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "absolute_url")
+    }
+}
+
+impl Filter for AbsoluteUrlFilter {
+    fn evaluate(&self, input: &dyn ValueView, _runtime: &dyn Runtime) -> Result<Value> {
+        Ok(Value::scalar(absolute_url(&input.to_kstr(), &self.base)))
+    }
+}
+
+/// Liquid filter normalizing an URL from the root of the website, optionally making it relative
+/// to the URL of a page
+///
+/// Usage: `{{ tag.url | relative_url }}` or `{{ tag.url | relative_url: url }}`
+#[derive(Clone)]
+pub struct RelativeUrl;
+
+const RELATIVE_URL_PARAMETERS: &[ParameterReflection] = &[ParameterReflection {
+    name: "from",
+    description: "URL of the page the URL is relative to",
+    is_optional: true,
+}];
+
+impl FilterReflection for RelativeUrl {
+    fn name(&self) -> &str {
+        "relative_url"
+    }
+
+    fn description(&self) -> &str {
+        "Normalize an URL from the root of the website, or make it relative to a page"
+    }
+
+    fn positional_parameters(&self) -> &'static [ParameterReflection] {
+        RELATIVE_URL_PARAMETERS
+    }
+
+    fn keyword_parameters(&self) -> &'static [ParameterReflection] {
+        &[]
+    }
+}
+
+impl ParseFilter for RelativeUrl {
+    fn parse(&self, mut arguments: FilterArguments) -> Result<Box<dyn Filter>> {
+        let from = arguments.positional.next();
+        if arguments.positional.next().is_some() || arguments.keyword.next().is_some() {
+            return Err(Error::with_msg(
+                "`relative_url` takes at most the URL of a page as argument",
+            ));
+        }
+
+        Ok(Box::new(RelativeUrlFilter { from }))
+    }
+
+    fn reflection(&self) -> &dyn FilterReflection {
+        self
+    }
+}
+
+#[derive(Debug)]
+struct RelativeUrlFilter {
+    from: Option<Expression>,
+}
+
+impl fmt::Display for RelativeUrlFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.from {
+            Some(from) => write!(f, "relative_url: {from}"),
+            None => write!(f, "relative_url"),
+        }
+    }
+}
+
+impl Filter for RelativeUrlFilter {
+    fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> Result<Value> {
+        let from = match &self.from {
+            Some(from) => Some(from.evaluate(runtime)?.to_kstr().into_owned()),
+            None => None,
+        };
+
+        Ok(Value::scalar(relative_url(
+            &input.to_kstr(),
+            from.as_deref(),
+        )))
+    }
+}
+
+/// Register the filters in a minijinja environment, with the same names and arguments as the
+/// liquid filters
+pub fn register_jinja(
+    env: &mut minijinja::Environment,
+    syntax_conf: Arc<SyntaxConfig<'static>>,
+    base: Option<String>,
+    options: html::RenderOptions,
+    diagnostics: Diagnostics,
+) {
+    use minijinja::{Error, ErrorKind};
+
+    env.add_filter("md", move |input: Option<String>| {
+        markdown(
+            input.as_deref().unwrap_or_default(),
+            &syntax_conf,
+            options,
+            &diagnostics,
+        )
+        .map_err(|e| Error::new(ErrorKind::InvalidOperation, format!("{e:#}")))
+    });
+    env.add_filter("absolute_url", move |url: String| match &base {
+        Some(base) => Ok(absolute_url(&url, base)),
+        None => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            "`absolute_url` requires a `base_url` in the configuration",
+        )),
+    });
+    env.add_filter("relative_url", |url: String, from: Option<String>| {
+        relative_url(&url, from.as_deref())
+    });
+}
@@ -0,0 +1,129 @@
+//! Detection of the articles that were not updated for too long.
+//!
+//! Each policy of the `freshness` section of the configuration gives the maximum age of the
+//! articles with a tag (or of all the articles). Stale articles are reported by `verin check`, and
+//! templates get a `stale` flag to display a banner.
+
+use std::path::Path;
+
+use chrono::{Months, NaiveDate};
+use color_eyre::{eyre::Context, Result};
+use serde::Deserialize;
+
+use crate::{parse_article, read_sources, Config, Metadata};
+
+#[derive(Deserialize, Debug)]
+pub struct FreshnessPolicy {
+    /// Tag of the articles the policy applies to, all the articles if absent
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Number of months after which an article that was not updated is stale
+    pub max_age_months: u32,
+}
+
+impl FreshnessPolicy {
+    fn applies_to(&self, metadata: &Metadata) -> bool {
+        match &self.tag {
+            Some(tag) => metadata.tags.contains(tag),
+            None => true,
+        }
+    }
+}
+
+/// Policy for which the article is stale on `today`, if any
+pub fn stale_policy<'c>(
+    config: &'c Config,
+    metadata: &Metadata,
+    today: NaiveDate,
+) -> Result<Option<&'c FreshnessPolicy>> {
+    let updated = metadata.updated(&config.date)?;
+
+    Ok(config.freshness.iter().find(|policy| {
+        policy.applies_to(metadata)
+            && updated
+                .checked_add_months(Months::new(policy.max_age_months))
+                .is_some_and(|limit| limit < today)
+    }))
+}
+
+/// Print the stale articles of the website in `input`
+pub fn check(input: &Path) -> Result<()> {
+    let config = Config::load(input)?;
+    let today = chrono::Local::now().date_naive();
+
+    let mut sources = read_sources(input)?;
+    sources.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut stale = 0;
+    for (path, source) in &sources {
+        let (metadata, _) =
+            parse_article(source).with_context(|| format!("Could not parse {}", path.display()))?;
+        let policy = stale_policy(&config, &metadata, today)
+            .with_context(|| format!("Invalid date in {}", path.display()))?;
+        let Some(policy) = policy else {
+            continue;
+        };
+
+        stale += 1;
+        let articles = match &policy.tag {
+            Some(tag) => format!("articles tagged `{tag}`"),
+            None => "articles".to_owned(),
+        };
+        println!(
+            "[WARNING] {} was not updated since {}, {articles} should be updated every {} months",
+            path.display(),
+            metadata.updated.as_ref().unwrap_or(&metadata.date),
+            policy.max_age_months
+        );
+    }
+
+    println!("Checked {} article(s): {stale} stale", sources.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        toml::from_str(concat!(
+            "name = \"Test\"\n",
+            "[date]\ninput = \"%Y-%m-%d\"\noutput = \"%Y-%m-%d\"\n",
+            "[[freshness]]\ntag = \"news\"\nmax_age_months = 3\n",
+            "[[freshness]]\nmax_age_months = 24\n",
+        ))
+        .unwrap()
+    }
+
+    fn metadata(tags: &str, updated: &str) -> Metadata {
+        toml::from_str(&format!(
+            "title = \"Intro\"\ndate = \"2023-04-05\"\npage = \"article\"\nsummary = \"\"\n\
+             tags = [{tags}]\n{updated}\n"
+        ))
+        .unwrap()
+    }
+
+    fn max_age(metadata: &Metadata, today: NaiveDate) -> Option<u32> {
+        stale_policy(&config(), metadata, today)
+            .unwrap()
+            .map(|policy| policy.max_age_months)
+    }
+
+    #[test]
+    fn policies() {
+        let today = NaiveDate::from_ymd_opt(2023, 8, 1).unwrap();
+        assert_eq!(max_age(&metadata("", ""), today), None);
+        assert_eq!(max_age(&metadata("\"news\"", ""), today), Some(3));
+        assert_eq!(
+            max_age(&metadata("\"news\"", "updated = \"2023-06-01\""), today),
+            None
+        );
+
+        let later = NaiveDate::from_ymd_opt(2025, 5, 1).unwrap();
+        assert_eq!(max_age(&metadata("", ""), later), Some(24));
+        assert_eq!(
+            max_age(&metadata("", "updated = \"2024-01-01\""), later),
+            None
+        );
+    }
+}
@@ -0,0 +1,311 @@
+//! Abbreviations defined in the articles (`*[HTML]: Hyper Text Markup Language`), collected in a
+//! glossary of the whole website.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet, VecDeque},
+    path::Path,
+};
+
+use pulldown_cmark::{Event, Tag, TagEnd};
+use pulldown_cmark_escape::{escape_href, escape_html};
+use serde::Serialize;
+
+use crate::{slug::slugify, url::SiteUrl};
+
+/// Definition of an abbreviation in an article
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub abbreviation: String,
+    pub expansion: String,
+}
+
+/// Parse an abbreviation definition line (`*[HTML]: Hyper Text Markup Language`)
+fn parse_definition(line: &str) -> Option<Definition> {
+    let (abbreviation, expansion) = line.trim().strip_prefix("*[")?.split_once("]:")?;
+    let (abbreviation, expansion) = (abbreviation.trim(), expansion.trim());
+    if abbreviation.is_empty() || expansion.is_empty() {
+        return None;
+    }
+
+    Some(Definition {
+        abbreviation: abbreviation.to_owned(),
+        expansion: expansion.to_owned(),
+    })
+}
+
+/// Remove the abbreviation definitions from the markdown `body`, returning them.
+///
+/// Definitions are lines of their own, and the lines of fenced code blocks are kept as is.
+pub fn extract(body: &str) -> (Cow<'_, str>, Vec<Definition>) {
+    let mut definitions = Vec::new();
+    let mut stripped = String::new();
+    // Offset of the text of `body` not yet copied to `stripped`
+    let mut copied = 0;
+    let mut offset = 0;
+    let mut fence: Option<&str> = None;
+
+    for line in body.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+
+        let trimmed = line.trim_start();
+        match fence {
+            Some(marker) if trimmed.starts_with(marker) => fence = None,
+            Some(_) => continue,
+            None if trimmed.starts_with("```") => fence = Some("```"),
+            None if trimmed.starts_with("~~~") => fence = Some("~~~"),
+            None => (),
+        }
+
+        if let Some(definition) = parse_definition(line) {
+            definitions.push(definition);
+            stripped.push_str(&body[copied..start]);
+            copied = offset;
+        }
+    }
+
+    if definitions.is_empty() {
+        return (body.into(), definitions);
+    }
+    stripped.push_str(&body[copied..]);
+    (stripped.into(), definitions)
+}
+
+#[derive(Debug, Serialize)]
+pub struct Entry {
+    pub abbreviation: String,
+    pub expansion: String,
+    /// The `id` of the entry in the glossary page
+    pub id: String,
+}
+
+/// Abbreviations of the whole website, sorted by abbreviation
+#[derive(Debug, Default)]
+pub struct Glossary {
+    pub entries: Vec<Entry>,
+    /// URL of the glossary page, if it is generated
+    pub page: Option<SiteUrl>,
+}
+
+impl Glossary {
+    /// Collect the definitions of the articles, by the path of their source.
+    ///
+    /// An abbreviation defined differently by several articles keeps its first definition.
+    pub fn collect<'a>(
+        definitions: impl IntoIterator<Item = (&'a Path, &'a [Definition])>,
+        page: Option<SiteUrl>,
+    ) -> Self {
+        let mut entries: BTreeMap<&str, (&Path, &str)> = BTreeMap::new();
+        for (source, definitions) in definitions {
+            for definition in definitions {
+                let (first, expansion) = *entries
+                    .entry(definition.abbreviation.as_str())
+                    .or_insert((source, &definition.expansion));
+                if expansion != definition.expansion {
+                    println!(
+                        "[WARNING] {} is defined as `{expansion}` in {} and `{}` in {}, using the \
+                         first definition",
+                        definition.abbreviation,
+                        first.display(),
+                        definition.expansion,
+                        source.display()
+                    );
+                }
+            }
+        }
+
+        Self {
+            entries: entries
+                .into_iter()
+                .map(|(abbreviation, (_, expansion))| Entry {
+                    abbreviation: abbreviation.to_owned(),
+                    expansion: expansion.to_owned(),
+                    id: slugify(abbreviation),
+                })
+                .collect(),
+            page,
+        }
+    }
+
+    /// Link the first occurrence of each abbreviation in `events` to the glossary
+    pub fn link<'a, I>(&self, events: I) -> Linked<'_, 'a, I>
+    where
+        I: Iterator<Item = Event<'a>>,
+    {
+        Linked {
+            events,
+            glossary: self,
+            pending: VecDeque::new(),
+            linked: HashSet::new(),
+            skipped: 0,
+        }
+    }
+
+    /// HTML of an occurrence of the abbreviation of `entry`
+    fn abbreviation_html(&self, entry: &Entry) -> String {
+        let mut html = String::from("<abbr title=\"");
+        escape_html(&mut html, &entry.expansion).expect("writing to a string can't fail");
+        html.push_str("\">");
+        if let Some(page) = &self.page {
+            html.push_str("<a href=\"");
+            escape_href(&mut html, &format!("{page}#{}", entry.id))
+                .expect("writing to a string can't fail");
+            html.push_str("\">");
+        }
+        escape_html(&mut html, &entry.abbreviation).expect("writing to a string can't fail");
+        if self.page.is_some() {
+            html.push_str("</a>");
+        }
+        html.push_str("</abbr>");
+        html
+    }
+}
+
+/// Find the first occurrence of `word` in `text` that is not part of a longer word
+fn find_word(text: &str, word: &str) -> Option<usize> {
+    text.match_indices(word).map(|(idx, _)| idx).find(|&idx| {
+        let before = text[..idx].chars().next_back();
+        let after = text[idx + word.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+/// Events of a document, with the first occurrence of each abbreviation linked to the glossary
+pub struct Linked<'g, 'a, I> {
+    events: I,
+    glossary: &'g Glossary,
+    /// Events split from a text
+    pending: VecDeque<Event<'a>>,
+    /// Entries already linked in the document
+    linked: HashSet<usize>,
+    /// Depth of the elements whose text is not linked (code blocks, headings, links and images)
+    skipped: usize,
+}
+
+impl<'a, I> Linked<'_, 'a, I> {
+    /// Split `text` around the first occurrences of the abbreviations not yet linked, returning
+    /// whether it contains any
+    fn split(&mut self, text: &str) -> bool {
+        let mut rest = text;
+        let mut found = false;
+        loop {
+            let next = self
+                .glossary
+                .entries
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| !self.linked.contains(idx))
+                .filter_map(|(idx, entry)| Some((find_word(rest, &entry.abbreviation)?, idx)))
+                .min();
+            let Some((start, idx)) = next else {
+                break;
+            };
+            self.linked.insert(idx);
+            found = true;
+
+            let entry = &self.glossary.entries[idx];
+            if start > 0 {
+                self.pending
+                    .push_back(Event::Text(rest[..start].to_owned().into()));
+            }
+            self.pending.push_back(Event::InlineHtml(
+                self.glossary.abbreviation_html(entry).into(),
+            ));
+            rest = &rest[start + entry.abbreviation.len()..];
+        }
+
+        if found && !rest.is_empty() {
+            self.pending.push_back(Event::Text(rest.to_owned().into()));
+        }
+        found
+    }
+}
+
+impl<'a, I> Iterator for Linked<'_, 'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+
+        let event = self.events.next()?;
+        match &event {
+            Event::Start(
+                Tag::CodeBlock(_) | Tag::Heading { .. } | Tag::Link { .. } | Tag::Image { .. },
+            ) => self.skipped += 1,
+            Event::End(TagEnd::CodeBlock | TagEnd::Heading(_) | TagEnd::Link | TagEnd::Image) => {
+                self.skipped -= 1
+            }
+            Event::Text(text) if self.skipped == 0 && self.split(text) => {
+                return self.pending.pop_front();
+            }
+            _ => (),
+        }
+
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn definitions() {
+        let body = "*[HTML]: Hyper Text Markup Language\nSome HTML.\n```\n*[CSS]: kept\n```\n  \
+                    *[JS]: JavaScript\n";
+        let (stripped, definitions) = extract(body);
+        assert_eq!(stripped, "Some HTML.\n```\n*[CSS]: kept\n```\n");
+        let abbreviations: Vec<_> = definitions
+            .iter()
+            .map(|d| (d.abbreviation.as_str(), d.expansion.as_str()))
+            .collect();
+        assert_eq!(
+            abbreviations,
+            [("HTML", "Hyper Text Markup Language"), ("JS", "JavaScript")]
+        );
+
+        assert!(matches!(extract("No definitions\n"), (Cow::Borrowed(_), d) if d.is_empty()));
+        assert!(parse_definition("*[]: Empty").is_none());
+        assert!(parse_definition("*[HTML]:").is_none());
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(find_word("XHTML or HTML", "HTML"), Some(9));
+        assert_eq!(find_word("(HTML)", "HTML"), Some(1));
+        assert_eq!(find_word("HTMLs", "HTML"), None);
+    }
+
+    #[test]
+    fn first_definition() {
+        let first =
+            extract("*[CSS]: Cascading Style Sheets\n*[HTML]: HyperText Markup Language\n").1;
+        let second = extract("*[CSS]: Something else\n").1;
+        let glossary = Glossary::collect(
+            [
+                (Path::new("a.md"), first.as_slice()),
+                (Path::new("b.md"), second.as_slice()),
+            ],
+            None,
+        );
+
+        let entries: Vec<_> = glossary
+            .entries
+            .iter()
+            .map(|e| (e.abbreviation.as_str(), e.expansion.as_str(), e.id.as_str()))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("CSS", "Cascading Style Sheets", "css"),
+                ("HTML", "HyperText Markup Language", "html"),
+            ]
+        );
+    }
+}
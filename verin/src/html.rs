@@ -21,29 +21,381 @@
 //! HTML renderer that takes an iterator of events as input.
 
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Write as _},
     io::{self, Write},
+    ops::RangeInclusive,
 };
 
 use asciimath_rs::format::mathml::ToMathML;
-use bstr::ByteSlice;
 use color_eyre::Result;
 
 use itertools::Itertools;
 use pulldown_cmark::{
     Alignment, BlockQuoteKind, CodeBlockKind, CowStr,
     Event::{self, *},
-    HeadingLevel, LinkType, Tag, TagEnd,
+    HeadingLevel, LinkType, Options, Tag, TagEnd, TextMergeStream,
 };
-use pulldown_cmark_escape::{escape_href, escape_html, IoWriter, StrWrite};
-use serde::Serialize;
-use ts_highlight_html::{Renderer, SyntaxConfig};
+use pulldown_cmark_escape::{escape_href, escape_html, IoWriter};
+use serde::{Deserialize, Serialize};
+use ts_highlight_html::{Diagnostic, LineOptions, Output, Renderer, SyntaxConfig};
+
+/// Info string of the fenced code blocks whose content is written as is, without escaping
+pub const RAW_HTML: &str = "{=html}";
+
+/// Start of the section collecting the footnote definitions at the end of the documents
+const FOOTNOTES_SECTION: &str = "<section class=\"footnotes\">";
 
 #[derive(Debug, Serialize)]
 pub struct HeadingInfo {
     pub level: u8,
-    pub text: String,
+    /// Text of the heading, without any markup
+    pub text_plain: String,
+    /// Content of the heading as HTML, keeping the inline code, emphasis and math
+    pub text_html: String,
     pub number: String,
+    /// The `id` attribute of the heading
+    pub id: String,
+}
+
+/// How the math of a document is written
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MathOutput {
+    /// MathML generated from the AsciiMath, displayed by browsers without any script
+    #[default]
+    MathMl,
+    /// The AsciiMath source in a `<span class="math inline">` (or `math block`), to be rendered by
+    /// a script
+    Source,
+}
+
+/// Variants of an image in another format, offered by a `<source>` of a `<picture>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageSource {
+    /// MIME type of the variants (`image/webp`)
+    pub mime: String,
+    /// URL of each variant, with its width (`/images/a-480.webp 480w, /images/a-960.webp 960w`)
+    pub srcset: String,
+}
+
+/// Image of a document found in the website
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalImage {
+    /// Width in pixels
+    pub width: usize,
+    /// Height in pixels
+    pub height: usize,
+    pub sources: Vec<ImageSource>,
+}
+
+/// Images of a document found in the website, by their URL.
+///
+/// They get `width` and `height` attributes avoiding layout shifts, and are loaded lazily. Images
+/// with variants are wrapped in a `<picture>` offering them.
+pub type LocalImages = HashMap<String, LocalImage>;
+
+/// Attributes of the links to other websites
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ExternalLinks {
+    /// Open the links in a new tab (`target="_blank"`, with `rel="noopener noreferrer"`)
+    #[serde(default)]
+    pub new_tab: bool,
+    /// Class of the links, to style them
+    #[serde(default)]
+    pub class: Option<String>,
+}
+
+/// Fenced code block of a document, given to the [`CodeBlockTemplate`] of the website
+#[derive(Debug, Serialize)]
+pub struct CodeBlock<'c> {
+    /// Highlighted lines of the code, to be written in a `<code>`
+    pub html: &'c str,
+    /// Language of the code, empty if not given
+    pub language: &'c str,
+    /// Title given by the `title=` option
+    pub title: Option<&'c str>,
+    /// Whether the line numbers are displayed
+    pub line_numbers: bool,
+    /// Ranges of emphasized lines, as `[first, last]`
+    pub highlighted: Vec<[usize; 2]>,
+    /// Number of lines of the code
+    pub lines: usize,
+    /// Inline style of the block, when the highlighting does not use classes
+    pub style: Option<&'c str>,
+}
+
+/// Markup of the fenced code blocks of a website, replacing the default `<pre><code>`
+pub trait CodeBlockTemplate: fmt::Debug {
+    /// HTML of the code `block`
+    fn render(&self, block: &CodeBlock) -> Result<String>;
+}
+
+/// Website a document is part of
+#[derive(Debug, Clone, Default)]
+pub struct Site<'s> {
+    /// Host of the website (`example.org`), the links to other hosts are external
+    pub host: Option<String>,
+    pub external_links: ExternalLinks,
+    pub images: LocalImages,
+    pub code_blocks: Option<&'s dyn CodeBlockTemplate>,
+}
+
+impl Site<'_> {
+    /// Whether `url` points to another website
+    fn is_external(&self, url: &str) -> bool {
+        let Some(host) = host(url) else {
+            return url.starts_with("//");
+        };
+        !self
+            .host
+            .as_deref()
+            .is_some_and(|own| own.eq_ignore_ascii_case(host))
+    }
+}
+
+/// Host of an absolute `url`, including its port (`example.org:8080`)
+///
+/// Hosts are case insensitive, they must be compared with [`str::eq_ignore_ascii_case`].
+pub fn host(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("://")?;
+    rest.split(['/', '?', '#']).next()
+}
+
+/// Options of the rendering of a document
+#[derive(Debug, Clone, Copy)]
+pub struct RenderOptions {
+    /// Add a link to itself at the end of each heading (`<a class="anchor" href="#header-1">`)
+    pub heading_anchors: bool,
+    /// Wrap each heading and the content up to the next heading of the same level in a
+    /// `<section aria-labelledby="header-1">`
+    pub sections: bool,
+    /// Wrap each code block in a `<div class="code-block">`, with its language in `data-lang` and
+    /// its `title=` in a `<div class="code-title">`
+    pub wrap_code_blocks: bool,
+    pub math: MathOutput,
+    /// Markdown extensions enabled when parsing the document
+    pub extensions: Options,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            heading_anchors: false,
+            sections: false,
+            wrap_code_blocks: false,
+            math: MathOutput::default(),
+            extensions: Options::ENABLE_MATH
+                | Options::ENABLE_FOOTNOTES
+                | Options::ENABLE_GFM
+                | Options::ENABLE_TABLES,
+        }
+    }
+}
+
+/// HTML of the math expression `text`, displayed `inline` or as a `block`
+fn math_html(text: &str, display: &str, output: MathOutput) -> Result<String, WriterError> {
+    Ok(match output {
+        MathOutput::MathMl => format!(
+            r#"<math display="{display}">{}</math>"#,
+            asciimath_rs::parse(text).to_mathml()
+        ),
+        MathOutput::Source => {
+            let mut html = format!(r#"<span class="math {display}">"#);
+            escape_html(&mut html, text)?;
+            html.push_str("</span>");
+            html
+        }
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct EquationInfo {
+    /// Number of the display math block in the document, starting at 1
+    pub number: usize,
+    /// The `id` attribute of the equation
+    pub id: String,
+    /// Name given to the equation with `\label{name}`, to reference it with `\ref{name}`
+    pub label: Option<String>,
+}
+
+/// Split the `\label{name}` out of a display math expression
+fn equation_label(math: &str) -> (Cow<'_, str>, Option<&str>) {
+    let Some(start) = math.find("\\label{") else {
+        return (math.into(), None);
+    };
+    let Some(length) = math[start..].find('}') else {
+        return (math.into(), None);
+    };
+    let end = start + length + 1;
+
+    let label = math[start + "\\label{".len()..end - 1].trim();
+    (
+        format!("{}{}", &math[..start], &math[end..]).into(),
+        Some(label),
+    )
+}
+
+/// Number the display math blocks of a document
+fn equations(events: &[Event]) -> Vec<EquationInfo> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            DisplayMath(math) => Some(math),
+            _ => None,
+        })
+        .enumerate()
+        .map(|(idx, math)| {
+            let (_, label) = equation_label(math);
+            EquationInfo {
+                number: idx + 1,
+                id: match label {
+                    Some(label) => format!("eq-{label}"),
+                    None => format!("eq-{}", idx + 1),
+                },
+                label: label.map(ToOwned::to_owned),
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct CrossReference {
+    /// Kind of the labelled element: `fig`, `tbl` or `lst`
+    pub kind: String,
+    /// Number of the element among the ones of the same kind, starting at 1
+    pub number: usize,
+    /// The `id` attribute of the element (`fig-arch` for `{#fig:arch}`)
+    pub id: String,
+    /// Label of the element, referenced with `[@fig:arch]`
+    pub label: String,
+}
+
+impl CrossReference {
+    /// Text of the links to this element (`Figure 1`)
+    fn name(&self) -> String {
+        let kind = match self.kind.as_str() {
+            "fig" => "Figure",
+            "tbl" => "Table",
+            _ => "Listing",
+        };
+        format!("{kind} {}", self.number)
+    }
+}
+
+/// Information collected while rendering a document
+#[derive(Debug, Default)]
+pub struct Rendered {
+    pub headers: Vec<HeadingInfo>,
+    /// Display math blocks of the document, in order
+    pub equations: Vec<EquationInfo>,
+    /// Labelled figures, tables and listings of the document, in order
+    pub cross_references: Vec<CrossReference>,
+    /// Number of words in the text of the document (code blocks excluded)
+    pub words: usize,
+    /// Language of each fenced code block
+    pub languages: Vec<String>,
+    /// Language of each fenced code block that was not highlighted, as the language is unknown
+    pub unhighlighted: Vec<String>,
+    /// Number of uses of each markdown extension (`tables`, `footnotes`, ...)
+    pub features: BTreeMap<&'static str, usize>,
+}
+
+impl Rendered {
+    /// Problems found while highlighting the code blocks, that were still written
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.unhighlighted
+            .iter()
+            .map(|language| Diagnostic::UnknownLanguage(language.clone()))
+            .collect()
+    }
+
+    /// Table of contents of the document, as nested lists of links to the headings in a `<nav>`.
+    ///
+    /// Only the headings with a level lower than `max_depth` are listed. The table of contents is
+    /// empty if there are no such headings.
+    pub fn toc(&self, max_depth: u8) -> String {
+        let mut toc = String::new();
+        // Level of the headings in each open list
+        let mut levels: Vec<u8> = Vec::new();
+
+        for heading in self.headers.iter().filter(|h| h.level < max_depth) {
+            while levels.last().is_some_and(|&level| level > heading.level) {
+                toc.push_str("</li>\n</ol>\n");
+                levels.pop();
+            }
+
+            match levels.last() {
+                Some(&level) if level == heading.level => toc.push_str("</li>\n"),
+                _ => {
+                    toc.push_str("<ol>\n");
+                    levels.push(heading.level);
+                }
+            }
+
+            let _ = write!(
+                toc,
+                r##"<li><a href="#{}">{}</a>"##,
+                heading.id, heading.text_html
+            );
+        }
+
+        if levels.is_empty() {
+            return toc;
+        }
+
+        for _ in levels {
+            toc.push_str("</li>\n</ol>\n");
+        }
+        format!("<nav class=\"toc\">\n{toc}</nav>\n")
+    }
+}
+
+/// Text of the heading being rendered
+#[derive(Default)]
+struct HeadingText {
+    /// Escaped `id` attribute of the heading
+    id: String,
+    plain: String,
+    html: String,
+}
+
+impl HeadingText {
+    /// Record an event inside the heading
+    ///
+    /// Links, images and footnote references are not kept, as the HTML is used in table of
+    /// contents.
+    fn push(&mut self, event: &Event, math: MathOutput) -> Result<(), WriterError> {
+        match event {
+            Text(text) => {
+                self.plain.push_str(text);
+                escape_html(&mut self.html, text)?;
+            }
+            Code(text) => {
+                self.plain.push_str(text);
+                self.html.push_str("<code>");
+                escape_html(&mut self.html, text)?;
+                self.html.push_str("</code>");
+            }
+            InlineMath(text) => {
+                self.plain.push_str(text);
+                self.html.push_str(&math_html(text, "inline", math)?);
+            }
+            SoftBreak | HardBreak => {
+                self.plain.push(' ');
+                self.html.push(' ');
+            }
+            Start(Tag::Emphasis) => self.html.push_str("<em>"),
+            End(TagEnd::Emphasis) => self.html.push_str("</em>"),
+            Start(Tag::Strong) => self.html.push_str("<strong>"),
+            End(TagEnd::Strong) => self.html.push_str("</strong>"),
+            Start(Tag::Strikethrough) => self.html.push_str("<del>"),
+            End(TagEnd::Strikethrough) => self.html.push_str("</del>"),
+            _ => (),
+        }
+        Ok(())
+    }
 }
 
 struct HeadingStack {
@@ -110,6 +462,17 @@ impl HeadingStack {
     }
 }
 
+/// Class and title of the admonition written for a GitHub alert (`> [!NOTE]`)
+fn admonition(kind: BlockQuoteKind) -> (&'static str, &'static str) {
+    match kind {
+        BlockQuoteKind::Note => ("note", "Note"),
+        BlockQuoteKind::Tip => ("tip", "Tip"),
+        BlockQuoteKind::Important => ("important", "Important"),
+        BlockQuoteKind::Warning => ("warning", "Warning"),
+        BlockQuoteKind::Caution => ("caution", "Caution"),
+    }
+}
+
 enum TableState {
     Head,
     Body,
@@ -128,17 +491,41 @@ struct HtmlWriter<'a, I, W> {
     /// Whether if inside a metadata block (text should not be written)
     in_non_writing_block: bool,
 
+    /// Whether inside a raw HTML block (text is written as is)
+    in_raw_block: bool,
+
+    /// Language of the current code block
     code: Option<CowStr<'a>>,
+    /// Text of the current highlighted code block
+    code_text: String,
+    code_lines: LineOptions,
+    code_title: Option<String>,
     syntax: Renderer<'a>,
+    syntax_config: &'a SyntaxConfig<'a>,
+    highlight_output: Output,
 
     table_state: TableState,
     table_alignments: Vec<Alignment>,
     table_cell_index: usize,
     numbers: HashMap<CowStr<'a>, usize>,
+    /// Text of the footnote definitions, duplicated in the references
+    footnotes: HashMap<CowStr<'a>, String>,
+    /// Number of references already written to each footnote
+    footnote_references: HashMap<CowStr<'a>, usize>,
+    /// Number of display math blocks already written
+    equation_count: usize,
 
     header_stack: HeadingStack,
-    current_header: Option<String>,
-    headers: Vec<HeadingInfo>,
+    current_header: Option<HeadingText>,
+    /// Level of the headings of the open `<section>`s
+    sections: Vec<HeadingLevel>,
+    /// Depth of the blocks containing the current event (quotes, lists, ...), the headings inside
+    /// them don't start a section
+    containers: usize,
+    rendered: Rendered,
+
+    options: RenderOptions,
+    site: Site<'a>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -147,6 +534,97 @@ enum WriterError {
     Io(#[from] std::io::Error),
     #[error("fmt error")]
     Fmt(#[from] std::fmt::Error),
+    #[error("highlight error")]
+    Highlight(#[from] ts_highlight_html::Error),
+    #[error("code block template error: {0}")]
+    CodeBlock(String),
+}
+
+/// Options of a fenced code block, parsed from its info string (e.g. `rust,linenos,hl=3-5`)
+#[derive(Debug, Default)]
+pub struct CodeInfo<'i> {
+    pub lang: &'i str,
+    lines: LineOptions,
+    pub title: Option<&'i str>,
+    /// File included in the code block (`file=examples/demo.rs`)
+    pub file: Option<&'i str>,
+    /// Lines of the included file (`lines=10-42`), left unparsed so that they can be reported
+    pub file_lines: Option<&'i str>,
+}
+
+/// Parse a line range (`3` or `3-5`)
+pub fn parse_range(range: &str) -> Option<RangeInclusive<usize>> {
+    match range.split_once('-') {
+        Some((start, end)) => Some(start.parse().ok()?..=end.parse().ok()?),
+        None => {
+            let line = range.parse().ok()?;
+            Some(line..=line)
+        }
+    }
+}
+
+/// Options of the info string of a fenced code block, separated by commas or whitespace outside
+/// of double quotes (`title="Hello world.rs"`)
+fn info_tokens(info: &str) -> impl Iterator<Item = &str> {
+    let separator = |c: char| c == ',' || c.is_whitespace();
+    let mut rest = info;
+    std::iter::from_fn(move || {
+        rest = rest.trim_start_matches(separator);
+        if rest.is_empty() {
+            return None;
+        }
+
+        let mut quoted = false;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                if c == '"' {
+                    quoted = !quoted;
+                }
+                !quoted && separator(c)
+            })
+            .map_or(rest.len(), |(end, _)| end);
+        let (token, tail) = rest.split_at(end);
+        rest = tail;
+        Some(token)
+    })
+}
+
+impl<'i> CodeInfo<'i> {
+    pub fn parse(info: &'i str) -> Self {
+        let mut code_info = CodeInfo::default();
+        // Ranges following `hl=` are all highlighted (`hl=1,3-5`)
+        let mut in_hl = false;
+
+        for (idx, token) in info_tokens(info).enumerate() {
+            if let Some(range) = token.strip_prefix("hl=") {
+                in_hl = true;
+                code_info.lines.highlighted.extend(parse_range(range));
+            } else if token == "linenos" {
+                in_hl = false;
+                code_info.lines.numbers = true;
+            } else if let Some(title) = token.strip_prefix("title=") {
+                in_hl = false;
+                let unquoted = title.strip_prefix('"').and_then(|t| t.strip_suffix('"'));
+                code_info.title = Some(unquoted.unwrap_or(title));
+            } else if let Some(file) = token.strip_prefix("file=") {
+                in_hl = false;
+                code_info.file = Some(file);
+            } else if let Some(lines) = token.strip_prefix("lines=") {
+                in_hl = false;
+                code_info.file_lines = Some(lines);
+            } else if let Some(range) = parse_range(token).filter(|_| in_hl) {
+                code_info.lines.highlighted.push(range);
+            } else if idx == 0 {
+                code_info.lang = token;
+            } else {
+                // Unknown options are ignored
+                in_hl = false;
+            }
+        }
+
+        code_info
+    }
 }
 
 impl<'a, I, W> HtmlWriter<'a, I, W>
@@ -154,21 +632,46 @@ where
     I: Iterator<Item = Event<'a>>,
     W: Write,
 {
-    fn new(iter: I, writer: W, syntax: &'a SyntaxConfig) -> Self {
+    fn new(
+        iter: I,
+        writer: W,
+        syntax: &'a SyntaxConfig,
+        options: RenderOptions,
+        footnotes: HashMap<CowStr<'a>, String>,
+        equations: Vec<EquationInfo>,
+        cross_references: Vec<CrossReference>,
+    ) -> Self {
         Self {
             iter,
             writer,
             syntax: Renderer::new(syntax),
+            syntax_config: syntax,
+            highlight_output: syntax.output(),
             code: None,
+            code_text: String::new(),
+            code_lines: LineOptions::default(),
+            code_title: None,
             end_newline: true,
             in_non_writing_block: false,
+            in_raw_block: false,
             table_state: TableState::Head,
             table_alignments: vec![],
             table_cell_index: 0,
             numbers: HashMap::new(),
-            headers: Vec::new(),
+            footnotes,
+            footnote_references: HashMap::new(),
+            equation_count: 0,
+            rendered: Rendered {
+                equations,
+                cross_references,
+                ..Default::default()
+            },
             current_header: None,
             header_stack: HeadingStack::new(),
+            sections: Vec::new(),
+            containers: 0,
+            options,
+            site: Site::default(),
         }
     }
 
@@ -181,9 +684,6 @@ where
     /// Writes a buffer, and tracks whether or not a newline was written.
     #[inline]
     fn write(&mut self, s: &[u8]) -> Result<(), WriterError> {
-        if let Some(header) = &mut self.current_header {
-            header.write_str(&String::from_utf8_lossy(s))?
-        }
         self.writer.write_all(s)?;
 
         if !s.is_empty() {
@@ -192,8 +692,38 @@ where
         Ok(())
     }
 
-    fn run(mut self) -> Result<Vec<HeadingInfo>> {
+    fn run(mut self) -> Result<Rendered> {
         while let Some(event) = self.iter.next() {
+            if let Some(feature) = feature(&event) {
+                *self.rendered.features.entry(feature).or_default() += 1;
+            }
+            if let Some(header) = &mut self.current_header {
+                header.push(&event, self.options.math)?;
+            }
+            match &event {
+                Start(
+                    Tag::BlockQuote(_)
+                    | Tag::List(_)
+                    | Tag::DefinitionList
+                    | Tag::FootnoteDefinition(_),
+                ) => self.containers += 1,
+                End(
+                    TagEnd::BlockQuote(_)
+                    | TagEnd::List(_)
+                    | TagEnd::DefinitionList
+                    | TagEnd::FootnoteDefinition,
+                ) => self.containers -= 1,
+                // The `<details>` written in the articles count as well, as they are closed by the
+                // same rule
+                Html(html) if opens_details(html) => self.containers += 1,
+                Html(html) if html.starts_with("</details>") => {
+                    self.containers = self.containers.saturating_sub(1)
+                }
+                // The footnotes are not part of the last section
+                Html(html) if html.starts_with(FOOTNOTES_SECTION) => self.close_sections(None)?,
+                _ => (),
+            }
+
             match event {
                 Start(tag) => {
                     self.start_tag(tag)?;
@@ -201,47 +731,48 @@ where
                 End(tag) => {
                     self.end_tag(tag)?;
                 }
+                Text(text) if self.in_raw_block => {
+                    self.write(text.as_bytes())?;
+                }
                 Text(text) => {
                     match self.code.as_deref() {
-                        None | Some("") => {
-                            if let Some(header) = &mut self.current_header {
-                                escape_html(header, &text)?;
-                            }
-                            escape_html(IoWriter(&mut self.writer), &text)?;
+                        None => {
+                            self.rendered.words += text.split_whitespace().count();
+                            self.text_with_references(&text)?;
                         }
-                        Some(lang) => {
-                            let rendered = self.syntax.render(lang, &text)?;
-                            for line in rendered.lines() {
-                                self.write(br#"<span class="newline">"#)?;
-                                self.write(line)?;
-                                self.write(b"\n")?;
-                                self.write(br#"</span>"#)?;
-                            }
+                        // The code is given to the template of the website once complete
+                        Some("") if self.site.code_blocks.is_none() => {
+                            escape_html(IoWriter(&mut self.writer), &text)?
                         }
+                        Some(_) => self.code_text.push_str(&text),
                     }
                     self.end_newline = text.ends_with('\n');
                 }
                 Code(text) => {
                     self.write(b"<code>")?;
-                    if let Some(header) = &mut self.current_header {
-                        escape_html(header, &text)?;
-                    }
                     escape_html(IoWriter(&mut self.writer), &text)?;
                     self.write(b"</code>")?;
                 }
                 InlineMath(text) => {
-                    let expr = asciimath_rs::parse(text);
-                    let mathml = expr.to_mathml();
-                    self.write(br#"<math display="inline">"#)?;
-                    self.write(mathml.as_bytes())?;
-                    self.write(b"</math>")?;
+                    let html = math_html(&text, "inline", self.options.math)?;
+                    self.write(html.as_bytes())?;
                 }
                 DisplayMath(text) => {
-                    let expr = asciimath_rs::parse(text);
-                    let mathml = expr.to_mathml();
-                    self.write(br#"<math display="block">"#)?;
-                    self.write(mathml.as_bytes())?;
-                    self.write(b"</math>")?;
+                    let (math, _) = equation_label(&text);
+                    let html = math_html(&math, "block", self.options.math)?;
+
+                    let equation = &self.rendered.equations[self.equation_count];
+                    self.equation_count += 1;
+                    let (number, id) = (equation.number, equation.id.clone());
+
+                    self.write(br#"<span class="equation" id=""#)?;
+                    escape_html(IoWriter(&mut self.writer), &id)?;
+                    self.write(b"\">")?;
+                    self.write(html.as_bytes())?;
+                    write!(
+                        &mut self.writer,
+                        r#"<span class="equation-number">({number})</span></span>"#
+                    )?;
                 }
                 Html(html) | InlineHtml(html) => {
                     self.write(html.as_bytes())?;
@@ -261,11 +792,19 @@ where
                 }
                 FootnoteReference(name) => {
                     let len = self.numbers.len() + 1;
-                    self.write(b"<sup class=\"footnote-reference\"><a href=\"#")?;
-                    if let Some(header) = &mut self.current_header {
-                        escape_html(header, &name)?;
-                    }
+                    self.write(b"<sup class=\"footnote-reference\" id=\"")?;
+                    let reference = self.footnote_references.entry(name.clone()).or_default();
+                    *reference += 1;
+                    let id = footnote_reference_id(&name, *reference);
+                    escape_html(IoWriter(&mut self.writer), &id)?;
+                    self.write(b"\"><a href=\"#")?;
+                    escape_html(IoWriter(&mut self.writer), &name)?;
+                    self.write(b"\" aria-describedby=\"")?;
                     escape_html(IoWriter(&mut self.writer), &name)?;
+                    if let Some(text) = self.footnotes.get(&name) {
+                        self.writer.write_all(b"\" data-footnote=\"")?;
+                        escape_html(IoWriter(&mut self.writer), text)?;
+                    }
                     self.write(b"\">")?;
                     let number = *self.numbers.entry(name).or_insert(len);
                     write!(&mut self.writer, "{}", number)?;
@@ -279,7 +818,23 @@ where
                 }
             }
         }
-        Ok(self.headers)
+        self.close_sections(None)?;
+        Ok(self.rendered)
+    }
+
+    /// Close the open sections of the headings of `level` or deeper, or all of them
+    fn close_sections(&mut self, level: Option<HeadingLevel>) -> Result<(), WriterError> {
+        while let Some(&open) = self.sections.last() {
+            if level.is_some_and(|level| open < level) {
+                break;
+            }
+            self.sections.pop();
+            if !self.end_newline {
+                self.write_newline()?;
+            }
+            self.write(b"</section>\n")?;
+        }
+        Ok(())
     }
 
     /// Writes the start of an HTML tag.
@@ -295,11 +850,30 @@ where
             }
             Tag::Heading {
                 level,
-                id: _,
+                id,
                 classes,
                 attrs,
             } => {
                 self.header_stack.enter(level);
+                // A custom id (`# Title {#id}`) replaces the numbered one, it is still numbered
+                let mut heading = HeadingText::default();
+                match id {
+                    Some(id) if !id.is_empty() => escape_html(&mut heading.id, &id)?,
+                    _ => heading.id = format!("header-{}", self.header_stack.repr()),
+                }
+                if self.options.sections && self.containers == 0 {
+                    self.close_sections(Some(level))?;
+                    if !self.end_newline {
+                        self.write_newline()?;
+                    }
+                    writeln!(
+                        &mut self.writer,
+                        "<section aria-labelledby=\"{}\">",
+                        heading.id
+                    )?;
+                    self.end_newline = true;
+                    self.sections.push(level);
+                }
                 if self.end_newline {
                     self.end_newline = false;
                     self.write(b"<")?;
@@ -307,19 +881,13 @@ where
                     self.write(b"\n<")?;
                 }
                 write!(&mut self.writer, "{}", level)?;
-                self.write(format!(r#" id="header-{}" "#, self.header_stack.repr()).as_bytes())?;
+                self.write(format!(r#" id="{}" "#, heading.id).as_bytes())?;
                 let mut classes = classes.iter();
                 if let Some(class) = classes.next() {
                     self.write(b" class=\"")?;
-                    if let Some(header) = &mut self.current_header {
-                        escape_html(header, class)?;
-                    }
                     escape_html(IoWriter(&mut self.writer), class)?;
                     for class in classes {
                         self.write(b" ")?;
-                        if let Some(header) = &mut self.current_header {
-                            escape_html(header, class)?;
-                        }
                         escape_html(IoWriter(&mut self.writer), class)?;
                     }
                     self.write(b"\"")?;
@@ -336,7 +904,7 @@ where
                     }
                 }
                 self.write(b">")?;
-                self.current_header = Some(String::new());
+                self.current_header = Some(heading);
                 Ok(())
             }
             Tag::Table(alignments) => {
@@ -368,41 +936,63 @@ where
                     _ => self.write(b">"),
                 }
             }
-            Tag::BlockQuote(kind) => {
-                let class = match kind {
-                    None => "",
-                    Some(kind) => match kind {
-                        BlockQuoteKind::Note => r#" class="markdown-alert-note""#,
-                        BlockQuoteKind::Tip => r#" class="markdown-alert-tip""#,
-                        BlockQuoteKind::Important => r#" class="markdown-alert-important""#,
-                        BlockQuoteKind::Warning => r#" class="markdown-alert-warning""#,
-                        BlockQuoteKind::Caution => r#" class="markdown-alert-caution""#,
-                    },
-                };
-
+            Tag::BlockQuote(None) => {
                 if self.end_newline {
-                    self.write(format!("<blockquote{class}>\n").as_bytes())
+                    self.write(b"<blockquote>\n")
                 } else {
-                    self.write(format!("\n<blockquote{class}>\n").as_bytes())
+                    self.write(b"\n<blockquote>\n")
                 }
             }
+            Tag::BlockQuote(Some(kind)) => {
+                let (class, title) = admonition(kind);
+                if !self.end_newline {
+                    self.write_newline()?;
+                }
+                write!(
+                    &mut self.writer,
+                    "<div class=\"admonition {class}\">\n<p class=\"admonition-title\">{title}</p>\n"
+                )?;
+                self.end_newline = true;
+                Ok(())
+            }
             Tag::CodeBlock(info) => {
                 if !self.end_newline {
                     self.write_newline()?;
                 }
                 match info {
+                    CodeBlockKind::Fenced(info) if info.trim() == RAW_HTML => {
+                        self.in_raw_block = true;
+                        Ok(())
+                    }
                     CodeBlockKind::Fenced(info) => {
-                        let lang = info.split(' ').next().unwrap();
-                        if lang.is_empty() {
+                        let info = CodeInfo::parse(&info);
+                        if info.lang.is_empty() {
                             self.code = Some("".into());
                         } else {
-                            self.code = Some(info.clone());
+                            self.rendered.languages.push(info.lang.to_owned());
+                            if !self.syntax_config.has_language(info.lang) {
+                                self.rendered.unhighlighted.push(info.lang.to_owned());
+                            }
+                            self.code = Some(info.lang.to_owned().into());
                         }
-                        self.write(
-                            br#"<pre style="background-color: #080808; color: #c6c6c6"><code>"#,
-                        )
+                        self.code_lines = info.lines;
+                        self.code_title = info.title.map(ToOwned::to_owned);
+                        if self.site.code_blocks.is_some() {
+                            return Ok(());
+                        }
+                        self.start_code_wrapper(info.lang)?;
+                        match self.highlight_output {
+                            Output::Inline => self.write(
+                                br#"<pre style="background-color: #080808; color: #c6c6c6"><code>"#,
+                            ),
+                            Output::Classes => self.write(br#"<pre class="highlight"><code>"#),
+                        }
+                    }
+                    CodeBlockKind::Indented => {
+                        self.code_title = None;
+                        self.start_code_wrapper("")?;
+                        self.write(b"<pre><code>")
                     }
-                    CodeBlockKind::Indented => self.write(b"<pre><code>"),
                 }
             }
             Tag::List(Some(1)) => {
@@ -485,6 +1075,16 @@ where
                     self.write(b"\" title=\"")?;
                     escape_html(IoWriter(&mut self.writer), &title)?;
                 }
+                if self.site.is_external(&dest_url) {
+                    let ExternalLinks { new_tab, class } = &self.site.external_links;
+                    if let Some(class) = class {
+                        self.writer.write_all(b"\" class=\"")?;
+                        escape_html(IoWriter(&mut self.writer), class)?;
+                    }
+                    if *new_tab {
+                        self.write(b"\" target=\"_blank\" rel=\"noopener noreferrer")?;
+                    }
+                }
                 self.write(b"\">")
             }
             Tag::Image {
@@ -501,6 +1101,14 @@ where
                     self.write(b"\" title=\"")?;
                     escape_html(IoWriter(&mut self.writer), &title)?;
                 }
+                if let Some(LocalImage { width, height, .. }) =
+                    self.site.images.get(dest_url.as_ref())
+                {
+                    write!(
+                        &mut self.writer,
+                        "\" width=\"{width}\" height=\"{height}\" loading=\"lazy\" decoding=\"async"
+                    )?;
+                }
                 self.write(b"\" />")
             }
             Tag::FootnoteDefinition(name) => {
@@ -509,7 +1117,7 @@ where
                 } else {
                     self.write(b"\n<div class=\"footnote-definition\" id=\"")?;
                 }
-                escape_html(IoWriter(&mut self.writer), &*name)?;
+                escape_html(IoWriter(&mut self.writer), &name)?;
                 self.write(b"\"><sup class=\"footnote-definition-label\">")?;
                 let len = self.numbers.len() + 1;
                 let number = *self.numbers.entry(name).or_insert(len);
@@ -534,11 +1142,18 @@ where
                     .current_header
                     .take()
                     .expect("header end but did not start");
-                self.headers.push(HeadingInfo {
+                self.rendered.headers.push(HeadingInfo {
                     level: self.header_stack.effective_level(),
-                    text,
+                    text_plain: text.plain,
+                    text_html: text.html,
                     number: self.header_stack.repr(),
+                    id: text.id.clone(),
                 });
+                if self.options.heading_anchors {
+                    self.write(
+                        format!(r##"<a class="anchor" href="#{}">#</a>"##, text.id).as_bytes(),
+                    )?;
+                }
                 self.write(b"</")?;
                 write!(&mut self.writer, "{}", level)?;
                 self.write(b">\n")?;
@@ -564,12 +1179,33 @@ where
                 }
                 self.table_cell_index += 1;
             }
-            TagEnd::BlockQuote(_) => {
+            TagEnd::BlockQuote(None) => {
                 self.write(b"</blockquote>\n")?;
             }
+            TagEnd::BlockQuote(Some(_)) => {
+                self.write(b"</div>\n")?;
+            }
+            TagEnd::CodeBlock if self.in_raw_block => {
+                self.in_raw_block = false;
+            }
+            TagEnd::CodeBlock if self.code.is_some() && self.site.code_blocks.is_some() => {
+                self.code_block_template()?;
+            }
             TagEnd::CodeBlock => {
-                self.code = None;
+                if let Some(lang) = self.code.take().filter(|lang| !lang.is_empty()) {
+                    self.syntax.render_lines_to(
+                        &lang,
+                        &self.code_text,
+                        &self.code_lines,
+                        &mut self.writer,
+                    )?;
+                    // Keep the allocation for the next code blocks
+                    self.code_text.clear();
+                }
                 self.write(b"</code></pre>")?;
+                if self.options.wrap_code_blocks {
+                    self.write(b"\n</div>\n")?;
+                }
             }
             TagEnd::List(true) => {
                 self.write(b"</ol>\n")?;
@@ -612,6 +1248,124 @@ where
         Ok(())
     }
 
+    /// Opens the `<div class="code-block">` of a code block in `lang`, if code blocks are wrapped
+    fn start_code_wrapper(&mut self, lang: &str) -> Result<(), WriterError> {
+        if !self.options.wrap_code_blocks {
+            return Ok(());
+        }
+
+        self.write(br#"<div class="code-block""#)?;
+        if !lang.is_empty() {
+            self.write(br#" data-lang=""#)?;
+            escape_html(IoWriter(&mut self.writer), lang)?;
+            self.write(b"\"")?;
+        }
+        self.write(b">\n")?;
+        if let Some(title) = self.code_title.take() {
+            self.write(br#"<div class="code-title">"#)?;
+            escape_html(IoWriter(&mut self.writer), &title)?;
+            self.write(b"</div>\n")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the current fenced code block with the code block template of the website
+    fn code_block_template(&mut self) -> Result<(), WriterError> {
+        let (Some(lang), Some(template)) = (self.code.take(), self.site.code_blocks) else {
+            return Ok(());
+        };
+
+        let mut html = Vec::new();
+        if lang.is_empty() {
+            escape_html(IoWriter(&mut html), &self.code_text)?;
+        } else {
+            self.syntax
+                .render_lines_to(&lang, &self.code_text, &self.code_lines, &mut html)?;
+        }
+        let html = String::from_utf8(html).expect("the highlighted code is UTF-8");
+
+        let block = CodeBlock {
+            html: &html,
+            language: &lang,
+            title: self.code_title.as_deref(),
+            line_numbers: self.code_lines.numbers,
+            highlighted: self
+                .code_lines
+                .highlighted
+                .iter()
+                .map(|range| [*range.start(), *range.end()])
+                .collect(),
+            lines: self.code_text.lines().count(),
+            style: match self.highlight_output {
+                Output::Inline => Some("background-color: #080808; color: #c6c6c6"),
+                Output::Classes => None,
+            },
+        };
+        let rendered = template
+            .render(&block)
+            .map_err(|e| WriterError::CodeBlock(format!("{e:#}")))?;
+        self.code_text.clear();
+        self.write(rendered.as_bytes())
+    }
+
+    /// Writes text, replacing the `\ref{name}` by links to the equations and the `[@fig:name]` by
+    /// links to the labelled figures, tables and listings
+    fn text_with_references(&mut self, text: &str) -> Result<(), WriterError> {
+        let mut rest = text;
+        loop {
+            let equation = rest.find("\\ref{").map(|start| (start, "\\ref{", '}'));
+            let cross_reference = rest.find("[@").map(|start| (start, "[@", ']'));
+            let Some((start, prefix, terminator)) = [equation, cross_reference]
+                .into_iter()
+                .flatten()
+                .min_by_key(|(start, _, _)| *start)
+            else {
+                break;
+            };
+            let Some(length) = rest[start..].find(terminator) else {
+                break;
+            };
+            let end = start + length + 1;
+
+            let label = rest[start + prefix.len()..end - 1].trim();
+            let link = if prefix == "[@" {
+                self.rendered
+                    .cross_references
+                    .iter()
+                    .find(|reference| reference.label == label)
+                    .map(|reference| ("crossref", reference.id.clone(), reference.name()))
+            } else {
+                self.rendered
+                    .equations
+                    .iter()
+                    .find(|equation| equation.label.as_deref() == Some(label))
+                    .map(|equation| {
+                        (
+                            "equation-ref",
+                            equation.id.clone(),
+                            format!("({})", equation.number),
+                        )
+                    })
+            };
+            // Unknown references are kept as is
+            let Some((class, id, text)) = link else {
+                escape_html(IoWriter(&mut self.writer), &rest[..end])?;
+                rest = &rest[end..];
+                continue;
+            };
+
+            escape_html(IoWriter(&mut self.writer), &rest[..start])?;
+            write!(&mut self.writer, r##"<a class="{class}" href="#"##)?;
+            escape_html(IoWriter(&mut self.writer), &id)?;
+            self.writer.write_all(b"\">")?;
+            escape_html(IoWriter(&mut self.writer), &text)?;
+            self.writer.write_all(b"</a>")?;
+            rest = &rest[end..];
+        }
+        escape_html(IoWriter(&mut self.writer), rest)?;
+        Ok(())
+    }
+
     // run raw text, consuming end tag
     fn raw_text(&mut self) -> Result<(), WriterError> {
         let mut nest = 0;
@@ -655,6 +1409,510 @@ where
     }
 }
 
+/// Markdown extension used by `event`, for the statistics of the documents
+fn feature(event: &Event) -> Option<&'static str> {
+    Some(match event {
+        Start(Tag::Table(_)) => "tables",
+        FootnoteReference(_) => "footnotes",
+        InlineMath(_) | DisplayMath(_) => "math",
+        TaskListMarker(_) => "task lists",
+        Start(Tag::Strikethrough) => "strikethrough",
+        Start(Tag::BlockQuote(Some(_))) => "alerts",
+        Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) if info.trim() == RAW_HTML => "raw html",
+        Html(html) if html.starts_with("<details>") => "details",
+        Html(html) | InlineHtml(html)
+            if html.starts_with("<picture>") && html.contains("prefers-color-scheme") =>
+        {
+            "dark images"
+        }
+        _ => return None,
+    })
+}
+
+/// Collect the plain text of the footnote definitions, so that it can be written in the references
+/// appearing before the definitions
+fn footnote_texts<'a>(events: &[Event<'a>]) -> HashMap<CowStr<'a>, String> {
+    let mut footnotes = HashMap::new();
+    let mut current: Option<(CowStr<'a>, String)> = None;
+
+    for event in events {
+        match event {
+            Start(Tag::FootnoteDefinition(name)) => {
+                current = Some((name.clone(), String::new()));
+            }
+            End(TagEnd::FootnoteDefinition) => {
+                if let Some((name, text)) = current.take() {
+                    footnotes.insert(name, text.split_whitespace().join(" "));
+                }
+            }
+            Text(text) | Code(text) | InlineMath(text) | DisplayMath(text) => {
+                if let Some((_, footnote)) = &mut current {
+                    footnote.push_str(text);
+                }
+            }
+            SoftBreak | HardBreak | End(TagEnd::Paragraph) => {
+                if let Some((_, footnote)) = &mut current {
+                    footnote.push(' ');
+                }
+            }
+            _ => (),
+        }
+    }
+
+    footnotes
+}
+
+/// The `id` of the `n`-th reference to the footnote `name`, starting at 1 (`fnref-name`, then
+/// `fnref-name-2`, ...)
+fn footnote_reference_id(name: &str, n: usize) -> String {
+    match n {
+        1 => format!("fnref-{name}"),
+        _ => format!("fnref-{name}-{n}"),
+    }
+}
+
+/// Links from a footnote definition back to the `count` references to the footnote `name`
+fn footnote_backlinks(name: &str, count: usize) -> String {
+    let mut html = String::new();
+    for n in 1..=count {
+        html.push_str(" <a href=\"#");
+        escape_href(&mut html, &footnote_reference_id(name, n))
+            .expect("writing to a string can't fail");
+        write!(
+            html,
+            "\" class=\"footnote-backref\" aria-label=\"Back to reference {n}\">\u{21a9}"
+        )
+        .expect("writing to a string can't fail");
+        if n > 1 {
+            write!(html, "<sup>{n}</sup>").expect("writing to a string can't fail");
+        }
+        html.push_str("</a>");
+    }
+    html
+}
+
+/// Move the footnote definitions to a `<section class="footnotes">` at the end of the document,
+/// in the order of their first reference, each one ending with links back to its references
+fn footnotes_section(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut definitions = Vec::new();
+    let mut current: Option<(CowStr, Vec<Event>)> = None;
+    let mut references: HashMap<CowStr, usize> = HashMap::new();
+
+    for event in events {
+        if let FootnoteReference(name) = &event {
+            *references.entry(name.clone()).or_default() += 1;
+        }
+
+        match event {
+            Start(Tag::FootnoteDefinition(name)) => {
+                current = Some((name.clone(), vec![Start(Tag::FootnoteDefinition(name))]));
+            }
+            End(TagEnd::FootnoteDefinition) => {
+                let (name, mut definition) = current.take().expect("definition end without start");
+                definition.push(End(TagEnd::FootnoteDefinition));
+                definitions.push((name, definition));
+            }
+            event => match &mut current {
+                Some((_, definition)) => definition.push(event),
+                None => output.push(event),
+            },
+        }
+    }
+
+    if definitions.is_empty() {
+        return output;
+    }
+
+    // Unreferenced footnotes stay in the order of their definitions, after the others
+    definitions.sort_by_cached_key(|(name, _)| {
+        output
+            .iter()
+            .position(|event| matches!(event, FootnoteReference(reference) if reference == name))
+            .unwrap_or(usize::MAX)
+    });
+
+    output.push(Html(format!("{FOOTNOTES_SECTION}\n").into()));
+    for (name, mut definition) in definitions {
+        let count = references.get(&name).copied().unwrap_or_default();
+        if count > 0 {
+            // The links end the last paragraph of the definition, if it ends with one
+            let end = definition.len() - 1;
+            let position = match definition[end - 1] {
+                End(TagEnd::Paragraph) => end - 1,
+                _ => end,
+            };
+            definition.insert(
+                position,
+                InlineHtml(footnote_backlinks(&name, count).into()),
+            );
+        }
+        output.extend(definition);
+    }
+    output.push(Html("</section>\n".into()));
+
+    output
+}
+
+/// Split the dark variant of an image at the start of the text following it
+/// (`![alt](light.png){dark=dark.png}`), returning its URL and the rest of the text
+fn dark_variant(text: &str) -> Option<(&str, &str)> {
+    let (url, rest) = text.strip_prefix("{dark=")?.split_once('}')?;
+    Some((url.trim(), rest))
+}
+
+/// Wrap the images with a dark variant or local variants in a `<picture>`.
+///
+/// Browsers using a dark color scheme display the dark variant instead, and the others pick the
+/// first format of the local variants they support.
+fn pictures<'a>(events: Vec<Event<'a>>, images: &LocalImages) -> Vec<Event<'a>> {
+    let mut output = Vec::with_capacity(events.len());
+    // Position and URL of the current image
+    let mut image_start = None;
+    let mut depth = 0;
+
+    let mut events = events.into_iter().peekable();
+    while let Some(event) = events.next() {
+        match &event {
+            Start(Tag::Image { dest_url, .. }) => {
+                if depth == 0 {
+                    image_start = Some((output.len(), dest_url.to_string()));
+                }
+                depth += 1;
+            }
+            End(TagEnd::Image) => depth -= 1,
+            _ => (),
+        }
+
+        let is_image_end = matches!(event, End(TagEnd::Image)) && depth == 0;
+        output.push(event);
+        if !is_image_end {
+            continue;
+        }
+
+        let (start, url) = image_start.take().expect("image end without a start");
+        let dark = events.next_if(|next| match next {
+            Text(text) => dark_variant(text).is_some(),
+            _ => false,
+        });
+        let sources = images
+            .get(&url)
+            .map(|image| image.sources.as_slice())
+            .unwrap_or_default();
+        if dark.is_none() && sources.is_empty() {
+            continue;
+        }
+
+        let mut picture = String::from("<picture>");
+        let mut rest = None;
+        if let Some(Text(text)) = &dark {
+            let (url, after) = dark_variant(text).expect("checked by next_if");
+            picture.push_str("<source srcset=\"");
+            escape_href(&mut picture, url).expect("writing to a string can't fail");
+            picture.push_str("\" media=\"(prefers-color-scheme: dark)\" />");
+            rest = Some(after);
+        }
+        for source in sources {
+            picture.push_str("<source type=\"");
+            escape_html(&mut picture, &source.mime).expect("writing to a string can't fail");
+            picture.push_str("\" srcset=\"");
+            escape_html(&mut picture, &source.srcset).expect("writing to a string can't fail");
+            picture.push_str("\" />");
+        }
+        output.insert(start, InlineHtml(picture.into()));
+        output.push(InlineHtml("</picture>".into()));
+        if let Some(rest) = rest.filter(|rest| !rest.is_empty()) {
+            output.push(Text(rest.to_owned().into()));
+        }
+    }
+
+    output
+}
+
+/// Title of a `::: details Title` marker opening a collapsible block
+fn details_title(text: &str) -> Option<&str> {
+    let title = text
+        .trim()
+        .strip_prefix(":::")?
+        .trim_start()
+        .strip_prefix("details")?;
+    if !title.is_empty() && !title.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(match title.trim() {
+        "" => "Details",
+        title => title,
+    })
+}
+
+/// Whether the `html` starts with a `<details>` tag, with or without attributes
+fn opens_details(html: &str) -> bool {
+    html.strip_prefix("<details")
+        .is_some_and(|rest| rest.starts_with(|c: char| c == '>' || c.is_ascii_whitespace()))
+}
+
+/// HTML opening a collapsible block
+fn details_open(title: &str) -> Event<'static> {
+    let mut html = String::from("<details>\n<summary>");
+    escape_html(&mut html, title).expect("writing to a string can't fail");
+    html.push_str("</summary>\n");
+    Html(html.into())
+}
+
+/// Turn the blocks between a `::: details Title` line and a `:::` line into collapsible
+/// `<details>` elements.
+///
+/// The markers are the first or last line of a paragraph, or a paragraph of their own. A block
+/// that is not closed ends with the element containing it.
+fn details(mut events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut output = Vec::with_capacity(events.len());
+    // Nesting depth of the elements, and the depth of each open block
+    let mut depth = 0;
+    let mut open: Vec<usize> = Vec::new();
+
+    let mut idx = 0;
+    while idx < events.len() {
+        let next = (events.get(idx + 1), events.get(idx + 2));
+        let title = match next {
+            (Some(Text(text)), Some(End(TagEnd::Paragraph) | SoftBreak)) => details_title(text),
+            _ => None,
+        };
+        let is_close = matches!(
+            next,
+            (Some(Text(text)), Some(End(TagEnd::Paragraph))) if text.trim() == ":::"
+        );
+
+        match (&events[idx], next.1) {
+            (Start(Tag::Paragraph), Some(End(TagEnd::Paragraph))) if title.is_some() => {
+                output.push(details_open(title.expect("checked by the guard")));
+                open.push(depth);
+                idx += 3;
+            }
+            (Start(Tag::Paragraph), Some(SoftBreak)) if title.is_some() => {
+                output.push(details_open(title.expect("checked by the guard")));
+                open.push(depth);
+                output.push(Start(Tag::Paragraph));
+                depth += 1;
+                idx += 3;
+            }
+            (Start(Tag::Paragraph), _) if is_close && open.last() == Some(&depth) => {
+                output.push(Html("</details>\n".into()));
+                open.pop();
+                idx += 3;
+            }
+            (SoftBreak, _) if is_close && open.last() == Some(&(depth - 1)) => {
+                output.push(End(TagEnd::Paragraph));
+                output.push(Html("</details>\n".into()));
+                open.pop();
+                depth -= 1;
+                idx += 3;
+            }
+            (event, _) => {
+                match event {
+                    Start(_) => depth += 1,
+                    End(_) => {
+                        depth -= 1;
+                        while open.last().is_some_and(|&open| open > depth) {
+                            output.push(Html("</details>\n".into()));
+                            open.pop();
+                        }
+                    }
+                    _ => (),
+                }
+                output.push(std::mem::replace(&mut events[idx], SoftBreak));
+                idx += 1;
+            }
+        }
+    }
+
+    output.extend(open.iter().map(|_| Html("</details>\n".into())));
+    output
+}
+
+/// Split a cross-reference label (`{#fig:arch}`) at the start of `text`, returning the kind, the
+/// label and the rest of the text
+fn label_prefix(text: &str) -> Option<(&str, &str, &str)> {
+    let (label, rest) = text.strip_prefix("{#")?.split_once('}')?;
+    let (kind, _) = label.split_once(':')?;
+    matches!(kind, "fig" | "tbl" | "lst").then_some((kind, label, rest))
+}
+
+/// Split the caption of a table (`: Results {#tbl:results}`), returning its text, the kind and the
+/// label
+fn table_caption(text: &str) -> Option<(&str, &str, &str)> {
+    text.strip_prefix(':')
+        .and_then(label_suffix)
+        .filter(|(_, kind, _)| *kind == "tbl")
+}
+
+/// Split a cross-reference label (`{#tbl:results}`) at the end of `text`, returning the rest of
+/// the text, the kind and the label
+fn label_suffix(text: &str) -> Option<(&str, &str, &str)> {
+    let text = text.trim_end();
+    let start = text.rfind("{#")?;
+    let (kind, label, rest) = label_prefix(&text[start..])?;
+    rest.is_empty().then_some((&text[..start], kind, label))
+}
+
+/// Labelled elements found by the cross-reference pass
+#[derive(Default)]
+struct CrossReferences {
+    list: Vec<CrossReference>,
+}
+
+impl CrossReferences {
+    /// Record an element with the `label`, returning the HTML opening its wrapper and the one
+    /// writing its number
+    fn add(&mut self, kind: &str, label: &str) -> (String, String) {
+        let number = self.list.iter().filter(|r| r.kind == kind).count() + 1;
+        let reference = CrossReference {
+            kind: kind.to_owned(),
+            number,
+            id: label.replacen(':', "-", 1),
+            label: label.to_owned(),
+        };
+
+        let (element, class) = match kind {
+            "fig" => ("span", "figure"),
+            "tbl" => ("div", "table"),
+            _ => ("div", "listing"),
+        };
+        let mut open = format!(r#"<{element} class="{class}" id=""#);
+        escape_html(&mut open, &reference.id).expect("writing to a string can't fail");
+        open.push_str("\">");
+        if element == "div" {
+            open.push('\n');
+        }
+        let mut number = format!(r#"<span class="{class}-number">"#);
+        escape_html(&mut number, &reference.name()).expect("writing to a string can't fail");
+        number.push_str("</span>");
+
+        self.list.push(reference);
+        (open, number)
+    }
+}
+
+/// Number the figures (`![alt](image.png){#fig:name}`), the tables (followed by a
+/// `: Caption {#tbl:name}` paragraph) and the code blocks (```` ```rust {#lst:name} ````) with a
+/// label, wrapping them in an element with the `id` of the label
+fn cross_references(events: Vec<Event<'_>>) -> (Vec<Event<'_>>, Vec<CrossReference>) {
+    let mut output = Vec::with_capacity(events.len());
+    let mut references = CrossReferences::default();
+    let mut image_start = None;
+    let mut table_start = None;
+    let mut listing_number = None;
+    let mut depth = 0;
+
+    let mut events = events.into_iter().peekable();
+    while let Some(event) = events.next() {
+        match event {
+            Start(Tag::Image { .. }) => {
+                if depth == 0 {
+                    // The image may be wrapped in a `<picture>` by its dark variant
+                    let picture = matches!(
+                        output.last(),
+                        Some(InlineHtml(html)) if html.starts_with("<picture>")
+                    );
+                    image_start = Some(output.len() - usize::from(picture));
+                }
+                depth += 1;
+                output.push(event);
+            }
+            End(TagEnd::Image) => {
+                depth -= 1;
+                output.push(event);
+                if depth != 0 {
+                    continue;
+                }
+
+                let start = image_start.take().expect("image end without a start");
+                if let Some(picture) = events
+                    .next_if(|next| matches!(next, InlineHtml(html) if &**html == "</picture>"))
+                {
+                    output.push(picture);
+                }
+                let Some(Text(text)) = events.next_if(|next| match next {
+                    Text(text) => label_prefix(text).is_some_and(|(kind, _, _)| kind == "fig"),
+                    _ => false,
+                }) else {
+                    continue;
+                };
+                let (kind, label, rest) = label_prefix(&text).expect("checked by next_if");
+
+                let (open, number) = references.add(kind, label);
+                output.insert(start, InlineHtml(open.into()));
+                output.push(InlineHtml(format!("{number}</span>").into()));
+                if !rest.is_empty() {
+                    output.push(Text(rest.to_owned().into()));
+                }
+            }
+            Start(Tag::Table(_)) => {
+                table_start = Some(output.len());
+                output.push(event);
+            }
+            End(TagEnd::Table) => {
+                output.push(event);
+                let start = table_start.take().expect("table end without a start");
+
+                // The caption is a paragraph of text right after the table. The events read
+                // ahead are written as they are if they are not a caption.
+                let Some(paragraph) = events.next_if(|next| matches!(next, Start(Tag::Paragraph)))
+                else {
+                    continue;
+                };
+                let text = events
+                    .next_if(|next| matches!(next, Text(text) if table_caption(text).is_some()));
+                let end = text
+                    .as_ref()
+                    .and_then(|_| events.next_if(|next| matches!(next, End(TagEnd::Paragraph))));
+                let text = match (text, end) {
+                    (Some(Text(text)), Some(_)) => text,
+                    (text, _) => {
+                        output.push(paragraph);
+                        output.extend(text);
+                        continue;
+                    }
+                };
+                let (caption, kind, label) = table_caption(&text).expect("checked by next_if");
+
+                let (open, number) = references.add(kind, label);
+                let mut caption_html = format!(r#"<p class="caption">{number}: "#);
+                escape_html(&mut caption_html, caption.trim())
+                    .expect("writing to a string can't fail");
+                caption_html.push_str("</p>\n</div>\n");
+
+                output.insert(start, Html(open.into()));
+                output.push(Html(caption_html.into()));
+            }
+            Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                let label = info
+                    .split_whitespace()
+                    .find_map(label_prefix)
+                    .filter(|(kind, _, rest)| *kind == "lst" && rest.is_empty());
+                let Some((kind, label, _)) = label else {
+                    output.push(Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))));
+                    continue;
+                };
+
+                let (open, number) = references.add(kind, label);
+                let info = info.replace(&format!("{{#{label}}}"), "");
+                output.push(Html(open.into()));
+                output.push(Start(Tag::CodeBlock(CodeBlockKind::Fenced(info.into()))));
+                listing_number = Some(number);
+            }
+            End(TagEnd::CodeBlock) => {
+                output.push(event);
+                if let Some(number) = listing_number.take() {
+                    output.push(Html(format!("\n{number}\n</div>\n").into()));
+                }
+            }
+            event => output.push(event),
+        }
+    }
+
+    (output, references.list)
+}
+
 /// Iterate over an `Iterator` of `Event`s, generate HTML for each `Event`, and
 /// write it out to a writable stream.
 ///
@@ -666,8 +1924,9 @@ where
 /// # Examples
 ///
 /// ```
-/// use pulldown_cmark::{html, Parser};
-/// use std::io::Cursor;
+/// use pulldown_cmark::Parser;
+/// use ts_highlight_html::{theme, SyntaxConfig};
+/// use verin::html;
 ///
 /// let markdown_str = r#"
 /// hello
@@ -678,24 +1937,61 @@ where
 /// "#;
 /// let mut bytes = Vec::new();
 /// let parser = Parser::new(markdown_str);
+/// let syntax = SyntaxConfig::new(&theme::TOKYO_NIGHT);
 ///
-/// html::write_html(Cursor::new(&mut bytes), parser);
+/// let rendered = html::write_html(&mut bytes, parser, &syntax, Default::default()).unwrap();
 ///
-/// assert_eq!(&String::from_utf8_lossy(&bytes)[..], r#"<h1>hello</h1>
+/// assert_eq!(&String::from_utf8_lossy(&bytes)[..], r#"<h1 id="header-1" >hello</h1>
 /// <ul>
 /// <li>alpha</li>
 /// <li>beta</li>
 /// </ul>
 /// "#);
+/// assert_eq!(rendered.headers[0].text_plain, "hello");
 /// ```
 pub fn write_html<'a, I, W>(
     writer: W,
     iter: I,
     syntax: &'a SyntaxConfig,
-) -> Result<Vec<HeadingInfo>>
+    options: RenderOptions,
+) -> Result<Rendered>
 where
     I: Iterator<Item = Event<'a>>,
     W: Write,
 {
-    HtmlWriter::new(iter, writer, syntax).run()
+    write_html_in_site(writer, iter, syntax, options, Site::default())
+}
+
+/// Same as [`write_html`], for a document of the `site`: its local images get their size and
+/// variants, and the links to other websites get the attributes of its external links
+pub fn write_html_in_site<'a, I, W>(
+    writer: W,
+    iter: I,
+    syntax: &'a SyntaxConfig,
+    options: RenderOptions,
+    site: Site<'a>,
+) -> Result<Rendered>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: Write,
+{
+    // Footnote references need the text of definitions that may come later in the document
+    // Text is merged so that the equation references are not split
+    let events = details(pictures(TextMergeStream::new(iter).collect(), &site.images));
+    let (events, cross_references) = cross_references(events);
+    let events = footnotes_section(events);
+    let footnotes = footnote_texts(&events);
+    let equations = equations(&events);
+
+    let mut html = HtmlWriter::new(
+        events.into_iter(),
+        writer,
+        syntax,
+        options,
+        footnotes,
+        equations,
+        cross_references,
+    );
+    html.site = site;
+    html.run()
 }
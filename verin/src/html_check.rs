@@ -0,0 +1,187 @@
+//! Invariants of the HTML writer, checked by the property tests and the fuzz target.
+//!
+//! The writer is a fork of the one in `pulldown-cmark`, so these checks make sure that changes to
+//! it keep producing sound HTML.
+
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag};
+use ts_highlight_html::SyntaxConfig;
+
+use crate::html::{self, RenderOptions, Rendered};
+
+/// Elements that are never closed
+const VOID_ELEMENTS: &[&str] = &["br", "hr", "img", "input", "source"];
+
+/// Render `markdown` and panic if the output breaks one of the invariants of the writer:
+///
+/// - the output is valid UTF-8
+/// - the elements are well nested
+/// - `<`, `>` and `&` only appear escaped in text and attribute values
+/// - the headings collected while rendering are the ones present in the output, and their HTML
+///   and the table of contents follow the same rules
+///
+/// Raw HTML and raw blocks are written as is by the writer, so raw HTML is removed from the
+/// document and raw blocks are rendered as code blocks.
+pub fn check_markdown(markdown: &str, syntax: &SyntaxConfig, options: RenderOptions) {
+    let events = Parser::new_ext(markdown, Options::all())
+        .filter(|event| !matches!(event, Event::Html(_) | Event::InlineHtml(_)))
+        .map(|event| match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))
+                if info.trim() == html::RAW_HTML =>
+            {
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced("".into())))
+            }
+            event => event,
+        });
+
+    let mut output = Vec::new();
+    let rendered =
+        html::write_html(&mut output, events, syntax, options).expect("could not render");
+    let output = String::from_utf8(output).expect("output is not UTF-8");
+
+    let heading_ids = check_html(&output);
+    check_headings(&rendered, &heading_ids);
+}
+
+/// Check the nesting and the escaping of `html`, returning the `id`s of the headings
+fn check_html(html: &str) -> Vec<String> {
+    let mut stack = Vec::new();
+    let mut heading_ids = Vec::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        check_text(&rest[..start], html);
+        rest = &rest[start + 1..];
+
+        if let Some(closing) = rest.strip_prefix('/') {
+            let end = closing.find('>').expect("unterminated closing tag");
+            let name = &closing[..end];
+            match stack.pop() {
+                Some(open) if open == name => (),
+                open => panic!("`</{name}>` closes {open:?} in:\n{html}"),
+            }
+            rest = &closing[end + 1..];
+            continue;
+        }
+
+        let name_len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .expect("unterminated tag");
+        let name = &rest[..name_len];
+        assert!(!name.is_empty(), "empty tag name in:\n{html}");
+
+        let (attributes, self_closing, after) = split_tag(&rest[name_len..], html);
+        if name.len() == 2 && name.starts_with('h') && name.as_bytes()[1].is_ascii_digit() {
+            let id = attributes
+                .iter()
+                .find(|(attr, _)| *attr == "id")
+                .map(|(_, value)| value.to_string())
+                .unwrap_or_else(|| panic!("heading without an id in:\n{html}"));
+            heading_ids.push(id);
+        }
+        rest = after;
+
+        if name == "math" {
+            // MathML is generated by asciimath, only check that the element is closed
+            let end = rest.find("</math>").expect("unclosed `<math>`");
+            rest = &rest[end + "</math>".len()..];
+        } else if !self_closing && !VOID_ELEMENTS.contains(&name) {
+            stack.push(name);
+        }
+    }
+    check_text(rest, html);
+
+    assert!(stack.is_empty(), "unclosed elements {stack:?} in:\n{html}");
+
+    heading_ids
+}
+
+/// Split the attributes of a tag from the rest of the document, returning the attributes, whether
+/// the tag is self closing, and the document after the tag
+fn split_tag<'h>(tag: &'h str, html: &str) -> (Vec<(&'h str, &'h str)>, bool, &'h str) {
+    let mut attributes = Vec::new();
+    let mut rest = tag;
+
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix("/>") {
+            return (attributes, true, after);
+        }
+        if let Some(after) = rest.strip_prefix('>') {
+            return (attributes, false, after);
+        }
+
+        let name_end = rest
+            .find(['=', ' ', '>'])
+            .unwrap_or_else(|| panic!("unterminated tag in:\n{html}"));
+        let name = &rest[..name_end];
+        assert!(!name.is_empty(), "malformed tag in:\n{html}");
+        rest = &rest[name_end..];
+
+        let value = match rest.strip_prefix("=\"") {
+            Some(quoted) => {
+                let end = quoted
+                    .find('"')
+                    .unwrap_or_else(|| panic!("unterminated attribute in:\n{html}"));
+                rest = &quoted[end + 1..];
+                &quoted[..end]
+            }
+            None => "",
+        };
+        check_text(value, html);
+        attributes.push((name, value));
+    }
+}
+
+/// Check that `text` does not contain unescaped characters
+fn check_text(text: &str, html: &str) {
+    assert!(!text.contains(['<', '>']), "unescaped `{text}` in:\n{html}");
+
+    for (idx, _) in text.match_indices('&') {
+        let entity = &text[idx + 1..];
+        let end = entity
+            .find(';')
+            .unwrap_or_else(|| panic!("unescaped `&` in `{text}` in:\n{html}"));
+        let entity = &entity[..end];
+        let valid = match entity.strip_prefix('#') {
+            Some(code) => match code.strip_prefix(['x', 'X']) {
+                Some(hex) => !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()),
+                None => !code.is_empty() && code.chars().all(|c| c.is_ascii_digit()),
+            },
+            None => !entity.is_empty() && entity.chars().all(|c| c.is_ascii_alphanumeric()),
+        };
+        assert!(valid, "invalid entity `&{entity};` in:\n{html}");
+    }
+}
+
+fn check_headings(rendered: &Rendered, heading_ids: &[String]) {
+    let collected = rendered
+        .headers
+        .iter()
+        .map(|heading| heading.id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        collected, heading_ids,
+        "collected headings differ from the output"
+    );
+
+    for heading in &rendered.headers {
+        // Custom ids (`# Title {#id}`) replace the numbered ones
+        assert!(
+            !heading.id.is_empty(),
+            "heading {} has no id",
+            heading.number
+        );
+        // The HTML of the headings is also used in table of contents
+        check_html(&heading.text_html);
+        assert_eq!(
+            heading.level as usize,
+            heading.number.split('.').count(),
+            "level of `{}` does not match its number",
+            heading.id
+        );
+    }
+
+    for max_depth in 1..=7 {
+        check_html(&rendered.toc(max_depth));
+    }
+}
@@ -0,0 +1,184 @@
+//! HTTP client shared by the features using the network (the check of the external links, ...).
+//!
+//! The requests to a same host are spaced out, the transient failures are retried with a backoff,
+//! and the responses are cached for the whole run so that a page linked many times is only fetched
+//! once. The network can be disabled with `--offline`, the requests failing instead.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use verin::html;
+
+/// Time allowed for each request
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Number of times a request is retried after a transient failure
+const RETRIES: u32 = 2;
+/// Delay before the first retry, doubled for each of the next ones
+const BACKOFF: Duration = Duration::from_secs(1);
+/// Longest delay waited for a server asking to retry later (`Retry-After`)
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+/// Minimum delay between two requests to the same host
+const HOST_DELAY: Duration = Duration::from_millis(250);
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Disable the network for the rest of the run
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+pub fn offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Method {
+    Head,
+    Get,
+}
+
+#[derive(Debug, Clone)]
+pub enum FetchError {
+    /// The network is disabled by `--offline`
+    Offline,
+    /// The server answered with an error status
+    Status(u16, String),
+    /// The server could not be reached, or the response could not be read
+    Transport(String),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Offline => write!(f, "the network is disabled by --offline"),
+            FetchError::Status(code, text) => write!(f, "HTTP {code} {text}"),
+            FetchError::Transport(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+impl FetchError {
+    /// Whether the request may succeed if retried
+    fn transient(&self) -> bool {
+        match self {
+            FetchError::Offline => false,
+            FetchError::Status(code, _) => matches!(code, 429 | 502 | 503 | 504),
+            FetchError::Transport(_) => true,
+        }
+    }
+}
+
+/// Status of the successful response to a request
+type Fetched = Result<u16, FetchError>;
+
+pub struct Client {
+    agent: ureq::Agent,
+    /// Earliest time of the next request to each host
+    hosts: Mutex<HashMap<String, Instant>>,
+    /// Result of the requests already made
+    cache: Mutex<HashMap<(Method, String), Fetched>>,
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .timeout(TIMEOUT)
+                .user_agent(concat!("verin/", env!("CARGO_PKG_VERSION")))
+                .build(),
+            hosts: Mutex::new(HashMap::new()),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn head(&self, url: &str) -> Fetched {
+        self.request(Method::Head, url)
+    }
+
+    pub fn get(&self, url: &str) -> Fetched {
+        self.request(Method::Get, url)
+    }
+
+    /// Send a request, or reuse the result of the same request made earlier in the run
+    pub fn request(&self, method: Method, url: &str) -> Fetched {
+        if offline() {
+            return Err(FetchError::Offline);
+        }
+
+        let key = (method, url.to_owned());
+        if let Some(fetched) = self.lock_cache().get(&key) {
+            return fetched.clone();
+        }
+
+        let mut retries = 0;
+        let fetched = loop {
+            self.wait_for(html::host(url).unwrap_or(url));
+            let (fetched, retry_after) = self.send(method, url);
+            match fetched {
+                Err(e) if e.transient() && retries < RETRIES => {
+                    let backoff = BACKOFF * 2u32.pow(retries);
+                    std::thread::sleep(retry_after.unwrap_or(backoff).min(MAX_RETRY_AFTER));
+                    retries += 1;
+                }
+                fetched => break fetched,
+            }
+        };
+
+        self.lock_cache().insert(key, fetched.clone());
+        fetched
+    }
+
+    fn lock_cache(&self) -> MutexGuard<'_, HashMap<(Method, String), Fetched>> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Wait until a request can be sent to `host`, reserving the next slot for this request
+    fn wait_for(&self, host: &str) {
+        let now = Instant::now();
+        let slot = {
+            let mut hosts = self.hosts.lock().unwrap_or_else(PoisonError::into_inner);
+            let next = hosts.entry(host.to_owned()).or_insert(now);
+            let slot = (*next).max(now);
+            *next = slot + HOST_DELAY;
+            slot
+        };
+        std::thread::sleep(slot.saturating_duration_since(now));
+    }
+
+    /// Send a single request, returning the delay asked by the server before retrying it
+    fn send(&self, method: Method, url: &str) -> (Fetched, Option<Duration>) {
+        let request = match method {
+            Method::Head => self.agent.head(url),
+            Method::Get => self.agent.get(url),
+        };
+
+        match request.call() {
+            // The body is not needed to check that the URL can be fetched
+            Ok(response) => (Ok(response.status()), None),
+            Err(ureq::Error::Status(code, response)) => {
+                let retry_after = response
+                    .header("Retry-After")
+                    .and_then(|seconds| seconds.trim().parse().ok())
+                    .map(Duration::from_secs);
+                let error = FetchError::Status(code, response.status_text().to_owned());
+                (Err(error), retry_after)
+            }
+            Err(ureq::Error::Transport(transport)) => {
+                let message = match transport.message() {
+                    Some(message) => format!("{}: {message}", transport.kind()),
+                    None => transport.kind().to_string(),
+                };
+                (Err(FetchError::Transport(message)), None)
+            }
+        }
+    }
+}
@@ -0,0 +1,332 @@
+//! Images of the articles that are files of the input directory.
+//!
+//! Their dimensions are given to their `<img>` so that browsers reserve their space before loading
+//! them lazily, instead of shifting the layout of the page. They can also be resized and converted
+//! to modern formats, offered by a `<picture>`.
+//!
+//! Images outside of the input directory, referenced by their path (`~/Pictures/shot.png`), can be
+//! imported in the output if they are in one of the allowed directories.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock, PoisonError},
+};
+
+use color_eyre::{eyre::Context, Result};
+use pulldown_cmark::{Event, Tag};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use verin::html::{ImageSource, LocalImage, LocalImages};
+
+use crate::{assets, links, secrets::println_redacted, url::SiteUrl};
+
+/// Directory of the variants, in the output and cache directories
+const VARIANTS: &str = "images";
+/// Number of characters of the hash of the source in the name of the variants
+const HASH_LEN: usize = 16;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VariantFormat {
+    Webp,
+    Avif,
+}
+
+impl VariantFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            VariantFormat::Webp => "webp",
+            VariantFormat::Avif => "avif",
+        }
+    }
+
+    fn mime(self) -> &'static str {
+        match self {
+            VariantFormat::Webp => "image/webp",
+            VariantFormat::Avif => "image/avif",
+        }
+    }
+
+    fn encoding(self) -> image::ImageFormat {
+        match self {
+            VariantFormat::Webp => image::ImageFormat::WebP,
+            VariantFormat::Avif => image::ImageFormat::Avif,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ImagesConfig {
+    /// Add the dimensions of the local images to their `<img>`, and load them lazily
+    #[serde(default = "default_dimensions")]
+    pub dimensions: bool,
+    /// Widths in pixels of the variants of the local images, the larger ones are skipped
+    #[serde(default)]
+    pub widths: Vec<u32>,
+    /// Formats of the variants, preferred in this order (they are only generated if not empty)
+    #[serde(default)]
+    pub formats: Vec<VariantFormat>,
+    /// Directories from which the images referenced by their path are imported, relative to the
+    /// input directory or to the home directory (`~/Pictures`)
+    #[serde(default)]
+    pub import: Vec<PathBuf>,
+}
+
+fn default_dimensions() -> bool {
+    true
+}
+
+impl Default for ImagesConfig {
+    fn default() -> Self {
+        Self {
+            dimensions: default_dimensions(),
+            widths: Vec::new(),
+            formats: Vec::new(),
+            import: Vec::new(),
+        }
+    }
+}
+
+/// Generates the variants of the local images, reusing the ones of the previous builds
+pub struct Pipeline<'a> {
+    config: &'a ImagesConfig,
+    /// Directory holding the variants generated by all the builds
+    cache: PathBuf,
+    output: PathBuf,
+    /// Sources of each image, computed once per build for the images used by several articles
+    processed: Mutex<HashMap<PathBuf, Arc<OnceLock<Vec<ImageSource>>>>>,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn new(config: &'a ImagesConfig, cache: &Path, output: &Path) -> Self {
+        Self {
+            config,
+            cache: cache.join(VARIANTS),
+            output: output.to_owned(),
+            processed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sources offering the variants of the image at `path`, that is `width` pixels wide
+    fn sources(&self, path: &Path, width: u32) -> Vec<ImageSource> {
+        let vector = path.extension().is_some_and(|extension| extension == "svg");
+        if self.config.formats.is_empty() || vector {
+            return Vec::new();
+        }
+
+        let processed = self
+            .processed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(path.to_owned())
+            .or_default()
+            .clone();
+
+        processed
+            .get_or_init(|| {
+                self.process(path, width).unwrap_or_else(|e| {
+                    println_redacted!(
+                        "[WARNING] Could not generate the variants of {}: {e:#}",
+                        path.display()
+                    );
+                    Vec::new()
+                })
+            })
+            .clone()
+    }
+
+    fn process(&self, path: &Path, width: u32) -> Result<Vec<ImageSource>> {
+        let content = std::fs::read(path).context("Could not read the image")?;
+        let hash = format!("{:x}", Sha256::digest(&content));
+        let hash = &hash[..HASH_LEN];
+
+        let mut widths: Vec<_> = self
+            .config
+            .widths
+            .iter()
+            .copied()
+            .filter(|&variant| variant < width)
+            .chain([width])
+            .collect();
+        widths.sort_unstable();
+        widths.dedup();
+
+        // The image is only decoded if a variant is not in the cache
+        let mut image = None;
+        std::fs::create_dir_all(&self.cache).context("Could not create the cache directory")?;
+        std::fs::create_dir_all(self.output.join(VARIANTS))
+            .context("Could not create the images directory")?;
+
+        let mut sources = Vec::new();
+        for &format in &self.config.formats {
+            let mut srcset = Vec::new();
+            for &variant in &widths {
+                let name = format!("{hash}-{variant}.{}", format.extension());
+                let cached = self.cache.join(&name);
+                if !cached.exists() {
+                    if image.is_none() {
+                        image = Some(image::load_from_memory(&content).context("Invalid image")?);
+                    }
+                    let image = image.as_ref().expect("image was decoded");
+
+                    // Written to a temporary file, so that an interrupted build doesn't leave a
+                    // partial variant in the cache
+                    let partial = self.cache.join(format!("{name}.partial"));
+                    image
+                        .resize(variant, u32::MAX, image::imageops::FilterType::Lanczos3)
+                        .save_with_format(&partial, format.encoding())
+                        .with_context(|| format!("Could not encode {name}"))?;
+                    std::fs::rename(&partial, &cached)
+                        .with_context(|| format!("Could not cache {name}"))?;
+                }
+
+                let relative = Path::new(VARIANTS).join(&name);
+                std::fs::copy(&cached, self.output.join(&relative))
+                    .with_context(|| format!("Could not copy {name}"))?;
+                srcset.push(format!("{} {variant}w", SiteUrl::from_path(&relative)));
+            }
+
+            sources.push(ImageSource {
+                mime: format.mime().to_owned(),
+                srcset: srcset.join(", "),
+            });
+        }
+
+        Ok(sources)
+    }
+}
+
+/// Expand the `~` at the start of `path` to the home directory
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => path.to_owned(),
+    }
+}
+
+/// Copies the images referenced by their path on the filesystem to the output, with a fingerprint
+/// in their name
+pub struct Imports {
+    /// Allowed directories, canonicalized
+    roots: Vec<PathBuf>,
+    output: PathBuf,
+    /// URL of each imported image, the images used by several articles being copied once
+    imported: Mutex<HashMap<PathBuf, String>>,
+}
+
+impl Imports {
+    pub fn new(config: &ImagesConfig, input: &Path, output: &Path) -> Self {
+        let roots = config
+            .import
+            .iter()
+            .filter_map(|root| {
+                let root = input.join(expand_home(root));
+                match root.canonicalize() {
+                    Ok(root) => Some(root),
+                    Err(e) => {
+                        println_redacted!(
+                            "[WARNING] Could not import images from {}: {e}",
+                            root.display()
+                        );
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Self {
+            roots,
+            output: output.to_owned(),
+            imported: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Import the image at `url` if it is the path of a file in one of the allowed directories
+    /// (`~/Pictures/shot.png` or `/home/me/Pictures/shot.png`), returning its URL in the website.
+    ///
+    /// Returns `None` for the other URLs, that are URLs of the website.
+    pub fn import(&self, url: &str) -> Result<Option<String>> {
+        let home = url.starts_with("~/");
+        if self.roots.is_empty() || !(home || url.starts_with('/')) || url.starts_with("//") {
+            return Ok(None);
+        }
+
+        let path = expand_home(Path::new(url));
+        // Paths escaping the allowed directories with `..` or symbolic links are not imported
+        let Some(path) = path
+            .canonicalize()
+            .ok()
+            .filter(|path| self.roots.iter().any(|root| path.starts_with(root)))
+        else {
+            if home {
+                println_redacted!(
+                    "[WARNING] The image {url} is not in a directory of `images.import`, it is \
+                     not imported"
+                );
+            }
+            return Ok(None);
+        };
+
+        let mut imported = self.imported.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(url) = imported.get(&path) {
+            return Ok(Some(url.clone()));
+        }
+
+        let content = std::fs::read(&path)
+            .with_context(|| format!("Could not read the image {}", path.display()))?;
+        let hash = format!("{:x}", Sha256::digest(&content));
+        let name = Path::new(path.file_name().unwrap_or_default());
+        let relative = Path::new(VARIANTS).join(assets::fingerprinted(name, &hash));
+
+        std::fs::create_dir_all(self.output.join(VARIANTS))
+            .context("Could not create the images directory")?;
+        std::fs::write(self.output.join(&relative), &content)
+            .with_context(|| format!("Could not import the image {}", path.display()))?;
+
+        let url = SiteUrl::from_path(&relative).to_string();
+        imported.insert(path, url.clone());
+        Ok(Some(url))
+    }
+}
+
+/// Images of an article, resolved in the input directory
+pub struct ArticleImages<'a> {
+    pub input: &'a Path,
+    /// Path of the source of the article, relative to the input directory
+    pub source: &'a Path,
+    pub pipeline: &'a Pipeline<'a>,
+}
+
+impl ArticleImages<'_> {
+    /// Read the sizes of the images of `events` that are files of the input directory, and
+    /// generate their variants.
+    ///
+    /// Imported images are resolved in the output directory. Images that can't be read (remote
+    /// images, missing files or unknown formats) are skipped.
+    pub fn resolve(&self, events: &[Event]) -> LocalImages {
+        let article = self.input.join(self.source);
+
+        events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Start(Tag::Image { dest_url, .. }) => Some(dest_url),
+                _ => None,
+            })
+            .filter_map(|url| {
+                let path = links::resolve_local(self.input, &article, url)
+                    .filter(|path| path.exists())
+                    .or_else(|| links::resolve_local(&self.pipeline.output, &article, url))?;
+                let size = imagesize::size(&path).ok()?;
+                Some((
+                    url.to_string(),
+                    LocalImage {
+                        width: size.width,
+                        height: size.height,
+                        sources: self.pipeline.sources(&path, size.width as u32),
+                    },
+                ))
+            })
+            .collect()
+    }
+}
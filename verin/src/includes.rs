@@ -0,0 +1,148 @@
+//! Source files included in the fenced code blocks of the articles
+//! (```` ```rust file=examples/demo.rs lines=10-42 ````), so that the code samples stay in sync
+//! with the real sources.
+
+use std::path::Path;
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+use pulldown_cmark::{CodeBlockKind, Event, Tag, TagEnd};
+use verin::html::{self, CodeInfo};
+
+/// File and lines included by a code block, parsed from its info string
+struct Include<'i> {
+    /// Path of the file, relative to the article
+    file: &'i str,
+    /// First and last lines included, starting at 1
+    lines: Option<(usize, usize)>,
+}
+
+impl<'i> Include<'i> {
+    fn parse(info: &'i str) -> Result<Option<Self>> {
+        let info = CodeInfo::parse(info);
+        let lines = match info.file_lines {
+            Some(range) => match html::parse_range(range) {
+                Some(lines) if *lines.start() >= 1 && !lines.is_empty() => Some(lines.into_inner()),
+                _ => eyre::bail!("Invalid range of lines `{range}`"),
+            },
+            None => None,
+        };
+
+        Ok(info.file.map(|file| Include { file, lines }))
+    }
+
+    /// Content of the included lines of the file, read relative to `article`
+    fn read(&self, article: &Path) -> Result<String> {
+        let path = article.parent().unwrap_or(Path::new("")).join(self.file);
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Could not include {}", self.file))?;
+        self.select(content)
+    }
+
+    /// Included lines of the `content` of the file
+    fn select(&self, content: String) -> Result<String> {
+        let Some((start, end)) = self.lines else {
+            return Ok(content);
+        };
+        let count = content.lines().count();
+        if end > count {
+            eyre::bail!(
+                "Could not include the lines {start}-{end} of {}, it has {count} lines",
+                self.file
+            );
+        }
+
+        let mut included = String::new();
+        for line in content.lines().skip(start - 1).take(end + 1 - start) {
+            included.push_str(line);
+            included.push('\n');
+        }
+        Ok(included)
+    }
+}
+
+/// Replace the content of the code blocks of `events` including a file by the file, read
+/// relative to the `article`
+pub fn include<'a>(events: Vec<Event<'a>>, article: &Path) -> Result<Vec<Event<'a>>> {
+    let mut output = Vec::with_capacity(events.len());
+    // The content written in the code block including a file is replaced
+    let mut replaced = false;
+
+    for event in events {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                let include = Include::parse(&info)
+                    .with_context(|| format!("Invalid code block `{info}`"))?;
+                let content = include.map(|include| include.read(article)).transpose()?;
+
+                output.push(Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))));
+                if let Some(content) = content {
+                    output.push(Event::Text(content.into()));
+                    replaced = true;
+                }
+            }
+            Event::Text(_) if replaced => (),
+            Event::End(TagEnd::CodeBlock) => {
+                replaced = false;
+                output.push(event);
+            }
+            event => output.push(event),
+        }
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "one\ntwo\nthree\nfour\n";
+
+    fn select(info: &str) -> Result<String> {
+        Include::parse(info)?
+            .expect("the code block includes a file")
+            .select(CONTENT.to_owned())
+    }
+
+    #[test]
+    fn file() {
+        assert!(Include::parse("rust").unwrap().is_none());
+        assert!(Include::parse("rust lines=2").unwrap().is_none());
+
+        let include = Include::parse("rust,file=src/demo.rs").unwrap().unwrap();
+        assert_eq!(include.file, "src/demo.rs");
+        assert_eq!(include.lines, None);
+        assert_eq!(select("file=demo.rs").unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn lines() {
+        assert_eq!(
+            select("rust file=demo.rs lines=2-3").unwrap(),
+            "two\nthree\n"
+        );
+        assert_eq!(select("rust file=demo.rs lines=4").unwrap(), "four\n");
+        assert_eq!(select("rust lines=1-4 file=demo.rs").unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        for range in ["0-2", "3-2", "two", "1-", ""] {
+            let info = format!("rust file=demo.rs lines={range}");
+            let error = Include::parse(&info).err().expect(&info);
+            assert_eq!(
+                error.to_string(),
+                format!("Invalid range of lines `{range}`")
+            );
+        }
+
+        let error = select("rust file=demo.rs lines=3-5").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Could not include the lines 3-5 of demo.rs, it has 4 lines"
+        );
+    }
+}
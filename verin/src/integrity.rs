@@ -0,0 +1,146 @@
+//! Integrity of the articles, for the readers mirroring them.
+//!
+//! The SHA-256 of the content of each article is given to its template to be embedded in a
+//! `<meta>`, and the pages of the articles can be signed with a SSH or minisign key, their detached
+//! signature being written next to them (`intro.html.sig`).
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::images::expand_home;
+
+#[derive(Deserialize, Debug)]
+pub struct IntegrityConfig {
+    /// Key signing the pages of the articles
+    #[serde(default)]
+    pub signing: Option<Signing>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "tool", rename_all = "lowercase")]
+pub enum Signing {
+    /// Signed by `ssh-keygen -Y sign` in the `file` namespace
+    Ssh { key: PathBuf },
+    /// Signed by `minisign -S`
+    Minisign { key: PathBuf },
+}
+
+/// Path of the detached signature of the page at `path`
+fn signature_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.file_name().unwrap_or_default());
+    name.push(".sig");
+    path.with_file_name(name)
+}
+
+/// `<meta>` tag holding the hash of the HTML `content` of an article, to be inserted in the
+/// `<head>` of its page
+pub fn meta(content: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(content));
+    format!(r#"<meta name="content-sha256" content="{hash}">"#)
+}
+
+/// Remove the file at `path` if it exists
+fn remove_previous(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Could not remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl Signing {
+    /// Sign the pages at `paths`, writing their detached signature next to them.
+    ///
+    /// All the pages are signed by a single run of the tool, so that the passphrase of the key is
+    /// only asked once.
+    pub fn sign(&self, paths: &[PathBuf]) -> Result<()> {
+        if paths.is_empty() {
+            return Ok(());
+        }
+
+        // `ssh-keygen` refuses to replace the signature of the previous build
+        for path in paths {
+            remove_previous(&signature_path(path))?;
+        }
+
+        let (tool, mut command) = match self {
+            Signing::Ssh { key } => {
+                let mut command = Command::new("ssh-keygen");
+                command
+                    .args(["-Y", "sign", "-n", "file", "-f"])
+                    .arg(expand_home(key))
+                    .args(paths);
+                ("ssh-keygen", command)
+            }
+            Signing::Minisign { key } => {
+                let mut command = Command::new("minisign");
+                command
+                    .arg("-S")
+                    .arg("-s")
+                    .arg(expand_home(key))
+                    .arg("-m")
+                    .args(paths);
+                ("minisign", command)
+            }
+        };
+
+        // The tools may ask for the passphrase of the key
+        let status = command
+            .status()
+            .with_context(|| format!("Could not run `{tool}`"))?;
+        if !status.success() {
+            eyre::bail!("`{tool}` could not sign the pages");
+        }
+
+        // `minisign` can only choose the name of the signature of a single file
+        if let Signing::Minisign { .. } = self {
+            for path in paths {
+                let mut minisig = path.as_os_str().to_owned();
+                minisig.push(".minisig");
+                std::fs::rename(&minisig, signature_path(path)).with_context(|| {
+                    format!("Could not move the signature of {}", path.display())
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_next_to_the_page() {
+        assert_eq!(
+            signature_path(Path::new("out/posts/intro.html")),
+            Path::new("out/posts/intro.html.sig")
+        );
+        assert_eq!(
+            signature_path(Path::new("out/posts/intro/index.html")),
+            Path::new("out/posts/intro/index.html.sig")
+        );
+    }
+
+    #[test]
+    fn meta_tag() {
+        assert_eq!(
+            meta("<p>Hello</p>"),
+            format!(
+                r#"<meta name="content-sha256" content="{:x}">"#,
+                Sha256::digest("<p>Hello</p>")
+            )
+        );
+    }
+}
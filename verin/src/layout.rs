@@ -0,0 +1,198 @@
+//! Layout inheritance of the templates.
+//!
+//! A template starting with `{% layout "base" %}` is rendered with `layouts/base.liquid`, in which
+//! each `{% block name %}...{% endblock %}` is replaced by the block of the same name of the
+//! template, if it defines one. Layouts can themselves use a layout, and the templates are
+//! resolved before being given to liquid.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+use glob::glob;
+
+use crate::{shortcodes, templates::EngineKind};
+
+/// Directory of the layouts, relative to the input directory
+pub const LAYOUTS: &str = "layouts";
+
+/// Templates of the pages for the template engine `kind`, excluding the layouts and the shortcodes
+pub fn page_templates(input: &Path, kind: EngineKind) -> Result<Vec<PathBuf>> {
+    let layouts = input.join(LAYOUTS);
+    let shortcodes = input.join(shortcodes::SHORTCODES);
+    let pattern = input.join("**").join(format!("*.{}", kind.extension()));
+
+    let mut templates = Vec::new();
+    for entry in glob(&pattern.to_string_lossy())? {
+        let entry = entry?;
+        if !entry.starts_with(&layouts) && !entry.starts_with(&shortcodes) {
+            templates.push(entry);
+        }
+    }
+
+    Ok(templates)
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Block { name: String, body: Vec<Node> },
+}
+
+#[derive(Debug)]
+struct Parsed {
+    layout: Option<String>,
+    nodes: Vec<Node>,
+}
+
+fn parse(source: &str) -> Result<Parsed> {
+    let mut layout = None;
+    let mut names = HashSet::new();
+    // Blocks being parsed, the first one being the whole template
+    let mut stack = vec![(String::new(), Vec::new())];
+
+    let mut rest = source;
+    while let Some(start) = rest.find("{%") {
+        let Some(length) = rest[start..].find("%}") else {
+            break;
+        };
+        let end = start + length + 2;
+        let tag = rest[start + 2..end - 2]
+            .trim_start_matches('-')
+            .trim_end_matches('-')
+            .trim();
+        let (keyword, argument) = tag
+            .split_once(char::is_whitespace)
+            .map_or((tag, ""), |(keyword, argument)| (keyword, argument.trim()));
+
+        let depth = stack.len();
+        let nodes = &mut stack.last_mut().expect("the template is never popped").1;
+        match keyword {
+            "layout" => {
+                if depth > 1 {
+                    eyre::bail!("`{{% layout %}}` can't be used inside of a block");
+                }
+                if layout.is_some() {
+                    eyre::bail!("Only one `{{% layout %}}` can be used in a template");
+                }
+
+                let name = argument
+                    .strip_prefix('"')
+                    .and_then(|name| name.strip_suffix('"'))
+                    .or_else(|| {
+                        argument
+                            .strip_prefix('\'')
+                            .and_then(|name| name.strip_suffix('\''))
+                    })
+                    .ok_or_else(|| {
+                        eyre::eyre!("Expected a quoted layout name, found `{argument}`")
+                    })?;
+                layout = Some(name.to_owned());
+                nodes.push(Node::Text(rest[..start].to_owned()));
+            }
+            "block" => {
+                if argument.is_empty() || argument.contains(char::is_whitespace) {
+                    eyre::bail!("Expected a block name, found `{argument}`");
+                }
+                if !names.insert(argument) {
+                    eyre::bail!("The block `{argument}` is defined twice");
+                }
+
+                nodes.push(Node::Text(rest[..start].to_owned()));
+                stack.push((argument.to_owned(), Vec::new()));
+            }
+            "endblock" => {
+                nodes.push(Node::Text(rest[..start].to_owned()));
+                if depth == 1 {
+                    eyre::bail!("`{{% endblock %}}` without a matching `{{% block %}}`");
+                }
+
+                let (name, body) = stack.pop().expect("checked the length of the stack");
+                if !argument.is_empty() && argument != name {
+                    eyre::bail!("The block `{name}` is closed by `{{% endblock {argument} %}}`");
+                }
+                stack
+                    .last_mut()
+                    .expect("the template is never popped")
+                    .1
+                    .push(Node::Block { name, body });
+            }
+            // Other tags are kept for liquid
+            _ => nodes.push(Node::Text(rest[..end].to_owned())),
+        }
+
+        rest = &rest[end..];
+    }
+
+    if stack.len() > 1 {
+        eyre::bail!(
+            "The block `{}` is not closed by `{{% endblock %}}`",
+            stack.last().expect("checked the length of the stack").0
+        );
+    }
+
+    let (_, mut nodes) = stack.pop().expect("the template is never popped");
+    nodes.push(Node::Text(rest.to_owned()));
+    Ok(Parsed { layout, nodes })
+}
+
+/// Record the blocks defined in `nodes`, keeping the ones already defined by a child template
+fn collect_blocks(nodes: Vec<Node>, blocks: &mut HashMap<String, Vec<Node>>) {
+    for node in nodes {
+        if let Node::Block { name, body } = node {
+            collect_blocks(body.clone(), blocks);
+            blocks.entry(name).or_insert(body);
+        }
+    }
+}
+
+fn render(nodes: &[Node], blocks: &HashMap<String, Vec<Node>>, output: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Block { name, body } => render(blocks.get(name).unwrap_or(body), blocks, output),
+        }
+    }
+}
+
+/// Read the template at `path`, resolving its layouts from the `input` directory
+pub fn load(input: &Path, path: &Path) -> Result<String> {
+    let read = |path: &Path| -> Result<Parsed> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read the template {}", path.display()))?;
+        parse(&source).with_context(|| format!("Invalid template {}", path.display()))
+    };
+
+    let mut template = read(path)?;
+    let mut chain = vec![path.to_owned()];
+    let mut blocks = HashMap::new();
+    while let Some(layout) = template.layout.take() {
+        collect_blocks(template.nodes, &mut blocks);
+
+        let layout = input.join(LAYOUTS).join(format!("{layout}.liquid"));
+        if chain.contains(&layout) {
+            eyre::bail!(
+                "Cycle in the layouts of {}: {}",
+                path.display(),
+                chain
+                    .iter()
+                    .chain(std::iter::once(&layout))
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            );
+        }
+
+        template = read(&layout)?;
+        chain.push(layout);
+    }
+
+    let mut output = String::new();
+    render(&template.nodes, &blocks, &mut output);
+    Ok(output)
+}
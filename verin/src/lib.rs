@@ -0,0 +1,4 @@
+//! Library part of Verin, exposing the HTML writer to the tests and fuzz targets
+
+pub mod html;
+pub mod html_check;
@@ -0,0 +1,371 @@
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
+    ffi::OsStr,
+    path::{Component, Path, PathBuf},
+};
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+use glob::glob;
+use rayon::prelude::*;
+
+use crate::{
+    http::{self, FetchError},
+    url::SiteUrl,
+};
+
+/// Convert a relative path in the output directory to the path component of an URL
+pub fn url_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Find the values of all the occurrences of `attribute` in a page
+fn attribute_values<'a>(html: &'a str, attribute: &str) -> Vec<&'a str> {
+    let pattern = format!("{attribute}=\"");
+
+    html.match_indices(&pattern)
+        .filter(|&(idx, _)| html[..idx].ends_with(|c: char| c.is_ascii_whitespace()))
+        .filter_map(|(idx, _)| {
+            let start = idx + pattern.len();
+            let len = html[start..].find('"')?;
+            Some(&html[start..start + len])
+        })
+        .collect()
+}
+
+/// Find all the URLs referenced by `src`, `srcset` or `href` attributes in a page
+pub fn references(html: &str) -> Vec<&str> {
+    let mut references = attribute_values(html, "src");
+    references.extend(attribute_values(html, "href"));
+    // Each candidate of a srcset is an URL, optionally followed by a descriptor (`a.png 2x`)
+    references.extend(
+        attribute_values(html, "srcset")
+            .into_iter()
+            .flat_map(|srcset| srcset.split(','))
+            .filter_map(|candidate| candidate.split_whitespace().next()),
+    );
+    references
+}
+
+/// Find all the ids defined in a page, that can be used as fragments
+pub fn anchors(html: &str) -> HashSet<String> {
+    attribute_values(html, "id")
+        .into_iter()
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+/// Resolve an URL found in `page` to a path inside the output directory.
+///
+/// Returns `None` if the URL points outside of the site (it has a scheme, is protocol relative or
+/// is only a fragment).
+pub fn resolve_local(output: &Path, page: &Path, url: &str) -> Option<PathBuf> {
+    if url.is_empty() || url.starts_with('#') || url.starts_with("//") || url.contains(':') {
+        return None;
+    }
+
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+
+    match url.strip_prefix('/') {
+        Some(absolute) => Some(output.join(SiteUrl::parse(absolute).path())),
+        None => Some(page.parent()?.join(url)),
+    }
+}
+
+/// Rewrite the links to markdown sources in an article to the pages generated from them
+pub struct SourceLinks<'a> {
+    /// Path of the source of the article, relative to the input directory
+    pub source: &'a Path,
+    /// Paths of the pages of all the articles, relative to the output directory, by the path of
+    /// their source
+    pub pages: &'a HashMap<PathBuf, PathBuf>,
+}
+
+impl SourceLinks<'_> {
+    /// Rewrite `url` if it points to a markdown file (`other-post.md#intro` is rewritten to
+    /// `/other-post.html#intro`), or if it is absolute (it is prefixed with the base path).
+    ///
+    /// Returns `None` if the URL does not need to be rewritten, and fails if the markdown file is
+    /// not an article.
+    pub fn rewrite(&self, url: &str) -> Result<Option<String>> {
+        if url.starts_with("//") || url.contains(':') {
+            return Ok(None);
+        }
+
+        let end = url.find(['?', '#']).unwrap_or(url.len());
+        let (path, suffix) = url.split_at(end);
+        if !path.ends_with(".md") {
+            let absolute = path.starts_with('/');
+            return Ok(absolute.then(|| format!("{}{suffix}", SiteUrl::parse(path))));
+        }
+
+        let target = match path.strip_prefix('/') {
+            Some(absolute) => PathBuf::from(absolute),
+            None => self.source.parent().unwrap_or(Path::new("")).join(path),
+        };
+
+        let Some(page) = self.pages.get(&normalize(&target)) else {
+            eyre::bail!(
+                "Link to `{url}` in {} does not point to an article",
+                self.source.display()
+            );
+        };
+
+        Ok(Some(format!("{}{suffix}", SiteUrl::from_page(page))))
+    }
+}
+
+/// Remove the `.` and `..` components of a relative path
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[derive(Debug)]
+pub struct BrokenLink {
+    /// Page containing the link, relative to the output directory
+    pub page: PathBuf,
+    pub url: String,
+    pub reason: String,
+}
+
+/// Check that all the local links in `pages` point to existing files, and that their fragments
+/// point to existing ids in the target page
+pub fn check_internal(output: &Path, pages: &[PathBuf]) -> Result<Vec<BrokenLink>> {
+    let mut anchor_cache: HashMap<PathBuf, HashSet<String>> = HashMap::new();
+    let mut broken = Vec::new();
+
+    for page in pages {
+        let content = std::fs::read_to_string(page)
+            .with_context(|| format!("Could not read page {}", page.display()))?;
+
+        for url in references(&content) {
+            let fragment = url
+                .split_once('#')
+                .map(|(_, fragment)| fragment)
+                .filter(|fragment| !fragment.is_empty());
+
+            let target = if url.starts_with('#') {
+                page.clone()
+            } else {
+                let Some(target) = resolve_local(output, page, url) else {
+                    continue;
+                };
+
+                if target.is_dir() {
+                    target.join("index.html")
+                } else {
+                    target
+                }
+            };
+
+            let mut report = |reason: &str| {
+                broken.push(BrokenLink {
+                    page: page.strip_prefix(output).unwrap_or(page).to_owned(),
+                    url: url.to_owned(),
+                    reason: reason.to_owned(),
+                })
+            };
+
+            if !target.is_file() {
+                report("target does not exist");
+                continue;
+            }
+
+            let Some(fragment) = fragment else {
+                continue;
+            };
+
+            if target.extension() != Some(OsStr::new("html")) {
+                continue;
+            }
+
+            let target_anchors = match anchor_cache.entry(target) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let target_content =
+                        std::fs::read_to_string(entry.key()).with_context(|| {
+                            format!("Could not read page {}", entry.key().display())
+                        })?;
+                    entry.insert(anchors(&target_content))
+                }
+            };
+
+            if !target_anchors.contains(fragment) {
+                report("fragment does not exist in target");
+            }
+        }
+    }
+
+    Ok(broken)
+}
+
+/// Check that the external (`http` and `https`) links in `pages` can be fetched, by sending a
+/// `HEAD` request to each of them.
+///
+/// Some servers do not implement `HEAD`, in that case the link is fetched with `GET` instead.
+pub fn check_external(output: &Path, pages: &[PathBuf]) -> Result<Vec<BrokenLink>> {
+    let mut urls: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for page in pages {
+        let content = std::fs::read_to_string(page)
+            .with_context(|| format!("Could not read page {}", page.display()))?;
+
+        for url in references(&content) {
+            if url.starts_with("http://") || url.starts_with("https://") {
+                // Attributes are escaped in the pages
+                let url = url.replace("&amp;", "&");
+                urls.entry(url)
+                    .or_default()
+                    .push(page.strip_prefix(output).unwrap_or(page).to_owned());
+            }
+        }
+    }
+
+    let client = http::Client::new();
+
+    let broken = urls
+        .into_par_iter()
+        .filter_map(|(url, pages)| {
+            let reason = match client.head(&url) {
+                Ok(_) => return None,
+                Err(FetchError::Status(405 | 501, _)) => match client.get(&url) {
+                    Ok(_) => return None,
+                    Err(e) => e.to_string(),
+                },
+                Err(e) => e.to_string(),
+            };
+
+            Some(pages.into_iter().map(move |page| BrokenLink {
+                page,
+                url: url.clone(),
+                reason: reason.clone(),
+            }))
+        })
+        .flatten_iter()
+        .collect();
+
+    Ok(broken)
+}
+
+/// Check the links of all the pages in a generated website, failing if any of them is broken.
+///
+/// External links are only checked with `external`, as it requires network access.
+pub fn check_site(output: &Path, external: bool) -> Result<()> {
+    if external && http::offline() {
+        eyre::bail!("The external links can't be checked with --offline");
+    }
+
+    if !output.is_dir() {
+        eyre::bail!("{} is not a directory", output.display());
+    }
+
+    let mut pages = Vec::new();
+    for entry in glob(&output.join("**/*.html").to_string_lossy())? {
+        pages.push(entry?);
+    }
+    pages.sort();
+
+    let mut broken = check_internal(output, &pages)?;
+    if external {
+        broken.extend(check_external(output, &pages)?);
+    }
+
+    for link in &broken {
+        println!("{}: {} ({})", link.page.display(), link.url, link.reason);
+    }
+    println!(
+        "Checked {} page(s){}: {} broken link(s)",
+        pages.len(),
+        if external { " with external links" } else { "" },
+        broken.len()
+    );
+
+    if !broken.is_empty() {
+        eyre::bail!("Found broken links");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_of_pages() {
+        let html = concat!(
+            r#"<img src="a.png" srcset="a.png 1x, a@2x.png 2x">"#,
+            r#"<a href="/b.html#c" data-href="d.html">"#,
+        );
+        assert_eq!(
+            references(html),
+            ["a.png", "/b.html#c", "a.png", "a@2x.png"]
+        );
+    }
+
+    #[test]
+    fn local_references() {
+        let (output, page) = (Path::new("out"), Path::new("out/posts/a.html"));
+        assert_eq!(
+            resolve_local(output, page, "../img/b.png?v=1"),
+            Some(PathBuf::from("out/posts/../img/b.png"))
+        );
+        assert_eq!(
+            resolve_local(output, page, "/assets/c.css#x"),
+            Some(PathBuf::from("out/assets/c.css"))
+        );
+        for url in [
+            "",
+            "#top",
+            "https://example.org",
+            "//cdn.org/d.js",
+            "mailto:me",
+        ] {
+            assert_eq!(resolve_local(output, page, url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn links_to_sources() {
+        let pages = HashMap::from([
+            ("posts/intro.md".into(), "posts/intro.html".into()),
+            ("about.md".into(), "about/index.html".into()),
+        ]);
+        let links = SourceLinks {
+            source: Path::new("posts/other.md"),
+            pages: &pages,
+        };
+
+        let rewrite = |url: &str| links.rewrite(url).unwrap();
+        assert_eq!(
+            rewrite("intro.md#start").as_deref(),
+            Some("/posts/intro.html#start")
+        );
+        assert_eq!(rewrite("./../about.md").as_deref(), Some("/about/"));
+        assert_eq!(
+            rewrite("/about.md?lang=en").as_deref(),
+            Some("/about/?lang=en")
+        );
+        assert_eq!(rewrite("https://example.org/intro.md"), None);
+        assert_eq!(rewrite("image.png#dark"), None);
+
+        assert_eq!(
+            links.rewrite("missing.md").unwrap_err().to_string(),
+            "Link to `missing.md` in posts/other.md does not point to an article"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! Rules checking the house style of the articles, run by `verin lint`.
+//!
+//! Each rule can be turned off or made an error in the `lint` section of the configuration, the
+//! rules that are not configured are warnings.
+
+use std::{collections::BTreeMap, fmt, path::Path};
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+use pulldown_cmark::{Event, Parser, Tag};
+use serde::Deserialize;
+use verin::html;
+
+use crate::{links, parse_article, read_sources, Config, Metadata};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Off,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Off => write!(f, "off"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LintConfig {
+    /// Severity of the rules, by name
+    #[serde(default)]
+    rules: BTreeMap<String, Severity>,
+    /// Maximum number of characters of the titles
+    #[serde(default = "default_max_title_length")]
+    max_title_length: usize,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            rules: BTreeMap::new(),
+            max_title_length: default_max_title_length(),
+        }
+    }
+}
+
+fn default_max_title_length() -> usize {
+    70
+}
+
+/// Article being checked by the rules
+struct Article<'a> {
+    metadata: &'a Metadata,
+    /// Path of the article, relative to the input directory
+    path: &'a Path,
+    body: &'a str,
+    config: &'a Config,
+}
+
+impl Article<'_> {
+    fn events(&self) -> impl Iterator<Item = Event<'_>> {
+        Parser::new_ext(self.body, self.config.markdown.extensions())
+    }
+}
+
+struct Rule {
+    name: &'static str,
+    /// Returns a message for each violation of the rule in the article
+    check: fn(&Article) -> Vec<String>,
+}
+
+const RULES: &[Rule] = &[
+    Rule {
+        name: "title-length",
+        check: title_length,
+    },
+    Rule {
+        name: "summary",
+        check: summary,
+    },
+    Rule {
+        name: "tags",
+        check: tags,
+    },
+    Rule {
+        name: "images-in-assets",
+        check: images_in_assets,
+    },
+    Rule {
+        name: "own-domain-links",
+        check: own_domain_links,
+    },
+];
+
+fn title_length(article: &Article) -> Vec<String> {
+    let max = article.config.lint.max_title_length;
+    let length = article.metadata.title.chars().count();
+
+    if length <= max {
+        return Vec::new();
+    }
+    vec![format!("title is {length} characters long (maximum {max})")]
+}
+
+fn summary(article: &Article) -> Vec<String> {
+    if !article.metadata.summary.trim().is_empty() {
+        return Vec::new();
+    }
+    vec!["summary is empty".to_owned()]
+}
+
+fn tags(article: &Article) -> Vec<String> {
+    if !article.metadata.tags.is_empty() {
+        return Vec::new();
+    }
+    vec!["article has no tags".to_owned()]
+}
+
+fn images_in_assets(article: &Article) -> Vec<String> {
+    article
+        .events()
+        .filter_map(|event| match event {
+            Event::Start(Tag::Image { dest_url, .. }) => Some(dest_url),
+            _ => None,
+        })
+        .filter(|url| {
+            if url.starts_with("//") || url.contains(':') {
+                return true;
+            }
+
+            let path = match url.strip_prefix('/') {
+                Some(absolute) => Path::new(absolute).to_owned(),
+                None => article
+                    .path
+                    .parent()
+                    .unwrap_or(Path::new(""))
+                    .join(url.as_ref()),
+            };
+            !links::normalize(&path).starts_with("assets")
+        })
+        .map(|url| format!("image `{url}` is not in the assets directory"))
+        .collect()
+}
+
+fn own_domain_links(article: &Article) -> Vec<String> {
+    let Some(own) = article.config.base_url().and_then(html::host) else {
+        return Vec::new();
+    };
+
+    article
+        .events()
+        .filter_map(|event| match event {
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) => {
+                Some(dest_url)
+            }
+            _ => None,
+        })
+        .filter(|url| html::host(url).is_some_and(|host| host.eq_ignore_ascii_case(own)))
+        .map(|url| format!("absolute link `{url}` to the website, use a link from its root"))
+        .collect()
+}
+
+/// Check the articles in `input` against the rules, failing if an error is found
+pub fn lint(input: &Path) -> Result<()> {
+    let config = Config::load(input)?;
+
+    for name in config.lint.rules.keys() {
+        if !RULES.iter().any(|rule| rule.name == name) {
+            eyre::bail!(
+                "Unknown lint rule `{name}`, the rules are: {}",
+                RULES
+                    .iter()
+                    .map(|rule| rule.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+
+    let mut sources = read_sources(input)?;
+    sources.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+    let (mut warnings, mut errors) = (0, 0);
+    for (path, source) in &sources {
+        let (metadata, body) =
+            parse_article(source).with_context(|| format!("Could not parse {}", path.display()))?;
+        let article = Article {
+            metadata: &metadata,
+            path,
+            body,
+            config: &config,
+        };
+
+        for rule in RULES {
+            let severity = config
+                .lint
+                .rules
+                .get(rule.name)
+                .copied()
+                .unwrap_or(Severity::Warning);
+            if severity == Severity::Off {
+                continue;
+            }
+
+            for message in (rule.check)(&article) {
+                println!("{}: {severity}: {message} ({})", path.display(), rule.name);
+                match severity {
+                    Severity::Error => errors += 1,
+                    _ => warnings += 1,
+                }
+            }
+        }
+    }
+
+    println!(
+        "Checked {} article(s): {errors} error(s), {warnings} warning(s)",
+        sources.len()
+    );
+    if errors > 0 {
+        eyre::bail!("Found lint errors");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use std::{
+    fs::OpenOptions,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+
+/// Name of the lock file, in the output directory
+pub const LOCK_FILE: &str = ".verin.lock";
+
+/// Process holding the lock of an output directory
+#[derive(Serialize, Deserialize, Debug)]
+struct Holder {
+    pid: u32,
+    command: String,
+    started: String,
+}
+
+impl Holder {
+    /// Check if the process is still running, when it can be known
+    fn is_alive(&self) -> bool {
+        let proc = Path::new("/proc");
+        !proc.exists() || proc.join(self.pid.to_string()).exists()
+    }
+}
+
+/// Lock on an output directory, preventing concurrent builds from writing in it.
+///
+/// The lock is released when dropped.
+pub struct BuildLock {
+    path: PathBuf,
+}
+
+impl BuildLock {
+    pub fn acquire(output: &Path) -> Result<Self> {
+        let path = output.join(LOCK_FILE);
+        let holder = Holder {
+            pid: std::process::id(),
+            command: std::env::args().collect::<Vec<_>>().join(" "),
+            started: chrono::Local::now().to_rfc3339(),
+        };
+
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => {
+                    serde_json::to_writer(file, &holder)?;
+                    return Ok(Self { path });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    let current: Option<Holder> = std::fs::read_to_string(&path)
+                        .ok()
+                        .and_then(|lock| serde_json::from_str(&lock).ok());
+
+                    match current {
+                        Some(current) if !current.is_alive() => {
+                            println!(
+                                "[WARNING] Removing stale lock of process {} ({})",
+                                current.pid, current.command
+                            );
+                            std::fs::remove_file(&path).context("Could not remove stale lock")?;
+                        }
+                        Some(current) => eyre::bail!(
+                            "{} is being built by process {} ({}) since {}",
+                            output.display(),
+                            current.pid,
+                            current.command,
+                            current.started
+                        ),
+                        None => eyre::bail!(
+                            "{} is locked by another build, remove {} if no build is running",
+                            output.display(),
+                            path.display()
+                        ),
+                    }
+                }
+                Err(e) => return Err(e).context("Could not create lock file"),
+            }
+        }
+    }
+}
+
+impl Drop for BuildLock {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            eprintln!("Could not remove lock file {}: {e}", self.path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty output directory for the test `name`
+    fn output(name: &str) -> PathBuf {
+        let output = std::env::temp_dir().join(format!("verin-lock-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&output);
+        std::fs::create_dir_all(&output).unwrap();
+        output
+    }
+
+    #[test]
+    fn exclusive() {
+        let output = output("exclusive");
+
+        let lock = BuildLock::acquire(&output).unwrap();
+        let error = BuildLock::acquire(&output).err().unwrap().to_string();
+        assert!(
+            error.contains(&format!("is being built by process {}", std::process::id())),
+            "{error}"
+        );
+
+        drop(lock);
+        assert!(!output.join(LOCK_FILE).exists());
+        drop(BuildLock::acquire(&output).unwrap());
+
+        std::fs::remove_dir_all(&output).unwrap();
+    }
+
+    #[test]
+    fn unreadable() {
+        let output = output("unreadable");
+        std::fs::write(output.join(LOCK_FILE), "garbage").unwrap();
+
+        let error = BuildLock::acquire(&output).err().unwrap().to_string();
+        assert!(error.contains("is locked by another build"), "{error}");
+
+        std::fs::remove_dir_all(&output).unwrap();
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn stale() {
+        let output = output("stale");
+        let holder = Holder {
+            pid: u32::MAX,
+            command: "verin build".to_owned(),
+            started: String::new(),
+        };
+        assert!(!holder.is_alive());
+        std::fs::write(
+            output.join(LOCK_FILE),
+            serde_json::to_string(&holder).unwrap(),
+        )
+        .unwrap();
+
+        drop(BuildLock::acquire(&output).unwrap());
+
+        std::fs::remove_dir_all(&output).unwrap();
+    }
+}
@@ -1,8 +1,12 @@
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
     fs::OpenOptions,
     io::BufWriter,
     path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
 };
 
 use chrono::NaiveDate;
@@ -12,50 +16,266 @@ use color_eyre::{
     Result,
 };
 use glob::glob;
-use liquid::Template;
-use pulldown_cmark::Options;
+use pulldown_cmark::{Event, Options, Tag, TagEnd};
+use rayon::prelude::*;
+use secrets::println_redacted;
 use serde::{Deserialize, Serialize};
-use ts_highlight_html::{theme, SyntaxConfig};
+use ts_highlight_html::{theme, Output, SyntaxConfig, Theme};
+use url::SiteUrl;
+use verin::html;
+
+/// Directory holding the files kept between builds, relative to the input directory
+const CACHE_DIR: &str = ".verin-cache";
+
+#[derive(clap::Args, Clone)]
+struct BuildArgs {
+    input: PathBuf,
+    output: PathBuf,
+    /// Development build, whose pages are not signed
+    #[clap(short, long)]
+    debug: bool,
+    /// Port on which the websockets of the refresh server listen, set by `serve`
+    #[clap(skip)]
+    refresh_port: u16,
+    /// Generate a RSS feed
+    #[clap(short, long)]
+    rss: bool,
+    /// Generate an Atom feed
+    #[clap(long)]
+    atom: bool,
+    /// Generate a `publish-schedule.json` listing the posts dated in the future
+    #[clap(long)]
+    schedule: bool,
+    /// Highlight theme, overriding the one in the configuration
+    ///
+    /// Either the name of a builtin theme or a path to a theme file.
+    #[clap(long)]
+    theme: Option<String>,
+    /// Fail the build instead of warning when checks do not pass
+    #[clap(long)]
+    strict: bool,
+    /// Build the draft articles (they are not listed in the index or the RSS feed)
+    #[clap(long)]
+    drafts: bool,
+    /// Build the articles dated in the future, that are skipped until their date otherwise
+    #[clap(long)]
+    future: bool,
+    /// Report the allocations made during each phase of the build, and the peak memory usage
+    #[clap(long)]
+    profile_alloc: bool,
+    /// Write a flamegraph of the phases of the build and of each article in the cache directory
+    #[clap(long)]
+    profile: bool,
+    /// Path under which the website is published (`/preview/branch/`), prefixed to all its URLs
+    ///
+    /// Overrides the `base_path` of the configuration.
+    #[clap(long)]
+    base_path: Option<String>,
+    /// Print the number of code blocks per language, the ones that were not highlighted, and the
+    /// uses of the markdown extensions
+    #[clap(long)]
+    report: bool,
+    /// Sign the pages of the articles with the key of the configuration, which may ask for its
+    /// passphrase
+    #[clap(skip)]
+    sign: bool,
+}
 
 #[derive(Parser)]
+struct Cli {
+    /// Disable the network, the features using it failing instead
+    #[clap(long, global = true)]
+    offline: bool,
+    #[clap(subcommand)]
+    command: Args,
+}
+
+#[derive(clap::Subcommand)]
 enum Args {
-    Build {
-        input: PathBuf,
-        output: PathBuf,
-        /// Listen on the refresh server for refresh requests
-        #[clap(short, long)]
-        debug: bool,
-        #[clap(long, default_value = "4111")]
-        refresh_port: u16,
-        /// Generate a RSS feed
-        #[clap(short, long)]
-        rss: bool,
-    },
-    /// Start the refresh server used for debug mode
+    Build(BuildArgs),
+    /// Build the website in a temporary directory and serve it over HTTP
     ///
-    /// Whenever a refresh request occurs the server sends the request to the webpage by websocket.
-    StartRefreshServer {
+    /// The refresh script is injected in the served pages, and a refresh request rebuilds the
+    /// website before refreshing the pages.
+    Serve {
+        input: PathBuf,
+        /// Port of the HTTP server
+        #[clap(short, long, default_value = "8000")]
+        port: u16,
         /// Port on which the websockets listen
         #[clap(short = 'r', long, default_value = "4111")]
         refresh_port: u16,
         /// Port on which the server listens for refresh requests
-        #[clap(short = 'p', long, default_value = "4112")]
+        #[clap(long, default_value = "4112")]
         request_port: u16,
+        /// Build the draft articles
+        #[clap(long)]
+        drafts: bool,
+        /// Build the articles dated in the future
+        #[clap(long)]
+        future: bool,
     },
     TriggerRefresh {
         #[clap(short, long, default_value = "4112")]
         port: u16,
     },
+    /// Serve a JSON-RPC API on stdin/stdout for editor integration
+    ///
+    /// Messages are framed as in the Language Server Protocol. The available methods are
+    /// `articles/list`, `articles/resolve` (with a `name`), `articles/preview` (with the `text` of
+    /// an article), `site/config` and `site/reload`.
+    ServeApi {
+        input: PathBuf,
+    },
+    /// Print the JSON schema of the front matter of the articles
+    ///
+    /// This can be used by editors to validate and complete the metadata of articles.
+    Schema {
+        input: PathBuf,
+        /// Write the schema to a file instead of stdout
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Check that the URLs published in a previous manifest are still served
+    ///
+    /// Fails if an article was removed or moved without an alias, unless its URL is in the removed
+    /// paths of the `errors` configuration.
+    CheckUrls {
+        input: PathBuf,
+        /// `manifest.json` of the published website
+        #[clap(long)]
+        against: PathBuf,
+    },
+    /// Check the articles against the lint rules of the configuration
+    ///
+    /// Fails if a rule configured as an error is broken.
+    Lint {
+        input: PathBuf,
+    },
+    /// Print the articles that were not updated for longer than the freshness policies allow
+    Check {
+        input: PathBuf,
+    },
+    /// Print the articles scheduled for publication, the recently published ones and the drafts
+    Calendar {
+        input: PathBuf,
+        /// Number of published articles to show
+        #[clap(long, default_value_t = 5)]
+        recent: usize,
+    },
+    /// Print the textual changes of the pages between two builds of the website
+    ///
+    /// Only the text of the pages is compared, ignoring the changes of the markup or of the
+    /// fingerprints of the assets.
+    Diff {
+        /// Output of the previous build
+        old: PathBuf,
+        /// Output of the new build
+        new: PathBuf,
+    },
+    /// Save the image of the clipboard in the assets, and print its markdown reference
+    ///
+    /// The image is written as a PNG in the `assets/pasted` directory of the website of the
+    /// article, named after the article and fingerprinted with the hash of the image.
+    #[cfg(feature = "clipboard")]
+    PasteImage {
+        article: PathBuf,
+        /// Alternative text of the image
+        #[clap(long, default_value = "")]
+        alt: String,
+        /// Also add the reference at the end of the article
+        #[clap(long)]
+        append: bool,
+    },
+    /// Check the links of a generated website
+    ///
+    /// Fails if a local link does not point to a file of the website, or if its fragment is not
+    /// an `id` of the target page.
+    CheckLinks {
+        output: PathBuf,
+        /// Also check that the external links can be fetched, with HTTP `HEAD` requests
+        #[clap(long)]
+        external: bool,
+        /// Path under which the website is published (`/preview/branch/`), as given to `build`
+        #[clap(long)]
+        base_path: Option<String>,
+    },
+    /// Check that the secrets of the configuration are set in the environment
+    ///
+    /// Only prints whether each secret is set, never its value.
+    Secrets {
+        input: PathBuf,
+    },
+}
+
+/// Date of the metadata of an article, a string in the `date.input` format of the configuration or
+/// a TOML date (`date = 2024-01-15`)
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum MetadataDate {
+    Text(String),
+    Toml(toml::value::Datetime),
+}
+
+impl MetadataDate {
+    fn parse(&self, config: &DateConfig) -> Result<NaiveDate> {
+        match self {
+            MetadataDate::Text(date) => Ok(NaiveDate::parse_from_str(date, &config.input)?),
+            MetadataDate::Toml(datetime) => datetime
+                .date
+                .and_then(|date| {
+                    NaiveDate::from_ymd_opt(date.year.into(), date.month.into(), date.day.into())
+                })
+                .ok_or_else(|| eyre::eyre!("`{datetime}` is not a date")),
+        }
+    }
+}
+
+impl std::fmt::Display for MetadataDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetadataDate::Text(date) => write!(f, "{date}"),
+            MetadataDate::Toml(datetime) => write!(f, "{datetime}"),
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
 struct Metadata {
-    date: String,
+    date: MetadataDate,
     title: String,
     page: String,
     summary: String,
     #[serde(default = "create_seven")]
     max_depth: u8,
+    #[serde(default)]
+    tags: Vec<String>,
+    /// Drafts are only built with `--drafts`, and are never listed
+    #[serde(default)]
+    draft: bool,
+    /// Date of the last significant update of the article
+    #[serde(default)]
+    updated: Option<MetadataDate>,
+    /// Former URLs of the article, redirecting to its current URL
+    #[serde(default)]
+    aliases: Vec<String>,
+    /// Add a link to itself in each heading
+    #[serde(default)]
+    heading_anchors: bool,
+    /// Name of the article in its URL, defaults to the name of its file
+    #[serde(default)]
+    slug: Option<String>,
+    /// URL of the original version of the article, when it was first published elsewhere
+    #[serde(default)]
+    canonical_url: Option<String>,
+    /// URLs of the copies of the article published on other websites
+    #[serde(default)]
+    crosspost: Vec<String>,
+    /// Fields unknown to verin, given to the template as `extra`
+    ///
+    /// Unlike a TOML table, it can hold the `null` values of the YAML metadata.
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
 }
 
 fn create_seven() -> u8 {
@@ -64,14 +284,79 @@ fn create_seven() -> u8 {
 
 impl Metadata {
     fn date(&self, config: &DateConfig) -> Result<NaiveDate> {
-        Ok(NaiveDate::parse_from_str(&self.date, &config.input)?)
+        self.date.parse(config)
+    }
+
+    /// Date of the last update of the article, defaulting to its date
+    fn updated(&self, config: &DateConfig) -> Result<NaiveDate> {
+        match &self.updated {
+            Some(updated) => updated.parse(config),
+            None => self.date(config),
+        }
+    }
+
+    /// Canonical URL of the article generated at `path`: its `canonical_url`, or its URL on the
+    /// website published at `base_url`
+    fn canonical_url(&self, path: &Path, base_url: Option<&str>) -> Option<String> {
+        self.canonical_url
+            .clone()
+            .or_else(|| base_url.map(|base| SiteUrl::from_page(path).with_base(base)))
+    }
+
+    fn render_options(&self, config: &Config) -> html::RenderOptions {
+        html::RenderOptions {
+            heading_anchors: self.heading_anchors,
+            ..config.render_options()
+        }
     }
 }
 
-mod html;
+mod allocator;
+mod api;
+mod assets;
+mod atom;
+mod budget;
+mod calendar;
+mod diagrams;
+mod diff;
+mod errors;
+mod filters;
+mod freshness;
+mod glossary;
+mod http;
+mod images;
+mod includes;
+mod integrity;
+mod layout;
+mod links;
+mod lint;
+mod lock;
+mod manifest;
+mod pagination;
+#[cfg(feature = "clipboard")]
+mod paste;
+mod permalink;
+mod profile;
 mod refresh;
+mod schedule;
+mod schema;
+mod search;
+mod secrets;
+mod serve;
+mod service_worker;
+mod shortcodes;
+mod shutdown;
+mod sitemap;
+mod slug;
+mod staging;
+mod stats;
+mod svg;
+mod tags;
+mod templates;
+mod url;
+mod writer;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 struct ChannelData {
     title: String,
     link: String,
@@ -160,12 +445,317 @@ impl From<ChannelData> for rss::Channel {
     }
 }
 
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum FeedKind {
+    Rss,
+    Atom,
+}
+
+impl FeedKind {
+    fn file(self) -> &'static str {
+        match self {
+            FeedKind::Rss => "rss.xml",
+            FeedKind::Atom => "atom.xml",
+        }
+    }
+
+    fn mime(self) -> &'static str {
+        match self {
+            FeedKind::Rss => "application/rss+xml",
+            FeedKind::Atom => "application/atom+xml",
+        }
+    }
+}
+
+/// `<link rel="alternate">` tags announcing the generated `feeds` of the `channel`, to be inserted
+/// in the `<head>` of the pages
+fn feed_links(channel: Option<&ChannelData>, feeds: &[FeedKind]) -> String {
+    let Some(channel) = channel else {
+        return String::new();
+    };
+
+    let mut title = String::new();
+    pulldown_cmark_escape::escape_html(&mut title, &channel.title)
+        .expect("writing to a string can't fail");
+    feeds
+        .iter()
+        .map(|feed| {
+            format!(
+                r#"<link rel="alternate" type="{}" title="{title}" href="{}">"#,
+                feed.mime(),
+                SiteUrl::root().join(feed.file())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[derive(Deserialize, Debug)]
 struct Config {
     name: String,
+    /// URL at which the website is published (`https://example.org`)
+    #[serde(default)]
+    base_url: Option<String>,
+    /// Pattern of the paths of the articles (`/:year/:month/:slug/`)
+    #[serde(default)]
+    permalink: Option<String>,
+    /// Path under which the website is published (`/verin/`), prefixed to all its URLs
+    #[serde(default)]
+    base_path: Option<String>,
+    /// Write the articles to `<slug>/index.html` instead of `<slug>.html`
+    #[serde(default)]
+    pretty_urls: bool,
     date: DateConfig,
     #[serde(default)]
     rss: Option<ChannelData>,
+    /// Feeds to generate, in addition to the ones given on the command line
+    #[serde(default)]
+    feeds: Vec<FeedKind>,
+    #[serde(default)]
+    budget: Option<budget::BudgetConfig>,
+    #[serde(default)]
+    service_worker: Option<service_worker::ServiceWorkerConfig>,
+    #[serde(default)]
+    highlight: HighlightConfig,
+    #[serde(default)]
+    front_matter: schema::FrontMatterConfig,
+    #[serde(default)]
+    errors: errors::ErrorPagesConfig,
+    #[serde(default)]
+    lint: lint::LintConfig,
+    /// Maximum age of the articles before they are considered stale
+    #[serde(default)]
+    freshness: Vec<freshness::FreshnessPolicy>,
+    /// Write a `search_index.json` of the articles
+    #[serde(default)]
+    search: Option<search::SearchConfig>,
+    #[serde(default)]
+    svg: svg::SvgConfig,
+    /// Write a `sitemap.xml`, using the `base_url`
+    #[serde(default)]
+    sitemap: bool,
+    #[serde(default)]
+    math: html::MathOutput,
+    #[serde(default)]
+    markdown: MarkdownConfig,
+    /// Write the `mermaid` and `dot` code blocks as diagrams
+    #[serde(default)]
+    diagrams: Option<diagrams::DiagramsConfig>,
+    #[serde(default)]
+    template_engine: templates::EngineKind,
+    #[serde(default)]
+    images: images::ImagesConfig,
+    #[serde(default)]
+    assets: assets::AssetsConfig,
+    /// Hash and signature of the articles
+    #[serde(default)]
+    integrity: Option<integrity::IntegrityConfig>,
+    /// Environment variables holding the secrets of the integrations
+    #[serde(default)]
+    secrets: secrets::SecretsConfig,
+    /// Attributes of the links of the articles to other websites
+    #[serde(default)]
+    external_links: html::ExternalLinks,
+}
+
+impl Config {
+    fn load(input: &Path) -> Result<Self> {
+        Ok(toml::from_str(
+            &std::fs::read_to_string(input.join("config.toml"))
+                .context("Could not read config.toml")?,
+        )?)
+    }
+
+    /// URL at which the website is published, defaulting to the link of the RSS channel
+    fn base_url(&self) -> Option<&str> {
+        self.base_url
+            .as_deref()
+            .or(self.rss.as_ref().map(|rss| rss.link.as_str()))
+    }
+
+    /// Options of the markdown rendered by the templates and the shortcodes
+    fn render_options(&self) -> html::RenderOptions {
+        html::RenderOptions {
+            math: self.math,
+            extensions: self.markdown.extensions(),
+            sections: self.markdown.sections,
+            wrap_code_blocks: self.markdown.wrap_code_blocks,
+            ..Default::default()
+        }
+    }
+}
+
+/// Markdown extensions enabled in the articles, the templates and the shortcodes, and how their
+/// headings and code blocks are written
+#[derive(Deserialize, Debug)]
+struct MarkdownConfig {
+    #[serde(default = "enabled")]
+    tables: bool,
+    #[serde(default = "enabled")]
+    footnotes: bool,
+    #[serde(default = "enabled")]
+    math: bool,
+    /// GitHub alerts (`> [!NOTE]`)
+    #[serde(default = "enabled")]
+    alerts: bool,
+    #[serde(default)]
+    strikethrough: bool,
+    #[serde(default)]
+    tasklists: bool,
+    /// Attributes of the headings (`# Title {#id .class}`)
+    #[serde(default)]
+    heading_attributes: bool,
+    #[serde(default)]
+    definition_lists: bool,
+    /// Wrap the headings and their content in `<section>`s
+    #[serde(default)]
+    sections: bool,
+    /// Wrap the code blocks in a `<div class="code-block">` with their language and title
+    #[serde(default)]
+    wrap_code_blocks: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> Self {
+        Self {
+            tables: enabled(),
+            footnotes: enabled(),
+            math: enabled(),
+            alerts: enabled(),
+            strikethrough: false,
+            tasklists: false,
+            heading_attributes: false,
+            definition_lists: false,
+            sections: false,
+            wrap_code_blocks: false,
+        }
+    }
+}
+
+impl MarkdownConfig {
+    fn extensions(&self) -> Options {
+        [
+            (self.tables, Options::ENABLE_TABLES),
+            (self.footnotes, Options::ENABLE_FOOTNOTES),
+            (self.math, Options::ENABLE_MATH),
+            (self.alerts, Options::ENABLE_GFM),
+            (self.strikethrough, Options::ENABLE_STRIKETHROUGH),
+            (self.tasklists, Options::ENABLE_TASKLISTS),
+            (self.heading_attributes, Options::ENABLE_HEADING_ATTRIBUTES),
+            (self.definition_lists, Options::ENABLE_DEFINITION_LIST),
+        ]
+        .into_iter()
+        .filter(|&(enabled, _)| enabled)
+        .fold(Options::empty(), |options, (_, extension)| {
+            options | extension
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct HighlightConfig {
+    /// Use classes instead of inline styles for highlighting, the theme is written to
+    /// `highlight.css`
+    #[serde(default)]
+    classes: bool,
+    /// Name of a builtin theme, or path to a TOML theme file relative to the input directory
+    #[serde(default)]
+    theme: Option<String>,
+    /// Theme used when the reader prefers a dark color scheme, `theme` being the light one
+    #[serde(default)]
+    dark_theme: Option<String>,
+    /// Additional names of the languages of the code blocks (`shell = "bash"`)
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+}
+
+impl HighlightConfig {
+    /// Highlighter of the code blocks, with the configured theme and aliases, and the highlights
+    /// queries of the input directory (`queries/rust/highlights.scm`)
+    fn syntax(&self, input: &Path) -> Result<SyntaxConfig<'static>> {
+        if self.dark_theme.is_some() && !self.classes {
+            eyre::bail!("The `dark_theme` of the highlighting requires `classes = true`");
+        }
+
+        let mut syntax = SyntaxConfig::with_output(self.theme(input)?, self.output()).with_aliases(
+            self.aliases
+                .iter()
+                .map(|(alias, name)| (alias.as_str(), name.as_str())),
+        );
+
+        for query in glob(&input.join("queries/*/highlights.scm").to_string_lossy())? {
+            let query = query?;
+            let language = query
+                .parent()
+                .and_then(Path::file_name)
+                .unwrap_or_default()
+                .to_string_lossy();
+            let highlights = std::fs::read_to_string(&query)
+                .with_context(|| format!("Could not read {}", query.display()))?;
+            syntax = syntax
+                .with_highlights(&language, &highlights)
+                .with_context(|| format!("Invalid highlights query {}", query.display()))?;
+        }
+
+        Ok(syntax)
+    }
+
+    /// Load the configured theme
+    fn theme(&self, input: &Path) -> Result<&'static Theme> {
+        match &self.theme {
+            Some(name) => load_theme(input, name),
+            None => Ok(&*theme::TOKYO_NIGHT),
+        }
+    }
+
+    /// Stylesheet of the theme used with classes, following the color scheme of the reader if a
+    /// `dark_theme` is configured
+    fn stylesheet(&self, input: &Path, theme: &Theme) -> Result<String> {
+        match &self.dark_theme {
+            Some(dark) => Ok(theme.to_dual_css(load_theme(input, dark)?)),
+            None => Ok(theme.to_css()),
+        }
+    }
+
+    /// Theme files used by the configuration, relative to the `input` directory
+    fn theme_files(&self, input: &Path) -> Vec<PathBuf> {
+        self.theme
+            .iter()
+            .chain(&self.dark_theme)
+            .filter(|name| theme::builtin(name).is_none())
+            .map(|name| input.join(name))
+            .collect()
+    }
+
+    fn output(&self) -> Output {
+        if self.classes {
+            Output::Classes
+        } else {
+            Output::Inline
+        }
+    }
+}
+
+/// Load a builtin theme, or a theme file relative to the `input` directory
+///
+/// Themes loaded from a file are leaked, as they are used for the whole run
+fn load_theme(input: &Path, name: &str) -> Result<&'static Theme> {
+    match theme::builtin(name) {
+        Some(theme) => Ok(theme),
+        None => Ok(Box::leak(Box::new(
+            Theme::from_toml(input.join(name)).with_context(|| {
+                format!(
+                    "Could not load theme file {name} (builtin themes are: {})",
+                    theme::BUILTIN.join(", ")
+                )
+            })?,
+        ))),
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -174,7 +764,40 @@ struct DateConfig {
     output: String,
 }
 
+/// Read all the markdown files in `input`, returns their path relative to `input` and their
+/// content
+fn read_sources(input: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let mut sources = Vec::new();
+
+    for entry in glob(&input.join("**/*.md").to_string_lossy())? {
+        let entry = entry?;
+
+        let path = entry
+            .strip_prefix(input)
+            .context("could not remove leading dir from file")?
+            .to_owned();
+
+        sources.push((path, std::fs::read_to_string(&*entry)?));
+    }
+
+    Ok(sources)
+}
+
+/// Split the metadata of an article from its body, and parse it.
+///
+/// The metadata is either TOML terminated by `/~`, or fenced by `---` (YAML) or `+++` (TOML)
+/// lines like in other static site generators.
 fn parse_article(s: &str) -> Result<(Metadata, &str)> {
+    if let Some((start, end)) = split_fenced(s, "---")? {
+        return Ok((
+            serde_yaml::from_str(start).context("Invalid YAML metadata")?,
+            end,
+        ));
+    }
+    if let Some((start, end)) = split_fenced(s, "+++")? {
+        return Ok((toml::from_str(start)?, end));
+    }
+
     let pattern = "/~";
 
     let idx = s
@@ -186,282 +809,1315 @@ fn parse_article(s: &str) -> Result<(Metadata, &str)> {
     Ok((toml::from_str(start)?, end))
 }
 
-fn refresh(debug: bool, port: u16) -> String {
-    if debug {
-        format!(
-            r#"
-        <script>
-            let ws = new WebSocket("ws://localhost:{port}");
-            ws.onopen = function(_) {{
-                console.log("WS started");
-            }};
-
-            ws.onmessage = function(_) {{
-                console.log("REFRESH");
-                document.location.reload()
-            }};
-
-            ws.onerror = function(error) {{
-                console.log(`[error] WS error: ${{error.message}}`);
-            }};
-        </script>
-        "#
-        )
-        .into()
-    } else {
-        "".into()
+/// Split the metadata fenced by `fence` lines at the start of an article from its body
+fn split_fenced<'s>(s: &'s str, fence: &str) -> Result<Option<(&'s str, &'s str)>> {
+    let Some(rest) = s.strip_prefix(fence) else {
+        return Ok(None);
+    };
+    let Some(rest) = rest
+        .strip_prefix('\n')
+        .or_else(|| rest.strip_prefix("\r\n"))
+    else {
+        return Ok(None);
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == fence {
+            return Ok(Some((&rest[..offset], &rest[offset + line.len()..])));
+        }
+        offset += line.len();
     }
+
+    eyre::bail!("Could not find the closing `{fence}` of the metadata")
 }
 
 struct ArticleConfig<'a> {
     metadata: Metadata,
+    /// Input directory of the website
+    input: &'a Path,
     output: PathBuf,
+    /// Path of the page, relative to the output directory
+    page: &'a Path,
+    /// Position of the page, for the articles split in several pages
+    pagination: Option<&'a pagination::Pagination>,
     syntax_conf: &'a SyntaxConfig<'a>,
-    templates: &'a Templates,
-    debug: bool,
+    templates: &'a dyn templates::Engine,
+    /// Variables given to all the pages
+    shared: &'a templates::SharedContext,
     config: &'a Config,
+    links: links::SourceLinks<'a>,
+    shortcodes: &'a shortcodes::Shortcodes,
+    glossary: &'a glossary::Glossary,
+    images: &'a images::Pipeline<'a>,
+    imports: &'a images::Imports,
+    /// Buffer holding the rendered content, reused between articles
+    buffer: &'a mut Vec<u8>,
+    writers: &'a writer::Writers,
+}
+
+/// Render the markdown body of an article to HTML
+fn render_markdown(
+    body: &str,
+    syntax_conf: &SyntaxConfig,
+    options: html::RenderOptions,
+) -> Result<(String, html::Rendered)> {
+    let mut content = Vec::new();
+    let rendered = render_markdown_to(body, syntax_conf, options, None, &mut content)?;
+
+    Ok((
+        String::from_utf8(content).context("generated content was not UTF-8")?,
+        rendered,
+    ))
+}
+
+/// Changes made to the markdown of an article, using the rest of the website
+struct Rewrites<'a> {
+    /// Path of the source of the article, the files included in its code blocks being relative to
+    /// it
+    article: &'a Path,
+    /// Links to other markdown files, rewritten to their articles
+    links: &'a links::SourceLinks<'a>,
+    shortcodes: &'a shortcodes::Shortcodes,
+    /// Abbreviations linked to the glossary
+    glossary: &'a glossary::Glossary,
+    /// Local images, whose dimensions and variants are added to their `<img>` if enabled
+    images: Option<images::ArticleImages<'a>>,
+    /// Images referenced by their path on the filesystem, imported in the output
+    imports: &'a images::Imports,
+    diagrams: Option<&'a diagrams::DiagramsConfig>,
+    /// Template of the fenced code blocks, replacing the default `<pre><code>`
+    code_blocks: Option<&'a dyn html::CodeBlockTemplate>,
+    /// Host of the website, the links to other hosts get the `external_links` attributes
+    host: Option<&'a str>,
+    external_links: &'a html::ExternalLinks,
+}
+
+/// Render the markdown body of an article to HTML in `content`, replacing its contents.
+///
+/// This allows to reuse the same buffer for all the articles. The body of an article of the
+/// website is changed by its `rewrites`.
+fn render_markdown_to(
+    body: &str,
+    syntax_conf: &SyntaxConfig,
+    options: html::RenderOptions,
+    rewrites: Option<&Rewrites>,
+    content: &mut Vec<u8>,
+) -> Result<html::Rendered> {
+    let links = rewrites.map(|rewrites| rewrites.links);
+    let imports = rewrites.map(|rewrites| rewrites.imports);
+    let shortcodes = rewrites.map(|rewrites| (rewrites.shortcodes, rewrites.links.source));
+    let glossary = rewrites.map(|rewrites| rewrites.glossary);
+
+    content.clear();
+
+    let mut error = None;
+    let mut in_code_block = false;
+    let body = pulldown_cmark::TextMergeStream::new(pulldown_cmark::Parser::new_ext(
+        body,
+        options.extensions,
+    ))
+    .map(|event| match event {
+        Event::Start(Tag::CodeBlock(kind)) => {
+            in_code_block = true;
+            Event::Start(Tag::CodeBlock(kind))
+        }
+        Event::End(TagEnd::CodeBlock) => {
+            in_code_block = false;
+            Event::End(TagEnd::CodeBlock)
+        }
+        Event::Text(text) if !in_code_block && shortcodes.is_some() => {
+            match shortcodes.map(|(shortcodes, source)| shortcodes.expand(&text, source)) {
+                Some(Ok(Some(html))) => Event::InlineHtml(html.into()),
+                Some(Err(e)) => {
+                    error.get_or_insert(e);
+                    Event::Text(text)
+                }
+                Some(Ok(None)) | None => Event::Text(text),
+            }
+        }
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => {
+            let dest_url = match links.map(|links| links.rewrite(&dest_url)) {
+                Some(Ok(Some(url))) => url.into(),
+                Some(Err(e)) => {
+                    error.get_or_insert(e);
+                    dest_url
+                }
+                Some(Ok(None)) | None => dest_url,
+            };
+
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            })
+        }
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => {
+            // Images imported in the output are not rewritten as the other links
+            let rewritten = match imports.map(|imports| imports.import(&dest_url)) {
+                Some(Ok(None)) | None => links.map(|links| links.rewrite(&dest_url)),
+                imported => imported,
+            };
+            let dest_url = match rewritten {
+                Some(Ok(Some(url))) => url.into(),
+                Some(Err(e)) => {
+                    error.get_or_insert(e);
+                    dest_url
+                }
+                Some(Ok(None)) | None => dest_url,
+            };
+
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            })
+        }
+        event => event,
+    })
+    .collect::<Vec<_>>();
+    let body = match rewrites {
+        Some(rewrites) => includes::include(body, rewrites.article)
+            .with_context(|| format!("Invalid code block in {}", rewrites.article.display()))?,
+        None => body,
+    };
+    let body = match rewrites.and_then(|rewrites| rewrites.diagrams) {
+        Some(diagrams) => diagrams.render(body)?,
+        None => body,
+    };
+
+    let site = match rewrites {
+        Some(rewrites) => html::Site {
+            host: rewrites.host.map(ToOwned::to_owned),
+            external_links: rewrites.external_links.clone(),
+            code_blocks: rewrites.code_blocks,
+            images: match &rewrites.images {
+                Some(images) => images.resolve(&body),
+                None => html::LocalImages::new(),
+            },
+        },
+        None => html::Site::default(),
+    };
+    let rendered = match glossary {
+        Some(glossary) => html::write_html_in_site(
+            content,
+            glossary.link(body.into_iter()),
+            syntax_conf,
+            options,
+            site,
+        )?,
+        None => html::write_html_in_site(content, body.into_iter(), syntax_conf, options, site)?,
+    };
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(rendered),
+    }
 }
 
-fn render_article(cfg: ArticleConfig, body: &str, refresh_port: u16) -> Result<()> {
-    let template = cfg
-        .templates
-        .pages
-        .get(&cfg.metadata.page)
-        .ok_or_else(|| eyre::eyre!("Template `{}` does not exist", cfg.metadata.page))?;
+fn render_article(cfg: ArticleConfig, body: &str) -> Result<html::Rendered> {
+    if !cfg.templates.contains(&cfg.metadata.page) {
+        eyre::bail!("Template `{}` does not exist", cfg.metadata.page);
+    }
 
     let date = cfg.metadata.date(&cfg.config.date)?;
+    let datetime = date.and_time(Default::default()).and_utc();
+    let updated = cfg.metadata.updated(&cfg.config.date)?;
+    let stale =
+        freshness::stale_policy(cfg.config, &cfg.metadata, chrono::Local::now().date_naive())?
+            .is_some();
 
-    let mut output = BufWriter::new(
-        OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(cfg.output)
-            .context("Could not open output file")?,
-    );
+    // The page is written by the writer threads, while the next ones are rendered
+    let mut output = Vec::new();
 
-    let mut content = Vec::new();
+    let markdown_span = tracing::info_span!("markdown").entered();
+    let code_blocks =
+        cfg.templates
+            .contains(templates::CODE_BLOCK)
+            .then_some(templates::CodeBlocks {
+                engine: cfg.templates,
+                shared: cfg.shared,
+            });
+    let rendered = render_markdown_to(
+        body,
+        cfg.syntax_conf,
+        cfg.metadata.render_options(cfg.config),
+        Some(&Rewrites {
+            article: &cfg.input.join(cfg.links.source),
+            links: &cfg.links,
+            shortcodes: cfg.shortcodes,
+            glossary: cfg.glossary,
+            images: cfg
+                .config
+                .images
+                .dimensions
+                .then_some(images::ArticleImages {
+                    input: cfg.input,
+                    source: cfg.links.source,
+                    pipeline: cfg.images,
+                }),
+            imports: cfg.imports,
+            diagrams: cfg.config.diagrams.as_ref(),
+            code_blocks: code_blocks
+                .as_ref()
+                .map(|code_blocks| code_blocks as &dyn html::CodeBlockTemplate),
+            host: cfg.config.base_url().and_then(html::host),
+            external_links: &cfg.config.external_links,
+        }),
+        cfg.buffer,
+    )?;
+    let content = std::str::from_utf8(cfg.buffer).context("generated content was not UTF-8")?;
+    drop(markdown_span);
 
-    let body = pulldown_cmark::Parser::new_ext(body, Options::ENABLE_MATH);
-    let headers = html::write_html(&mut content, body, cfg.syntax_conf)?;
+    let _template_span = tracing::info_span!("template").entered();
 
-    template.render_to(
-        &mut output,
-        &liquid::object!({
+    cfg.templates.render_to(
+        &cfg.metadata.page,
+        cfg.shared,
+        &serde_json::json!({
             "title": cfg.metadata.title,
+            "url": SiteUrl::from_page(cfg.page),
+            "canonical_url": cfg.metadata.canonical_url(cfg.page, cfg.config.base_url()),
+            "crosspost": cfg.metadata.crosspost,
+            "extra": cfg.metadata.extra,
             "date": date.format(&cfg.config.date.output).to_string(),
-            "content": String::from_utf8(content).context("generated content was not UTF-8")?,
-            "refresh": refresh(cfg.debug, refresh_port),
-            "headers": headers,
+            "date_iso": datetime.to_rfc3339(),
+            "date_unix": datetime.timestamp(),
+            "updated": updated.format(&cfg.config.date.output).to_string(),
+            "stale": stale,
+            "content": content,
+            "integrity": match cfg.config.integrity {
+                Some(_) => integrity::meta(content),
+                None => String::new(),
+            },
+            "headers": rendered.headers,
+            "toc": rendered.toc(cfg.metadata.max_depth),
+            "equations": rendered.equations,
+            "pagination": cfg.pagination,
             "max_depth": cfg.metadata.max_depth,
         }),
+        &mut output,
     )?;
+    cfg.writers.write(cfg.output, output)?;
 
-    Ok(())
+    Ok(rendered)
 }
 
-struct Templates {
-    pages: HashMap<String, Template>,
+struct Article {
+    metadata: Metadata,
+    /// Path of the generated page, relative to the output directory
+    path: PathBuf,
+    rendered: html::Rendered,
 }
 
-fn main() -> Result<()> {
-    color_eyre::install()?;
-    let args = Args::parse();
+/// Write the RSS feed `channel` of the `articles` at `path`, their links using `base_url`
+fn write_rss<'a>(
+    path: &Path,
+    mut channel: rss::Channel,
+    base_url: &str,
+    config: &Config,
+    articles: impl Iterator<Item = &'a Article>,
+) -> Result<()> {
+    channel.set_items(
+        articles
+            .map(|Article { metadata, path, .. }| {
+                let mut item = rss::Item {
+                    pub_date: Some(
+                        chrono::NaiveDateTime::new(
+                            metadata.date(&config.date).unwrap(),
+                            Default::default(),
+                        )
+                        .and_utc()
+                        .to_rfc2822(),
+                    ),
+                    title: Some(metadata.title.clone()),
+                    link: metadata.canonical_url(path, Some(base_url)),
+                    description: Some(metadata.summary.clone()),
+                    ..Default::default()
+                };
 
-    let syntax_conf = SyntaxConfig::new(&theme::TOKYO_NIGHT);
-    let mut templates = Templates {
-        pages: HashMap::new(),
-    };
+                // RSS has no modification date, the one of Atom is commonly used instead
+                if metadata.updated.is_some() {
+                    let updated = rss::extension::Extension {
+                        name: "atom:updated".into(),
+                        value: Some(
+                            metadata
+                                .updated(&config.date)
+                                .unwrap()
+                                .and_time(Default::default())
+                                .and_utc()
+                                .to_rfc3339(),
+                        ),
+                        ..Default::default()
+                    };
+                    item.extensions
+                        .entry("atom".into())
+                        .or_default()
+                        .insert("updated".into(), vec![updated]);
+                }
 
-    match args {
-        Args::Build {
-            input,
-            output,
-            debug,
-            refresh_port,
-            rss,
-        } => {
-            std::fs::create_dir_all(&output)?;
+                item
+            })
+            .collect::<Vec<_>>(),
+    );
+    channel
+        .namespaces
+        .insert("atom".into(), "http://www.w3.org/2005/Atom".into());
 
-            let input = input
-                .canonicalize()
-                .context("failed to canonicalize input")?;
+    let feed = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Could not open {}", path.display()))?;
 
-            let config: Config = toml::from_str(
-                &std::fs::read_to_string(input.join("config.toml"))
-                    .context("Could not read config.toml")?,
-            )?;
+    channel.pretty_write_to(feed, b' ', 4)?;
+    Ok(())
+}
 
-            for entry in glob(&input.as_path().join("**/*.liquid").to_string_lossy())? {
-                let entry = entry?;
-                let template = liquid::ParserBuilder::with_stdlib()
-                    .build()?
-                    .parse_file(&entry)?;
-
-                templates.pages.insert(
-                    entry
-                        .file_stem()
-                        .expect("Template has no file stem, should not be possible")
-                        .to_str()
-                        .ok_or(eyre::eyre!("Template name should be valid UTF-8"))?
-                        .to_owned(),
-                    template,
-                );
-            }
+fn build(args: BuildArgs) -> Result<()> {
+    let BuildArgs {
+        input,
+        output,
+        rss,
+        atom,
+        schedule,
+        theme,
+        strict,
+        drafts,
+        future,
+        profile_alloc,
+        profile,
+        base_path,
+        report,
+        sign,
+        ..
+    } = args;
+    let mut profiler = allocator::Profiler::new(profile_alloc);
+    let flamegraph = profile.then(profile::Profile::start).transpose()?;
+    let build_span = tracing::info_span!("build").entered();
+    profiler.phase("setup")?;
 
-            let mut articles = Vec::new();
+    // Dropped after the lock, so that a shutdown waits for the lock file to be removed
+    let _building = shutdown::building()?;
+    std::fs::create_dir_all(&output)?;
+    let _lock = lock::BuildLock::acquire(&output)?;
+    let previous_manifest = manifest::PageManifest::load(&output.join("manifest.json"))?;
 
-            for entry in glob(&input.as_path().join("**/*.md").to_string_lossy())? {
-                let entry = entry?;
+    // The website is written to a staging directory, replacing the output once the build succeeded
+    let staging = staging::Staging::new(&output)?;
+    let (target, output) = (output, staging.path().to_owned());
 
-                let out = entry
-                    .strip_prefix(&input)
-                    .context("could not remove leading dir from file")?;
+    let input = input
+        .canonicalize()
+        .context("failed to canonicalize input")?;
 
-                let input = std::fs::read_to_string(&*entry)?;
+    let mut config = Config::load(&input)?;
+    if theme.is_some() {
+        config.highlight.theme = theme;
+    }
+    url::set_base_path(base_path.as_deref().or(config.base_path.as_deref()));
+    // Loaded by each build for the integrations, and so that their values are redacted from the
+    // warnings and the errors
+    let _secrets = secrets::Secrets::load(&config.secrets);
 
-                let (metadata, body) = parse_article(&input)?;
+    let syntax_conf = Arc::new(config.highlight.syntax(&input)?);
+    if config.highlight.classes {
+        let stylesheet = config.highlight.stylesheet(&input, syntax_conf.theme())?;
+        std::fs::write(output.join("highlight.css"), stylesheet)
+            .context("Could not write highlight stylesheet")?;
+    }
 
-                articles.push((metadata.clone(), Path::new(out).with_extension("html")));
+    // Problems found by the `md` filter of the templates and the shortcodes
+    let filter_diagnostics = filters::Diagnostics::default();
+    let templates = templates::load(
+        &input,
+        config.template_engine,
+        syntax_conf.clone(),
+        config.base_url().map(ToOwned::to_owned),
+        config.render_options(),
+        filter_diagnostics.clone(),
+    )?;
 
-                render_article(
-                    ArticleConfig {
-                        metadata,
-                        output: output.join(out).with_extension("html"),
-                        syntax_conf: &syntax_conf,
-                        templates: &templates,
-                        config: &config,
-                        debug,
-                    },
-                    body,
-                    refresh_port,
-                )?;
+    profiler.phase("assets")?;
+
+    let mut assets = assets::process(&input, &output)?;
+    profiler.phase("parse")?;
+
+    // Abbreviation definitions are removed from the articles, to be collected in the glossary
+    let mut definitions = HashMap::new();
+    let sources = read_sources(&input)?
+        .into_iter()
+        .map(|(path, source)| {
+            let (stripped, found) = glossary::extract(&source);
+            let stripped = match stripped {
+                Cow::Owned(stripped) => Some(stripped),
+                Cow::Borrowed(_) => None,
+            };
+            definitions.insert(path.clone(), found);
+            (path, stripped.unwrap_or(source))
+        })
+        .collect::<Vec<_>>();
+
+    let mut parsed = sources
+        .iter()
+        .map(|(path, source)| {
+            let (metadata, body) = parse_article(source)?;
+            let out = permalink::article_path(&config, path, &metadata)?;
+            Ok((path.as_path(), out, metadata, body))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    parsed.retain(|(_, _, metadata, _)| drafts || !metadata.draft);
+
+    let today = chrono::Local::now().date_naive();
+    if schedule {
+        let posts = parsed
+            .iter()
+            .filter(|(_, _, metadata, _)| !metadata.draft)
+            .map(|(_, out, metadata, _)| {
+                Ok((
+                    metadata.title.as_str(),
+                    out.as_path(),
+                    metadata.date(&config.date)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        schedule::write(&output, today, posts)?;
+    }
+
+    // Articles dated in the future are published by the first build after their date
+    if !future {
+        let mut published = Vec::with_capacity(parsed.len());
+        for article in parsed {
+            if article.2.date(&config.date)? <= today {
+                published.push(article);
             }
+        }
+        parsed = published;
+    }
 
-            if let Some(not_found) = templates.pages.get("not_found") {
-                let mut output = BufWriter::new(
-                    OpenOptions::new()
-                        .write(true)
-                        .create(true)
-                        .truncate(true)
-                        .open(output.join("404.html"))
-                        .context("Could not open output file")?,
+    let all_tags = tags::collect(
+        parsed
+            .iter()
+            .map(|(_, _, metadata, _)| metadata)
+            .filter(|metadata| !metadata.draft),
+        &tags::load_data(&input)?,
+    );
+    let glossary = glossary::Glossary::collect(
+        parsed
+            .iter()
+            .filter(|(_, _, metadata, _)| !metadata.draft)
+            .map(|(source, ..)| (*source, definitions[*source].as_slice())),
+        templates
+            .contains("glossary")
+            .then(|| SiteUrl::from_path(Path::new("glossary.html"))),
+    );
+
+    let rss = rss || config.feeds.contains(&FeedKind::Rss);
+    let atom = atom || config.feeds.contains(&FeedKind::Atom);
+    let feeds: Vec<_> = [(FeedKind::Rss, rss), (FeedKind::Atom, atom)]
+        .into_iter()
+        .filter_map(|(feed, generated)| generated.then_some(feed))
+        .collect();
+
+    // Variables given to all the pages, converted once for the template engine
+    let shared = templates.share(&serde_json::json!({
+        "blog_name": &config.name,
+        // Kept for the templates written when the refresh script was given to them, it is now
+        // injected by `serve`
+        "refresh": "",
+        "assets": &assets,
+        "service_worker": service_worker::registration(config.service_worker.as_ref()),
+        "all_tags": &all_tags,
+        "feeds": feed_links(config.rss.as_ref(), &feeds),
+    }))?;
+
+    profiler.phase("render")?;
+
+    let mut articles = Vec::new();
+    let mut pages = Vec::new();
+
+    let shortcodes = shortcodes::Shortcodes::load(
+        &input,
+        svg::InlineSvg::new(&input, &config.svg),
+        syntax_conf.clone(),
+        config.base_url().map(ToOwned::to_owned),
+        config.render_options(),
+        filter_diagnostics.clone(),
+    )?;
+
+    // Page of each article, by the path of its source
+    let mut article_pages = HashMap::new();
+    // Pages to render, an article split with page breaks having one page per part
+    let mut jobs = Vec::new();
+    let mut written = HashMap::new();
+    for (idx, (source, out, _, body)) in parsed.iter().enumerate() {
+        article_pages.insert(source.to_path_buf(), out.clone());
+
+        let parts = pagination::split(body, config.markdown.extensions());
+        for ((page, pagination), part) in pagination::pages(out, parts.len()).into_iter().zip(parts)
+        {
+            if let Some(other) = written.insert(page.clone(), source) {
+                eyre::bail!(
+                    "{} and {} are both written to {}",
+                    other.display(),
+                    source.display(),
+                    page.display()
                 );
+            }
+            jobs.push((idx, page, pagination, part));
+        }
+    }
 
-                not_found.render_to(
-                    &mut output,
-                    &liquid::object!({
-                        "blog_name": &config.name,
-                        "refresh": refresh(debug, refresh_port),
-                    }),
-                )?;
+    // Articles are rendered in parallel, each thread reusing its own buffer
+    let render_span = tracing::Span::current();
+    let writers = writer::Writers::new();
+    let images = images::Pipeline::new(&config.images, &input.join(CACHE_DIR), &output);
+    let imports = images::Imports::new(&config.images, &input, &output);
+    let rendered = jobs
+        .par_iter()
+        .map_init(Vec::new, |buffer, (idx, out, pagination, body)| {
+            let (source, _, metadata, _) = &parsed[*idx];
+            shutdown::check()?;
+            let _span = tracing::info_span!(parent: &render_span, "article", path = %out.display())
+                .entered();
+            render_article(
+                ArticleConfig {
+                    metadata: metadata.clone(),
+                    input: &input,
+                    output: output.join(out),
+                    page: out,
+                    pagination: pagination.as_ref(),
+                    syntax_conf: &syntax_conf,
+                    templates: &*templates,
+                    shared: &shared,
+                    config: &config,
+                    links: links::SourceLinks {
+                        source,
+                        pages: &article_pages,
+                    },
+                    shortcodes: &shortcodes,
+                    glossary: &glossary,
+                    images: &images,
+                    imports: &imports,
+                    buffer,
+                    writers: &writers,
+                },
+                body,
+            )
+        })
+        .collect::<Result<Vec<_>>>();
+    // The errors of the writers explain the failures to queue the pages
+    writers.finish()?;
+    let rendered = rendered?;
+    shortcodes
+        .svg()
+        .write_dependencies(&input.join(CACHE_DIR))?;
+
+    let mut search_entries = Vec::new();
+    // Problems found while rendering the articles, reported once at the end of the build
+    let mut diagnostics = Vec::new();
+    let mut rendered = jobs.iter().zip(rendered).peekable();
+    for (idx, (source, out, metadata, body)) in parsed.into_iter().enumerate() {
+        // Pages of the article, the rendering of the first one holding the whole article
+        let ((_, first, _, _), mut article_rendered) =
+            rendered.next().expect("each article has a page");
+        pages.push(output.join(first));
+        while let Some(((_, page, _, _), part)) = rendered.next_if(|((job, ..), _)| *job == idx) {
+            pages.push(output.join(page));
+            article_rendered.headers.extend(part.headers);
+            article_rendered.words += part.words;
+            article_rendered.languages.extend(part.languages);
+            article_rendered.unhighlighted.extend(part.unhighlighted);
+            for (feature, count) in part.features {
+                *article_rendered.features.entry(feature).or_default() += count;
             }
+        }
+        let rendered = article_rendered;
 
-            let index = templates
-                .pages
-                .get("index")
-                .context("should provide an index.html")?;
-            {
-                struct ArticleInfo<'a> {
-                    date: NaiveDate,
-                    name: &'a str,
-                    page: String,
-                    summary: String,
-                }
+        let mut reported = HashSet::new();
+        for diagnostic in rendered.diagnostics() {
+            if reported.insert(diagnostic.clone()) {
+                diagnostics.push((source.display().to_string(), diagnostic));
+            }
+        }
+
+        if metadata.draft {
+            continue;
+        }
+
+        if let Some(search) = &config.search {
+            search_entries.push(search::entry(
+                search,
+                &metadata,
+                &out,
+                &rendered,
+                body,
+                config.markdown.extensions(),
+            ));
+        }
+
+        articles.push(Article {
+            metadata,
+            path: out,
+            rendered,
+        });
+    }
+
+    // The pages are signed once written
+    let signing = config
+        .integrity
+        .as_ref()
+        .and_then(|integrity| integrity.signing.as_ref());
+    if let Some(signing) = signing.filter(|_| sign) {
+        let pages: Vec<_> = jobs
+            .iter()
+            .map(|(_, page, _, _)| output.join(page))
+            .collect();
+        signing.sign(&pages)?;
+    }
+
+    profiler.phase("pages")?;
+
+    let page_manifest = manifest::PageManifest::new(
+        articles
+            .iter()
+            .map(|article| (article.path.as_path(), &article.metadata)),
+    )
+    .with_history(previous_manifest);
+    page_manifest.write(&output)?;
+
+    for (alias, target) in &page_manifest.aliases {
+        if page_manifest.articles.contains_key(alias) {
+            println_redacted!(
+                "[WARNING] Alias {alias} of {target} is the URL of an article, ignoring it"
+            );
+            continue;
+        }
 
-                #[derive(Debug, Serialize)]
-                struct ArticleInfoStr<'a> {
-                    date: String,
-                    name: &'a str,
-                    page: String,
-                    summary: String,
+        let path = output.join(alias);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).context("Could not create output directory")?;
+        }
+        pages.push(path.clone());
+        std::fs::write(path, manifest::redirect_page(target)?)
+            .context("Could not write alias redirection")?;
+    }
+
+    if !templates.contains("index") {
+        eyre::bail!("should provide an index.html");
+    }
+    let info_str = {
+        struct ArticleInfo<'a> {
+            date: NaiveDate,
+            name: &'a str,
+            page: String,
+            url: String,
+            summary: String,
+            tags: &'a [String],
+            stale: bool,
+            updated: NaiveDate,
+        }
+
+        #[derive(Debug, Serialize)]
+        struct ArticleInfoStr<'a> {
+            date: String,
+            /// Midnight UTC of the date, in RFC 3339
+            date_iso: String,
+            date_unix: i64,
+            name: &'a str,
+            page: String,
+            url: String,
+            summary: String,
+            tags: Vec<&'a tags::TagInfo>,
+            stale: bool,
+            updated: String,
+        }
+
+        let today = chrono::Local::now().date_naive();
+        let info: Result<Vec<_>, _> = articles
+            .iter()
+            .map(|article| -> Result<_> {
+                Ok(ArticleInfo {
+                    date: article.metadata.date(&config.date)?,
+                    name: &article.metadata.title,
+                    page: article
+                        .path
+                        .file_name()
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string(),
+                    url: SiteUrl::from_page(&article.path).to_string(),
+                    summary: article.metadata.summary.trim_end().replace('\n', "<br/>"),
+                    tags: &article.metadata.tags,
+                    stale: freshness::stale_policy(&config, &article.metadata, today)?.is_some(),
+                    updated: article.metadata.updated(&config.date)?,
+                })
+            })
+            .collect();
+        let mut info = info?;
+        info.sort_unstable_by_key(|article| Reverse(article.date));
+
+        info.into_iter()
+            .map(|info| {
+                let datetime = info.date.and_time(Default::default()).and_utc();
+                ArticleInfoStr {
+                    date_iso: datetime.to_rfc3339(),
+                    date_unix: datetime.timestamp(),
+                    name: info.name,
+                    page: info.page,
+                    url: info.url,
+                    summary: info.summary,
+                    tags: tags::find(&all_tags, info.tags),
+                    stale: info.stale,
+                    updated: info.updated.format(&config.date.output).to_string(),
+                    date: info.date.format(&config.date.output).to_string(),
                 }
+            })
+            .collect::<Vec<_>>()
+    };
+    {
+        pages.push(output.join("index.html"));
+        let mut output = BufWriter::new(
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(output.join("index.html"))
+                .context("Could not open output file")?,
+        );
+
+        templates.render_to(
+            "index",
+            &shared,
+            &serde_json::json!({
+                "articles": &info_str,
+            }),
+            &mut output,
+        )?;
+    }
+
+    if templates.contains("tag") {
+        std::fs::create_dir_all(output.join("tags")).context("Could not create tags directory")?;
+
+        for tag in &all_tags {
+            let tagged: Vec<_> = info_str
+                .iter()
+                .filter(|article| article.tags.iter().any(|t| t.name == tag.name))
+                .collect();
+
+            let path = output.join(tag.path());
+            pages.push(path.clone());
+            let mut output = BufWriter::new(
+                OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(path)
+                    .context("Could not open output file")?,
+            );
+
+            templates.render_to(
+                "tag",
+                &shared,
+                &serde_json::json!({
+                    "tag": tag,
+                    "articles": tagged,
+                }),
+                &mut output,
+            )?;
+        }
+    }
+
+    if templates.contains("tags") {
+        std::fs::create_dir_all(output.join("tags")).context("Could not create tags directory")?;
+
+        let path = output.join("tags/index.html");
+        pages.push(path.clone());
+        let mut output = BufWriter::new(
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)
+                .context("Could not open output file")?,
+        );
+
+        templates.render_to("tags", &shared, &serde_json::json!({}), &mut output)?;
+    }
 
-                let info: Result<Vec<_>, _> = articles
-                    .iter()
-                    .map(|(metadata, file)| -> Result<_> {
-                        Ok(ArticleInfo {
-                            date: metadata.date(&config.date)?,
-                            name: &metadata.title,
-                            page: file.file_name().unwrap().to_string_lossy().to_string(),
-                            summary: metadata.summary.trim_end().replace('\n', "<br/>"),
-                        })
-                    })
-                    .collect();
-                let mut info = info?;
-                info.sort_unstable_by(|a, b| b.date.cmp(&a.date));
-
-                let info_str: Vec<_> = info
-                    .into_iter()
-                    .map(|info| ArticleInfoStr {
-                        name: info.name,
-                        page: info.page,
-                        summary: info.summary,
-                        date: info.date.format(&config.date.output).to_string(),
-                    })
-                    .collect();
+    if templates.contains("glossary") {
+        let path = output.join("glossary.html");
+        pages.push(path.clone());
+        let mut output = BufWriter::new(
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)
+                .context("Could not open output file")?,
+        );
 
+        templates.render_to(
+            "glossary",
+            &shared,
+            &serde_json::json!({
+                "glossary": &glossary.entries,
+            }),
+            &mut output,
+        )?;
+    }
+
+    let error_pages = config.errors.pages()?;
+    let mut statuses = Vec::new();
+    for error_page in &error_pages {
+        if !templates.contains(error_page.template) {
+            if error_page.optional {
+                continue;
+            }
+            eyre::bail!(
+                "Template {} for the {} error page does not exist",
+                error_page.template,
+                error_page.status
+            );
+        }
+
+        let path = output.join(error_page.file_name());
+        pages.push(path.clone());
+        statuses.push(error_page.status);
+        let mut output = BufWriter::new(
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)
+                .context("Could not open output file")?,
+        );
+
+        templates.render_to(
+            error_page.template,
+            &shared,
+            &serde_json::json!({
+                "articles": &info_str,
+                "status": error_page.status,
+            }),
+            &mut output,
+        )?;
+    }
+
+    if config.errors.stubs {
+        let gone = templates.contains("gone");
+        for (url, entry) in &page_manifest.removed {
+            let path = output.join(url);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).context("Could not create output directory")?;
+            }
+            pages.push(path.clone());
+
+            if gone {
                 let mut output = BufWriter::new(
                     OpenOptions::new()
                         .write(true)
                         .create(true)
                         .truncate(true)
-                        .open(output.join("index.html"))
+                        .open(path)
                         .context("Could not open output file")?,
                 );
 
-                index.render_to(
-                    &mut output,
-                    &liquid::object!({
-                        "blog_name": &config.name,
-                        "refresh": refresh(debug, refresh_port),
-                        "articles": info_str,
+                templates.render_to(
+                    "gone",
+                    &shared,
+                    &serde_json::json!({
+                        "articles": &info_str,
+                        "status": 410,
+                        "removed": {
+                            "title": &entry.title,
+                            "date": &entry.date,
+                            "url": url,
+                        },
                     }),
+                    &mut output,
                 )?;
+            } else {
+                std::fs::write(path, manifest::gone_stub(entry)?)
+                    .context("Could not write removed article stub")?;
             }
+        }
+    }
 
-            if rss {
-                let mut channel: rss::Channel = config
-                    .rss
-                    .context(
-                        "specifying --rss requires to have an `rss` section in the configuration",
-                    )?
-                    .into();
-
-                channel.set_items(
-                    articles
-                        .into_iter()
-                        .map(|(metadata, path)| rss::Item {
-                            pub_date: Some(
-                                chrono::NaiveDateTime::new(
-                                    metadata.date(&config.date).unwrap(),
-                                    Default::default(),
-                                )
-                                .and_utc()
-                                .to_rfc2822(),
-                            ),
-                            title: Some(metadata.title),
-                            link: Some(format!("{}/{}", channel.link, path.to_str().unwrap())),
-                            description: Some(metadata.summary),
-                            ..Default::default()
-                        })
-                        .collect::<Vec<_>>(),
-                );
+    let removed: Vec<_> = config
+        .errors
+        .removed
+        .iter()
+        .cloned()
+        .chain(
+            page_manifest
+                .removed
+                .keys()
+                .map(|url| SiteUrl::parse(url).to_string()),
+        )
+        .collect();
+    errors::write_host_config(
+        &output,
+        &config.errors,
+        &statuses,
+        &removed,
+        &page_manifest.aliases,
+    )?;
 
-                let feed = OpenOptions::new()
-                    .create(true)
-                    .truncate(true)
-                    .write(true)
-                    .open(output.join("rss.xml"))?;
+    if templates.contains("stats") {
+        pages.push(output.join("stats.html"));
+        let mut output = BufWriter::new(
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(output.join("stats.html"))
+                .context("Could not open output file")?,
+        );
 
-                channel.pretty_write_to(feed, b' ', 4)?;
+        templates.render_to(
+            "stats",
+            &shared,
+            &serde_json::json!({
+                "stats": stats::collect(&articles, &config.date)?,
+            }),
+            &mut output,
+        )?;
+    }
+
+    profiler.phase("feeds")?;
+
+    // Checked before the service worker is generated, as it precaches all the assets
+    let unused = assets::unused(&output, &pages, &assets)?;
+    if !unused.is_empty() {
+        if config.assets.prune_unused {
+            assets::prune(&output, &mut assets, &unused)?;
+            println!("Removed {} unused asset(s)", unused.len());
+        } else {
+            println_redacted!(
+                "[WARNING] {} asset(s) not referenced by any page:",
+                unused.len()
+            );
+            for name in &unused {
+                println_redacted!("  {name}");
             }
         }
-        Args::StartRefreshServer {
+    }
+
+    if let Some(sw) = &config.service_worker {
+        let mut recent = articles
+            .iter()
+            .map(|article| Ok((article.metadata.date(&config.date)?, &article.path)))
+            .collect::<Result<Vec<_>>>()?;
+        recent.sort_unstable_by_key(|entry| Reverse(entry.0));
+
+        let mut precached = vec!["index.html".to_owned()];
+        precached.extend(
+            recent
+                .into_iter()
+                .take(sw.recent)
+                .map(|(_, path)| links::url_path(path)),
+        );
+
+        service_worker::generate(&output, sw, &precached, &assets)?;
+    }
+
+    if config.search.is_some() {
+        search::write(&output, &search_entries)?;
+    }
+
+    if atom {
+        let channel = config.rss.as_ref().context(
+            "generating an atom feed requires to have an `rss` section in the configuration",
+        )?;
+
+        atom::write(&output, channel, &config, &articles)?;
+    }
+
+    if rss {
+        let channel: rss::Channel = config
+            .rss
+            .clone()
+            .context("specifying --rss requires to have an `rss` section in the configuration")?
+            .into();
+
+        for tag in &all_tags {
+            let mut tag_channel = channel.clone();
+            tag_channel.title = format!("{} - {}", channel.title, tag.name);
+            if templates.contains("tag") {
+                tag_channel.link = tag.url.with_base(&channel.link);
+            }
+            if let Some(description) = &tag.description {
+                tag_channel.description = description.clone();
+            }
+
+            let path = output.join(tag.feed_path());
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).context("Could not create tag feed directory")?;
+            }
+            let tagged = articles
+                .iter()
+                .filter(|article| article.metadata.tags.contains(&tag.name));
+            write_rss(&path, tag_channel, &channel.link, &config, tagged)?;
+        }
+
+        write_rss(
+            &output.join("rss.xml"),
+            channel.clone(),
+            &channel.link,
+            &config,
+            articles.iter(),
+        )?;
+    }
+
+    if config.sitemap {
+        let base_url = config
+            .base_url()
+            .context("generating a sitemap requires a `base_url` in the configuration")?;
+        sitemap::write(&output, base_url, &config, &articles)?;
+    }
+
+    profiler.phase("checks")?;
+
+    if let Some(budget) = &config.budget {
+        budget::check(&output, &pages, budget, strict)?;
+    }
+
+    let broken = links::check_internal(&output, &pages)?;
+    if !broken.is_empty() {
+        println_redacted!("[WARNING] {} broken internal link(s):", broken.len());
+        for link in &broken {
+            println_redacted!("  {}: {} ({})", link.page.display(), link.url, link.reason);
+        }
+
+        if strict {
+            eyre::bail!("Found broken internal links");
+        }
+    }
+
+    let mut reported = HashSet::new();
+    for diagnostic in filter_diagnostics.take() {
+        if reported.insert(diagnostic.clone()) {
+            diagnostics.push(("the `md` filter".to_owned(), diagnostic));
+        }
+    }
+
+    if !diagnostics.is_empty() {
+        println_redacted!(
+            "[WARNING] {} problem(s) in the articles:",
+            diagnostics.len()
+        );
+        for (source, diagnostic) in &diagnostics {
+            println_redacted!("  {source}: {diagnostic}");
+        }
+
+        if strict {
+            eyre::bail!("Found problems in the articles");
+        }
+    }
+
+    staging.commit(&target, &[lock::LOCK_FILE])?;
+
+    profiler.report();
+    if report {
+        stats::report(&stats::collect(&articles, &config.date)?);
+    }
+
+    drop(build_span);
+    if let Some(flamegraph) = flamegraph {
+        let path = flamegraph.write(&input.join(CACHE_DIR))?;
+        println!("Flamegraph written to {}", path.display());
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    if let Err(e) = color_eyre::install() {
+        eprintln!("Error: {e:?}");
+        return ExitCode::FAILURE;
+    }
+
+    let cli = Cli::parse();
+    http::set_offline(cli.offline);
+
+    match run(cli.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // The secrets may be part of the errors of the integrations
+            eprintln!("Error: {}", secrets::redact(&format!("{e:?}")));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> Result<()> {
+    if matches!(args, Args::Build(_) | Args::Serve { .. }) {
+        shutdown::install()?;
+    }
+
+    match args {
+        // The pages rebuilt at each change are not signed
+        Args::Build(args) => build(BuildArgs {
+            sign: !args.debug,
+            ..args
+        })?,
+        Args::Serve {
+            input,
+            port,
             refresh_port,
             request_port,
-        } => refresh::refresh_server(refresh_port, request_port)?,
+            drafts,
+            future,
+        } => serve::serve(
+            BuildArgs {
+                input,
+                output: std::env::temp_dir().join(format!("verin-{}", std::process::id())),
+                debug: false,
+                refresh_port,
+                rss: false,
+                atom: false,
+                schedule: false,
+                theme: None,
+                strict: false,
+                drafts,
+                future,
+                profile_alloc: false,
+                profile: false,
+                // The website is served from the root of the server
+                base_path: Some("/".to_owned()),
+                report: false,
+                sign: false,
+            },
+            port,
+            request_port,
+        )?,
         Args::TriggerRefresh { port } => refresh::trigger_refresh(port)?,
+        Args::ServeApi { input } => api::serve(&input)?,
+        Args::CheckUrls { input, against } => manifest::check_against(&input, &against)?,
+        Args::CheckLinks {
+            output,
+            external,
+            base_path,
+        } => {
+            url::set_base_path(base_path.as_deref());
+            links::check_site(&output, external)?
+        }
+        Args::Lint { input } => lint::lint(&input)?,
+        Args::Check { input } => freshness::check(&input)?,
+        Args::Diff { old, new } => diff::diff(&old, &new)?,
+        #[cfg(feature = "clipboard")]
+        Args::PasteImage {
+            article,
+            alt,
+            append,
+        } => paste::paste_image(&article, &alt, append)?,
+        Args::Calendar { input, recent } => {
+            calendar::calendar(&input, chrono::Local::now().date_naive(), recent)?
+        }
+        Args::Secrets { input } => secrets::check(&Config::load(&input)?.secrets)?,
+        Args::Schema { input, output } => {
+            let config = Config::load(&input)?;
+            let schema = serde_json::to_string_pretty(&schema::generate(&input, &config)?)?;
+
+            match output {
+                None => println!("{schema}"),
+                Some(path) => std::fs::write(path, schema).context("Could not write schema")?,
+            }
+        }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date_config() -> DateConfig {
+        DateConfig {
+            input: "%d/%m/%Y".to_owned(),
+            output: "%Y-%m-%d".to_owned(),
+        }
+    }
+
+    #[test]
+    fn split_fenced_metadata() {
+        assert_eq!(
+            split_fenced("---\ntitle: a\n---\nbody\n", "---").unwrap(),
+            Some(("title: a\n", "body\n"))
+        );
+        assert_eq!(
+            split_fenced("+++\r\ntitle = 'a'\r\n+++\r\nbody", "+++").unwrap(),
+            Some(("title = 'a'\r\n", "body"))
+        );
+        // The fence must be alone on the first line
+        assert_eq!(split_fenced("--- a\n---\n", "---").unwrap(), None);
+        assert_eq!(split_fenced("title = 'a'\n/~\n", "---").unwrap(), None);
+
+        let error = split_fenced("---\ntitle: a\nbody\n", "---").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Could not find the closing `---` of the metadata"
+        );
+    }
+
+    #[test]
+    fn metadata_dates() {
+        let (metadata, body) = parse_article(
+            "+++\ntitle = 'a'\ndate = 2024-01-15\nupdated = '01/02/2024'\npage = 'article'\n\
+             summary = ''\n+++\nbody",
+        )
+        .unwrap();
+        assert_eq!(body, "body");
+        assert_eq!(
+            metadata.date(&date_config()).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()
+        );
+        assert_eq!(
+            metadata.updated(&date_config()).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()
+        );
+        assert_eq!(metadata.date.to_string(), "2024-01-15");
+
+        let (metadata, _) =
+            parse_article("---\ntitle: a\ndate: 15/01/2024\npage: article\nsummary: ''\n---\nbody")
+                .unwrap();
+        assert_eq!(
+            metadata.date(&date_config()).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()
+        );
+
+        // A time alone is not a date
+        let (metadata, _) =
+            parse_article("title = 'a'\ndate = 10:00:00\npage = 'article'\nsummary = ''\n/~")
+                .unwrap();
+        assert!(metadata.date(&date_config()).is_err());
+    }
+
+    #[test]
+    fn extra_fields() {
+        let (metadata, _) = parse_article(
+            "---\ntitle: a\ndate: 15/01/2024\npage: article\nsummary: ''\ncover: ~\n\
+             series: { name: intro, part: 2 }\n---\nbody",
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::Value::Object(metadata.extra),
+            serde_json::json!({ "cover": null, "series": { "name": "intro", "part": 2 } })
+        );
+    }
+}
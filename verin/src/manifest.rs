@@ -0,0 +1,235 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs::OpenOptions,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{
+    eyre::{self, Context, ContextCompat},
+    Result,
+};
+use pulldown_cmark_escape::{escape_href, escape_html};
+use serde::{Deserialize, Serialize};
+
+use crate::{permalink, url::SiteUrl, Config, Metadata};
+
+/// A published article in `manifest.json`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    pub title: String,
+    pub date: String,
+}
+
+/// List of the articles published by a build, written in `manifest.json`.
+///
+/// The manifest of the previous build is used to know which articles were removed since.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PageManifest {
+    /// Published articles, by URL (relative to the root of the website)
+    pub articles: BTreeMap<String, Entry>,
+    /// Former URLs of the articles, redirecting to their current URL
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    /// Articles that were published by a previous build, but have since been removed
+    #[serde(default)]
+    pub removed: BTreeMap<String, Entry>,
+}
+
+impl PageManifest {
+    /// Load a manifest, if it exists
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Could not open manifest {}", path.display()))?;
+        serde_json::from_reader(file)
+            .with_context(|| format!("Invalid manifest {}", path.display()))
+            .map(Some)
+    }
+
+    /// Create the manifest of the `articles`, given by their output path (relative to the output
+    /// directory) and metadata
+    pub fn new<'a>(articles: impl IntoIterator<Item = (&'a Path, &'a Metadata)>) -> Self {
+        let mut manifest = Self::default();
+        for (path, metadata) in articles {
+            let url = SiteUrl::from_path(path).path().to_owned();
+            for alias in &metadata.aliases {
+                manifest
+                    .aliases
+                    .insert(SiteUrl::parse(alias).path().to_owned(), url.clone());
+            }
+
+            manifest.articles.insert(
+                url,
+                Entry {
+                    title: metadata.title.clone(),
+                    date: metadata.date.to_string(),
+                },
+            );
+        }
+
+        manifest
+    }
+
+    /// Keep track of the articles of the `previous` manifest that no longer exist
+    pub fn with_history(mut self, previous: Option<Self>) -> Self {
+        if let Some(previous) = previous {
+            self.removed = previous.removed;
+            self.removed.extend(previous.articles);
+        }
+        self.removed
+            .retain(|url, _| !self.articles.contains_key(url) && !self.aliases.contains_key(url));
+
+        self
+    }
+
+    pub fn write(&self, output: &Path) -> Result<()> {
+        let file = BufWriter::new(
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(output.join("manifest.json"))
+                .context("Could not open manifest")?,
+        );
+        serde_json::to_writer_pretty(file, self)?;
+
+        Ok(())
+    }
+}
+
+/// Page left at the URL of an alias, redirecting to the current URL of the article
+pub fn redirect_page(target: &str) -> Result<String> {
+    let mut url = String::new();
+    escape_href(&mut url, &SiteUrl::parse(target).to_string())?;
+
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta http-equiv="refresh" content="0; url={url}">
+<link rel="canonical" href="{url}">
+</head>
+<body>
+<p>This article has moved to <a href="{url}">{url}</a>.</p>
+</body>
+</html>
+"#
+    ))
+}
+
+/// Page left at the URL of a removed article when no `gone` template exists
+pub fn gone_stub(entry: &Entry) -> Result<String> {
+    let mut title = String::new();
+    escape_html(&mut title, &entry.title)?;
+    let mut root = String::new();
+    escape_href(&mut root, &SiteUrl::root().to_string())?;
+
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+</head>
+<body>
+<p>The article "{title}" has been removed.</p>
+<p><a href="{root}">Go to the archive</a></p>
+</body>
+</html>
+"#
+    ))
+}
+
+/// A previously published URL that is no longer served
+pub struct MissingUrl {
+    pub url: String,
+    /// Current URL of an article with the same title
+    pub moved_to: Option<String>,
+}
+
+/// Check that all the URLs of `previous` are still served by `current`, either as articles or as
+/// aliases, unless they are explicitly marked as removed in the configuration
+pub fn check_urls(
+    previous: &PageManifest,
+    current: &PageManifest,
+    config: &Config,
+) -> Vec<MissingUrl> {
+    previous
+        .articles
+        .iter()
+        .map(|(url, entry)| (url, Some(entry)))
+        .chain(previous.aliases.keys().map(|url| (url, None)))
+        .filter(|(url, _)| {
+            !current.articles.contains_key(*url)
+                && !current.aliases.contains_key(*url)
+                && !config
+                    .errors
+                    .removed
+                    .iter()
+                    .any(|removed| removed.trim_start_matches('/') == url.as_str())
+        })
+        .map(|(url, entry)| MissingUrl {
+            url: url.clone(),
+            moved_to: entry.and_then(|entry| {
+                current
+                    .articles
+                    .iter()
+                    .find(|(_, current)| current.title == entry.title)
+                    .map(|(url, _)| url.clone())
+            }),
+        })
+        .collect()
+}
+
+/// Run `check-urls`: compare the articles in `input` against a previously published manifest
+pub fn check_against(input: &Path, against: &Path) -> Result<()> {
+    let config = Config::load(input)?;
+    let previous = PageManifest::load(against)?
+        .with_context(|| format!("Manifest {} does not exist", against.display()))?;
+
+    let parsed = crate::read_sources(input)?
+        .into_iter()
+        .map(|(path, source)| {
+            let (metadata, _) = crate::parse_article(&source)?;
+            Ok((
+                permalink::article_path(&config, &path, &metadata)?,
+                metadata,
+            ))
+        })
+        .collect::<Result<Vec<(PathBuf, Metadata)>>>()?;
+    let current = PageManifest::new(
+        parsed
+            .iter()
+            .filter(|(_, metadata)| !metadata.draft)
+            .map(|(path, metadata)| (path.as_path(), metadata)),
+    );
+
+    let missing = check_urls(&previous, &current, &config);
+    if missing.is_empty() {
+        println!(
+            "All {} published URLs are still served",
+            previous.articles.len()
+        );
+        return Ok(());
+    }
+
+    let mut report = String::new();
+    for url in &missing {
+        match &url.moved_to {
+            Some(moved_to) => writeln!(
+                report,
+                "  {}: moved to {moved_to} without an alias",
+                url.url
+            )?,
+            None => writeln!(report, "  {}: no longer published", url.url)?,
+        }
+    }
+
+    eyre::bail!("{} published URL(s) would break:\n{report}", missing.len())
+}
@@ -0,0 +1,192 @@
+//! Split of long articles in several pages.
+//!
+//! An article containing `<!-- page-break -->` markers is rendered to one page per part: the first
+//! one at the path of the article, and the next ones next to it (`intro-2.html`, or
+//! `intro/2/index.html` for an article written to `intro/index.html`).
+//!
+//! The link reference definitions and the footnote definitions belong to the whole article, so
+//! they are copied to the pages using them that don't contain them.
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use serde::Serialize;
+
+use crate::url::SiteUrl;
+
+/// Marker separating the pages of an article
+const PAGE_BREAK: &str = "<!-- page-break -->";
+
+/// Position of an article page among the pages of the article, given to the template
+#[derive(Serialize, Debug)]
+pub struct Pagination {
+    /// Number of the page, starting at 1
+    pub number: usize,
+    pub total: usize,
+    pub previous: Option<SiteUrl>,
+    pub next: Option<SiteUrl>,
+    /// URLs of all the pages of the article
+    pub pages: Vec<SiteUrl>,
+}
+
+/// Split the markdown `body` of an article at its page breaks, parsing it with `options`
+pub fn split(body: &str, options: Options) -> Vec<Cow<'_, str>> {
+    let parser = Parser::new_ext(body, options).into_offset_iter();
+    let links: Vec<_> = parser
+        .reference_definitions()
+        .iter()
+        .map(|(_, definition)| definition.span.clone())
+        .collect();
+
+    let mut breaks = Vec::new();
+    let mut footnotes = HashMap::new();
+    let mut references = Vec::new();
+    for (event, range) in parser {
+        match event {
+            Event::Html(html) if html.trim() == PAGE_BREAK => breaks.push(range),
+            // Footnote labels are case insensitive
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                footnotes.insert(label.to_lowercase(), range);
+            }
+            Event::FootnoteReference(label) => references.push((range.start, label.to_lowercase())),
+            _ => (),
+        }
+    }
+
+    if breaks.is_empty() {
+        return vec![Cow::Borrowed(body)];
+    }
+
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for page_break in breaks.into_iter().chain([body.len()..body.len()]) {
+        let part = start..page_break.start;
+        let outside = |range: &&Range<usize>| range.start < part.start || range.end > part.end;
+
+        let mut definitions: Vec<_> = links
+            .iter()
+            .chain(
+                references
+                    .iter()
+                    .filter(|(offset, _)| part.contains(offset))
+                    .filter_map(|(_, label)| footnotes.get(label)),
+            )
+            .filter(outside)
+            .collect();
+        definitions.sort_by_key(|range| range.start);
+        definitions.dedup();
+
+        if definitions.is_empty() {
+            parts.push(Cow::Borrowed(&body[part]));
+        } else {
+            let mut text = body[part].to_owned();
+            for definition in definitions {
+                text.push_str("\n\n");
+                text.push_str(&body[definition.clone()]);
+            }
+            parts.push(Cow::Owned(text));
+        }
+        start = page_break.end;
+    }
+
+    parts
+}
+
+/// Path of the page `number` of an article whose first page is `first`
+fn page_path(first: &Path, number: usize) -> PathBuf {
+    if number == 1 {
+        return first.to_owned();
+    }
+
+    if first.file_name().is_some_and(|name| name == "index.html") {
+        return first.with_file_name(number.to_string()).join("index.html");
+    }
+
+    let stem = first.file_stem().unwrap_or_default().to_string_lossy();
+    first.with_file_name(format!("{stem}-{number}.html"))
+}
+
+/// Paths of the `count` pages of an article whose first page is `first`, with their pagination if
+/// the article has several pages
+pub fn pages(first: &Path, count: usize) -> Vec<(PathBuf, Option<Pagination>)> {
+    if count == 1 {
+        return vec![(first.to_owned(), None)];
+    }
+
+    let paths: Vec<_> = (1..=count).map(|number| page_path(first, number)).collect();
+    let urls: Vec<_> = paths.iter().map(|path| SiteUrl::from_page(path)).collect();
+
+    paths
+        .into_iter()
+        .enumerate()
+        .map(|(idx, path)| {
+            let pagination = Pagination {
+                number: idx + 1,
+                total: count,
+                previous: idx.checked_sub(1).map(|previous| urls[previous].clone()),
+                next: urls.get(idx + 1).cloned(),
+                pages: urls.clone(),
+            };
+            (path, Some(pagination))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(body: &str) -> Vec<Cow<'_, str>> {
+        super::split(body, Options::ENABLE_FOOTNOTES)
+    }
+
+    #[test]
+    fn single_page() {
+        assert_eq!(split("No break.\n"), ["No break.\n"]);
+    }
+
+    #[test]
+    fn page_breaks() {
+        let parts = split("One\n\n<!-- page-break -->\n\nTwo\n\n<!-- page-break -->\n\nThree\n");
+        assert_eq!(
+            parts.iter().map(|part| part.trim()).collect::<Vec<_>>(),
+            ["One", "Two", "Three"]
+        );
+        // Markers in code are kept
+        assert_eq!(split("```\n<!-- page-break -->\n```\n").len(), 1);
+    }
+
+    #[test]
+    fn definitions_are_copied_to_the_other_pages() {
+        let parts = split(
+            "A [link][docs].[^note]\n\n[docs]: https://example.org\n\n\
+             <!-- page-break -->\n\n\
+             Again [a link][docs].[^NOTE]\n\n\
+             <!-- page-break -->\n\n\
+             No reference.\n\n[^note]: A note\n",
+        );
+
+        assert_eq!(parts.len(), 3);
+        for part in &parts {
+            assert_eq!(part.matches("[docs]: https://example.org").count(), 1);
+            assert_eq!(part.matches("[^note]: A note").count(), 1);
+        }
+        assert!(parts[2].trim().starts_with("No reference."));
+    }
+
+    #[test]
+    fn footnotes_are_only_copied_to_the_pages_using_them() {
+        let parts = split("A note.[^note]\n\n<!-- page-break -->\n\nNone.\n\n[^note]: A note\n");
+
+        assert!(parts[0].contains("[^note]: A note"));
+        assert_eq!(parts[1].matches("[^note]: A note").count(), 1);
+
+        let parts = split("[^note]: A note\n\n<!-- page-break -->\n\nNone.\n");
+        assert!(!parts[1].contains("[^note]"));
+    }
+}
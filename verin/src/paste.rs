@@ -0,0 +1,88 @@
+//! Screenshots pasted in the articles, printed by `verin paste-image`.
+//!
+//! The image of the clipboard is saved as a PNG in the assets of the website, named after the
+//! article and fingerprinted like the imported images, so that pasting it again reuses the same
+//! file.
+
+use std::{
+    ffi::OsStr,
+    io::{Cursor, Write},
+    path::Path,
+};
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{assets, links, slug::slugify};
+
+/// Directory of the pasted images, relative to the `assets` directory
+const PASTED: &str = "pasted";
+
+/// Input directory of the website containing `article`, the first of its parents with a
+/// `config.toml`
+fn site_root(article: &Path) -> Result<&Path> {
+    article
+        .ancestors()
+        .skip(1)
+        .find(|directory| directory.join("config.toml").is_file())
+        .ok_or_else(|| eyre::eyre!("{} is not in a website", article.display()))
+}
+
+/// Read the image of the clipboard, encoded as a PNG
+fn clipboard_png() -> Result<Vec<u8>> {
+    let image = arboard::Clipboard::new()
+        .context("Could not access the clipboard")?
+        .get_image()
+        .context("The clipboard does not contain an image")?;
+
+    let image = image::RgbaImage::from_raw(
+        image.width as u32,
+        image.height as u32,
+        image.bytes.into_owned(),
+    )
+    .ok_or_else(|| eyre::eyre!("Invalid image in the clipboard"))?;
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+        .context("Could not encode the image")?;
+    Ok(png)
+}
+
+/// Save the image of the clipboard in the assets of the website of `article` and print its
+/// markdown reference, with `alt` as its alternative text.
+///
+/// If `append` is set, the reference is also added at the end of the article.
+pub fn paste_image(article: &Path, alt: &str, append: bool) -> Result<()> {
+    if article.extension() != Some(OsStr::new("md")) || !article.is_file() {
+        eyre::bail!("{} is not a markdown article", article.display());
+    }
+    let stem = article.file_stem().unwrap_or_default().to_string_lossy();
+    let input = site_root(article)?;
+
+    let png = clipboard_png()?;
+    let hash = format!("{:x}", Sha256::digest(&png));
+    let name = Path::new(PASTED).join(format!("{}.png", slugify(&stem)));
+    let relative = Path::new("assets").join(assets::fingerprinted(&name, &hash));
+
+    std::fs::create_dir_all(input.join("assets").join(PASTED))
+        .context("Could not create the pasted images directory")?;
+    std::fs::write(input.join(&relative), &png)
+        .with_context(|| format!("Could not write {}", relative.display()))?;
+
+    let reference = format!("![{alt}](/{})", links::url_path(&relative));
+    if append {
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(article)
+            .with_context(|| format!("Could not open {}", article.display()))?;
+        writeln!(file, "\n{reference}")
+            .with_context(|| format!("Could not write {}", article.display()))?;
+    }
+    println!("{reference}");
+
+    Ok(())
+}
@@ -0,0 +1,149 @@
+//! Output path of the articles.
+//!
+//! By default the articles are written at the path of their source, and the `permalink` pattern of
+//! the configuration (`/:year/:month/:slug/`) can be used to choose where they are written instead.
+//! With `pretty_urls`, the articles are written to the `index.html` of a directory, so that their
+//! URL does not end in `.html`.
+
+use std::path::{Component, Path, PathBuf};
+
+use color_eyre::{eyre, Result};
+
+use crate::{Config, Metadata};
+
+/// Path of the page of the article, relative to the output directory
+///
+/// `source` is the path of the article, relative to the input directory.
+pub fn article_path(config: &Config, source: &Path, metadata: &Metadata) -> Result<PathBuf> {
+    let slug = match &metadata.slug {
+        Some(slug) => slug.as_str(),
+        None => source
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| eyre::eyre!("Invalid file name {}", source.display()))?,
+    };
+
+    let path = match &config.permalink {
+        Some(pattern) => expand(config, pattern, slug, metadata)?,
+        None if config.pretty_urls => source.with_file_name(slug).join("index.html"),
+        None => source.with_file_name(format!("{slug}.html")),
+    };
+
+    if !path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        eyre::bail!(
+            "The permalink of {} is not a path inside the website: {}",
+            source.display(),
+            path.display()
+        );
+    }
+
+    Ok(path)
+}
+
+/// Path of the article given by the permalink `pattern`
+fn expand(config: &Config, pattern: &str, slug: &str, metadata: &Metadata) -> Result<PathBuf> {
+    let date = metadata.date(&config.date)?;
+    let mut expanded = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find(':') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        match &rest[..end] {
+            "year" => expanded.push_str(&date.format("%Y").to_string()),
+            "month" => expanded.push_str(&date.format("%m").to_string()),
+            "day" => expanded.push_str(&date.format("%d").to_string()),
+            "slug" => expanded.push_str(slug),
+            placeholder => eyre::bail!(
+                "Unknown placeholder `:{placeholder}` in the permalink `{pattern}`, the \
+                 placeholders are `:year`, `:month`, `:day` and `:slug`"
+            ),
+        }
+        rest = &rest[end..];
+    }
+    expanded.push_str(rest);
+
+    let mut path = PathBuf::from(expanded.trim_start_matches('/'));
+    if expanded.ends_with('/') || (config.pretty_urls && path.extension().is_none()) {
+        path.push("index.html");
+    } else if path.extension().is_none() {
+        path.set_extension("html");
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(extra: &str) -> Config {
+        toml::from_str(&format!(
+            "name = \"Test\"\n{extra}\n[date]\ninput = \"%Y-%m-%d\"\noutput = \"%Y-%m-%d\"\n"
+        ))
+        .unwrap()
+    }
+
+    fn metadata(slug: Option<&str>) -> Metadata {
+        let mut metadata: Metadata = toml::from_str(
+            "title = \"Intro\"\ndate = \"2023-04-05\"\npage = \"article\"\nsummary = \"\"\n",
+        )
+        .unwrap();
+        metadata.slug = slug.map(str::to_owned);
+        metadata
+    }
+
+    fn path(config: &Config, slug: Option<&str>) -> Result<PathBuf> {
+        article_path(config, Path::new("posts/intro.md"), &metadata(slug))
+    }
+
+    #[test]
+    fn source_path() {
+        assert_eq!(
+            path(&config(""), None).unwrap(),
+            Path::new("posts/intro.html")
+        );
+        assert_eq!(
+            path(&config(""), Some("hello")).unwrap(),
+            Path::new("posts/hello.html")
+        );
+        assert_eq!(
+            path(&config("pretty_urls = true"), None).unwrap(),
+            Path::new("posts/intro/index.html")
+        );
+    }
+
+    #[test]
+    fn pattern() {
+        assert_eq!(
+            path(&config("permalink = \"/:year/:month/:slug/\""), None).unwrap(),
+            Path::new("2023/04/intro/index.html")
+        );
+        assert_eq!(
+            path(&config("permalink = \"/:year/:day-:slug\""), None).unwrap(),
+            Path::new("2023/05-intro.html")
+        );
+        assert_eq!(
+            path(
+                &config("permalink = \"/:slug\"\npretty_urls = true"),
+                Some("hello")
+            )
+            .unwrap(),
+            Path::new("hello/index.html")
+        );
+        assert!(path(&config("permalink = \"/:week/:slug\""), None).is_err());
+    }
+
+    #[test]
+    fn outside_of_the_website() {
+        for extra in ["", "pretty_urls = true", "permalink = \"/:slug/\""] {
+            assert!(path(&config(extra), Some("../../x")).is_err(), "{extra}");
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Flamegraph of a build, recorded from the `tracing` spans of the phases and articles.
+//!
+//! Each span is a frame of the flamegraph, labelled with its name and the values of its fields
+//! (e.g. `article (posts/foo.html)`), so that the time spent in each article can be compared.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write as _},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use color_eyre::{eyre::Context, Result};
+use tracing::{
+    field::{Field, Visit},
+    span, Subscriber,
+};
+use tracing_subscriber::{layer::Context as LayerContext, prelude::*, registry::LookupSpan, Layer};
+
+/// Name of the flamegraph in the cache directory
+const FLAMEGRAPH: &str = "profile.svg";
+
+/// Timing of a span, stored in its extensions
+struct Timing {
+    label: String,
+    /// Time spent inside the span
+    busy: Duration,
+    /// Time spent inside the children of the span
+    children: Duration,
+    entered: Option<Instant>,
+}
+
+/// Collect the field values of a span in its label
+struct LabelVisitor<'l> {
+    values: &'l mut Vec<String>,
+}
+
+impl Visit for LabelVisitor<'_> {
+    fn record_debug(&mut self, _field: &Field, value: &dyn fmt::Debug) {
+        self.values.push(format!("{value:?}"));
+    }
+
+    fn record_str(&mut self, _field: &Field, value: &str) {
+        self.values.push(value.to_owned());
+    }
+}
+
+/// Time spent in each stack of spans, in microseconds
+type Samples = Arc<Mutex<BTreeMap<String, u64>>>;
+
+struct FoldedLayer {
+    samples: Samples,
+}
+
+impl<S> Layer<S> for FoldedLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut values = Vec::new();
+        attrs.record(&mut LabelVisitor {
+            values: &mut values,
+        });
+
+        let mut label = span.name().to_owned();
+        if !values.is_empty() {
+            let _ = write!(label, " ({})", values.join(", "));
+        }
+        // `;` separates the frames in the folded format
+        let label = label.replace(';', ",");
+
+        span.extensions_mut().insert(Timing {
+            label,
+            busy: Duration::ZERO,
+            children: Duration::ZERO,
+            entered: None,
+        });
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(timing) = extensions.get_mut::<Timing>() {
+            timing.entered = Some(Instant::now());
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(timing) = extensions.get_mut::<Timing>() {
+            if let Some(entered) = timing.entered.take() {
+                timing.busy += entered.elapsed();
+            }
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+
+        let (busy, children) = match span.extensions().get::<Timing>() {
+            Some(timing) => (timing.busy, timing.children),
+            None => return,
+        };
+
+        let stack = span
+            .scope()
+            .from_root()
+            .filter_map(|span| {
+                span.extensions()
+                    .get::<Timing>()
+                    .map(|timing| timing.label.clone())
+            })
+            .collect::<Vec<_>>()
+            .join(";");
+
+        // Children running in parallel can take more time than their parent
+        let own = busy.saturating_sub(children);
+        *self
+            .samples
+            .lock()
+            .expect("profile samples were poisoned")
+            .entry(stack)
+            .or_default() += own.as_micros() as u64;
+
+        if let Some(parent) = span.parent() {
+            let mut extensions = parent.extensions_mut();
+            if let Some(timing) = extensions.get_mut::<Timing>() {
+                timing.children += busy;
+            }
+        }
+    }
+}
+
+/// Records the spans of a build, to write them as a flamegraph
+pub struct Profile {
+    samples: Samples,
+}
+
+impl Profile {
+    /// Start recording the spans of the process
+    ///
+    /// This can only be called once, as it installs the global `tracing` subscriber.
+    pub fn start() -> Result<Self> {
+        let samples = Samples::default();
+
+        tracing::subscriber::set_global_default(tracing_subscriber::registry().with(FoldedLayer {
+            samples: samples.clone(),
+        }))
+        .context("Could not install the profiling subscriber")?;
+
+        Ok(Self { samples })
+    }
+
+    /// Write the flamegraph of the spans closed until now in `cache_dir`, returning its path
+    pub fn write(&self, cache_dir: &Path) -> Result<PathBuf> {
+        let samples = self.samples.lock().expect("profile samples were poisoned");
+        let lines = samples
+            .iter()
+            .filter(|(_, &micros)| micros > 0)
+            .map(|(stack, micros)| format!("{stack} {micros}"))
+            .collect::<Vec<_>>();
+
+        std::fs::create_dir_all(cache_dir).context("Could not create the cache directory")?;
+        let path = cache_dir.join(FLAMEGRAPH);
+        let output = BufWriter::new(
+            std::fs::File::create(&path).context("Could not create the flamegraph")?,
+        );
+
+        let mut options = inferno::flamegraph::Options {
+            title: "verin build".to_owned(),
+            count_name: "μs".to_owned(),
+            ..Default::default()
+        };
+        inferno::flamegraph::from_lines(&mut options, lines.iter().map(String::as_str), output)
+            .context("Could not write the flamegraph")?;
+
+        Ok(path)
+    }
+}
@@ -2,11 +2,63 @@ use std::{
     io::{Read, Write},
     net::{TcpListener, TcpStream},
     sync::{Arc, Mutex},
-    thread::spawn,
+    thread::{spawn, JoinHandle},
+    time::Duration,
 };
 
-pub fn refresh_server(refresh_port: u16, request_port: u16) -> color_eyre::Result<()> {
+use crate::{secrets, shutdown};
+
+/// Time given to the pages to acknowledge the closing of their websocket
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy)]
+enum Signal {
+    Refresh,
+    /// The server is shutting down
+    Close,
+}
+
+/// Script refreshing the page when the refresh server on `port` sends a refresh request
+pub fn script(port: u16) -> String {
+    format!(
+        r#"
+        <script>
+            let ws = new WebSocket("ws://localhost:{port}");
+            ws.onopen = function(_) {{
+                console.log("WS started");
+            }};
+
+            ws.onmessage = function(_) {{
+                console.log("REFRESH");
+                document.location.reload()
+            }};
+
+            ws.onerror = function(error) {{
+                console.log(`[error] WS error: ${{error.message}}`);
+            }};
+        </script>
+        "#
+    )
+}
+
+/// Start the refresh server, calling `before_refresh` on each refresh request.
+///
+/// The pages are not refreshed if `before_refresh` fails.
+pub fn refresh_server(
+    refresh_port: u16,
+    request_port: u16,
+    mut before_refresh: impl FnMut() -> color_eyre::Result<()>,
+) -> color_eyre::Result<()> {
     let bus = Arc::new(Mutex::new(bus::Bus::new(128)));
+    let websockets: Arc<Mutex<Vec<JoinHandle<()>>>> = Default::default();
+
+    let (b, w) = (bus.clone(), websockets.clone());
+    shutdown::on_shutdown(move || {
+        b.lock().unwrap().broadcast(Signal::Close);
+        for websocket in w.lock().unwrap().drain(..) {
+            let _ = websocket.join();
+        }
+    });
 
     let refresh_listener = TcpListener::bind(("0.0.0.0", refresh_port))?;
     let b = bus.clone();
@@ -20,8 +72,13 @@ pub fn refresh_server(refresh_port: u16, request_port: u16) -> color_eyre::Resul
                     match tungstenite::accept(s) {
                         Err(e) => eprintln!("Error in websocket accept: {e:?}"),
                         Ok(mut ws) => {
-                            spawn(move || {
-                                while r.recv().is_ok() {
+                            let handle = spawn(move || {
+                                while let Ok(signal) = r.recv() {
+                                    if let Signal::Close = signal {
+                                        close(&mut ws);
+                                        return;
+                                    }
+
                                     println!("Request taken into account");
                                     if let Err(e) = ws.send(tungstenite::Message::text("xxx")) {
                                         println!("WS error: {e:?}");
@@ -30,6 +87,10 @@ pub fn refresh_server(refresh_port: u16, request_port: u16) -> color_eyre::Resul
                                 }
                                 println!("Finished websocket")
                             });
+
+                            let mut websockets = websockets.lock().unwrap();
+                            websockets.retain(|websocket| !websocket.is_finished());
+                            websockets.push(handle);
                         }
                     }
                 }
@@ -49,13 +110,36 @@ pub fn refresh_server(refresh_port: u16, request_port: u16) -> color_eyre::Resul
             eprintln!("Invalid request on request_port");
         }
 
-        bus.lock().unwrap().broadcast(());
+        if let Err(e) = before_refresh() {
+            eprintln!(
+                "Error before refresh: {}",
+                secrets::redact(&format!("{e:?}"))
+            );
+            continue;
+        }
+
+        bus.lock().unwrap().broadcast(Signal::Refresh);
         println!("Refresh Requested");
     }
 
     Ok(())
 }
 
+/// Close a websocket with a close frame, waiting for the page to acknowledge it
+fn close(ws: &mut tungstenite::WebSocket<TcpStream>) {
+    if let Err(e) = ws.get_mut().set_read_timeout(Some(CLOSE_TIMEOUT)) {
+        eprintln!("Could not set the websocket timeout: {e:?}");
+    }
+
+    if let Err(e) = ws.close(None) {
+        eprintln!("Could not close websocket: {e:?}");
+        return;
+    }
+
+    // The close frame of the page ends the connection
+    while ws.read().is_ok() {}
+}
+
 pub fn trigger_refresh(request_port: u16) -> color_eyre::Result<()> {
     let mut stream = TcpStream::connect(("0.0.0.0", request_port))?;
     stream.write_all(b"xxx")?;
@@ -0,0 +1,103 @@
+use std::{fs::OpenOptions, io::BufWriter, path::Path};
+
+use chrono::NaiveDate;
+use color_eyre::{eyre::Context, Result};
+use serde::Serialize;
+
+#[derive(Serialize, Debug)]
+struct ScheduledPost<'a> {
+    title: &'a str,
+    page: String,
+    date: NaiveDate,
+}
+
+#[derive(Serialize, Debug)]
+struct Schedule<'a> {
+    /// Earliest time at which a rebuild would publish a new post
+    next_publish: Option<String>,
+    posts: Vec<ScheduledPost<'a>>,
+}
+
+/// Schedule of the posts dated after `today`, given by their title, output path and date
+fn schedule<'a>(
+    today: NaiveDate,
+    posts: impl IntoIterator<Item = (&'a str, &'a Path, NaiveDate)>,
+) -> Schedule<'a> {
+    let mut posts: Vec<_> = posts
+        .into_iter()
+        .filter(|&(_, _, date)| date > today)
+        .map(|(title, page, date)| ScheduledPost {
+            title,
+            page: crate::links::url_path(page),
+            date,
+        })
+        .collect();
+    posts.sort_unstable_by_key(|post| post.date);
+
+    Schedule {
+        next_publish: posts.first().map(|post| {
+            post.date
+                .and_time(Default::default())
+                .and_utc()
+                .to_rfc3339()
+        }),
+        posts,
+    }
+}
+
+/// Write `publish-schedule.json`, listing all the posts dated after `today`.
+///
+/// `posts` contains the title, output path and date of each article.
+pub fn write<'a>(
+    output: &Path,
+    today: NaiveDate,
+    posts: impl IntoIterator<Item = (&'a str, &'a Path, NaiveDate)>,
+) -> Result<()> {
+    let file = BufWriter::new(
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(output.join("publish-schedule.json"))
+            .context("Could not open publish schedule")?,
+    );
+    serde_json::to_writer_pretty(file, &schedule(today, posts))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn future_posts() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+        let posts = [
+            ("Later", Path::new("posts/later.html"), date(20)),
+            ("Published", Path::new("posts/published.html"), date(1)),
+            ("Today", Path::new("posts/today.html"), date(10)),
+            ("Next", Path::new("posts/next.html"), date(11)),
+        ];
+
+        let planned = schedule(date(10), posts);
+        assert_eq!(
+            planned.next_publish.as_deref(),
+            Some("2024-03-11T00:00:00+00:00")
+        );
+        let scheduled: Vec<_> = planned
+            .posts
+            .iter()
+            .map(|post| (post.title, post.page.as_str(), post.date))
+            .collect();
+        assert_eq!(
+            scheduled,
+            [
+                ("Next", "posts/next.html", date(11)),
+                ("Later", "posts/later.html", date(20)),
+            ]
+        );
+
+        assert!(schedule(date(20), posts).next_publish.is_none());
+    }
+}
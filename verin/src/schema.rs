@@ -0,0 +1,172 @@
+use std::{collections::BTreeMap, path::Path};
+
+use color_eyre::Result;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::{layout, Config};
+
+/// Custom front matter field declared in the configuration
+#[derive(Deserialize, Debug)]
+pub struct FieldConfig {
+    /// JSON schema type of the field (`string`, `boolean`, `integer`, `number`, `array`, ...)
+    #[serde(rename = "type")]
+    pub ty: String,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct FrontMatterConfig {
+    #[serde(default)]
+    pub fields: BTreeMap<String, FieldConfig>,
+}
+
+/// Generate the JSON schema of the front matter of the articles of the site in `input`
+pub fn generate(input: &Path, config: &Config) -> Result<Value> {
+    let mut templates = Vec::new();
+    for entry in layout::page_templates(input, config.template_engine)? {
+        if let Some(stem) = entry.file_stem().and_then(|s| s.to_str()) {
+            templates.push(stem.to_owned());
+        }
+    }
+    templates.sort();
+
+    let mut properties = Map::new();
+    properties.insert(
+        "title".into(),
+        json!({ "type": "string", "description": "Title of the article" }),
+    );
+    properties.insert(
+        "date".into(),
+        json!({
+            "type": "string",
+            "description": format!("Date of the article, in the `{}` format", config.date.input),
+        }),
+    );
+    properties.insert(
+        "page".into(),
+        json!({
+            "type": "string",
+            "description": "Template used to render the article",
+            "enum": templates,
+        }),
+    );
+    properties.insert(
+        "summary".into(),
+        json!({ "type": "string", "description": "Summary of the article" }),
+    );
+    properties.insert(
+        "max_depth".into(),
+        json!({
+            "type": "integer",
+            "description": "Maximum depth of the headings in the table of contents",
+            "default": 7,
+            "minimum": 0,
+        }),
+    );
+    properties.insert(
+        "tags".into(),
+        json!({
+            "type": "array",
+            "description": "Tags of the article",
+            "items": { "type": "string" },
+            "uniqueItems": true,
+        }),
+    );
+
+    properties.insert(
+        "draft".into(),
+        json!({
+            "type": "boolean",
+            "description": "Drafts are only built with `--drafts`, and are never listed",
+            "default": false,
+        }),
+    );
+
+    properties.insert(
+        "updated".into(),
+        json!({
+            "type": "string",
+            "description": format!(
+                "Date of the last significant update of the article, in the `{}` format",
+                config.date.input
+            ),
+        }),
+    );
+
+    properties.insert(
+        "aliases".into(),
+        json!({
+            "type": "array",
+            "description": "Former URLs of the article, redirecting to its current URL",
+            "items": { "type": "string" },
+            "uniqueItems": true,
+        }),
+    );
+
+    properties.insert(
+        "heading_anchors".into(),
+        json!({
+            "type": "boolean",
+            "description": "Add a link to itself in each heading",
+            "default": false,
+        }),
+    );
+
+    properties.insert(
+        "slug".into(),
+        json!({
+            "type": "string",
+            "description": "Name of the article in its URL, defaults to the name of its file",
+        }),
+    );
+
+    properties.insert(
+        "canonical_url".into(),
+        json!({
+            "type": "string",
+            "format": "uri",
+            "description": "URL of the original version of the article, when it was first published elsewhere",
+        }),
+    );
+
+    properties.insert(
+        "crosspost".into(),
+        json!({
+            "type": "array",
+            "description": "URLs of the copies of the article published on other websites",
+            "items": { "type": "string", "format": "uri" },
+            "uniqueItems": true,
+        }),
+    );
+
+    let mut required = vec![
+        "title".to_owned(),
+        "date".into(),
+        "page".into(),
+        "summary".into(),
+    ];
+
+    for (name, field) in &config.front_matter.fields {
+        let mut property = json!({ "type": field.ty });
+        if let Some(description) = &field.description {
+            property["description"] = description.as_str().into();
+        }
+        properties.insert(name.clone(), property);
+
+        if field.required {
+            required.push(name.clone());
+        }
+    }
+
+    Ok(json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": format!("Front matter of {}", config.name),
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    }))
+}
@@ -0,0 +1,118 @@
+//! Index of the articles for client-side search, written in `search_index.json`.
+
+use std::{fs::OpenOptions, io::BufWriter, path::Path};
+
+use color_eyre::{eyre::Context, Result};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{html, url::SiteUrl, Metadata};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    Title,
+    Url,
+    Summary,
+    Tags,
+    Headings,
+    Body,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchConfig {
+    /// Fields of the articles written in the index
+    #[serde(default = "default_fields")]
+    pub fields: Vec<Field>,
+}
+
+fn default_fields() -> Vec<Field> {
+    vec![
+        Field::Title,
+        Field::Url,
+        Field::Summary,
+        Field::Headings,
+        Field::Body,
+    ]
+}
+
+/// Text of a markdown document parsed with the `extensions`, without the code blocks and the math
+fn plain_text(body: &str, extensions: Options) -> String {
+    let mut text = Vec::new();
+    let mut in_code_block = false;
+
+    for event in Parser::new_ext(body, extensions) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Text(content) | Event::Code(content) if !in_code_block => {
+                text.extend(content.split_whitespace().map(ToOwned::to_owned))
+            }
+            _ => (),
+        }
+    }
+
+    text.join(" ")
+}
+
+/// Entry of an article in the index, with the fields of the configuration
+pub fn entry(
+    config: &SearchConfig,
+    metadata: &Metadata,
+    page: &Path,
+    rendered: &html::Rendered,
+    body: &str,
+    extensions: Options,
+) -> Value {
+    let mut entry = Map::new();
+    for field in &config.fields {
+        let (name, value) = match field {
+            Field::Title => ("title", metadata.title.clone().into()),
+            Field::Url => ("url", SiteUrl::from_page(page).to_string().into()),
+            Field::Summary => ("summary", metadata.summary.trim().into()),
+            Field::Tags => ("tags", metadata.tags.clone().into()),
+            Field::Headings => (
+                "headings",
+                rendered
+                    .headers
+                    .iter()
+                    .map(|heading| heading.text_plain.clone())
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+            Field::Body => ("body", plain_text(body, extensions).into()),
+        };
+        entry.insert(name.to_owned(), value);
+    }
+
+    Value::Object(entry)
+}
+
+/// Write `search_index.json` with the `entries` of the articles
+pub fn write(output: &Path, entries: &[Value]) -> Result<()> {
+    let file = BufWriter::new(
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(output.join("search_index.json"))
+            .context("Could not open search_index.json")?,
+    );
+
+    serde_json::to_writer(file, entries).context("Could not write search_index.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_blocks_and_math_are_skipped() {
+        let body = "Some `code` and $x^2$ math.\n\n```rust\nfn main() {}\n```\n\n- A  list\n";
+        assert_eq!(
+            plain_text(body, Options::ENABLE_MATH),
+            "Some code and math. A list"
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Secrets of the integrations (API tokens, webmention endpoints, deploy credentials, ...), read
+//! from the environment only.
+//!
+//! The configuration names the environment variable holding each secret, never its value. Secrets
+//! can't be serialized, so they can't end up in the templates, the manifest or the output, and
+//! their values are redacted from the errors printed by Verin.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    sync::{Mutex, PoisonError},
+};
+
+use color_eyre::{eyre, Result};
+
+/// Environment variable holding each secret, by the name of the secret
+pub type SecretsConfig = BTreeMap<String, String>;
+
+/// Text replacing the values of the secrets
+const REDACTED: &str = "[REDACTED]";
+
+/// Values of the secrets loaded by the process, redacted by [`redact`]
+///
+/// A set, as each build of `serve` loads the secrets again.
+static LOADED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Value of a secret, that is never printed
+pub struct Secret(String);
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({REDACTED})")
+    }
+}
+
+impl Secret {
+    /// Value of the secret, to be given to the integration using it only
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+pub struct Secrets<'c> {
+    config: &'c SecretsConfig,
+    /// Secrets whose variable is set, by name
+    values: BTreeMap<&'c str, Secret>,
+}
+
+impl<'c> Secrets<'c> {
+    /// Read the secrets of the `config` from the environment, the missing ones being an error
+    /// only when they are used
+    pub fn load(config: &'c SecretsConfig) -> Self {
+        let values: BTreeMap<_, _> = config
+            .iter()
+            .filter_map(|(name, variable)| {
+                let value = std::env::var(variable)
+                    .ok()
+                    .filter(|value| !value.is_empty())?;
+                Some((name.as_str(), Secret(value)))
+            })
+            .collect();
+
+        LOADED
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(values.values().map(|secret| secret.expose().to_owned()));
+
+        Self { config, values }
+    }
+
+    /// The secret `name` of the configuration
+    pub fn get(&self, name: &str) -> Result<&Secret> {
+        if let Some(secret) = self.values.get(name) {
+            return Ok(secret);
+        }
+        match self.config.get(name) {
+            Some(variable) => eyre::bail!("The secret `{name}` is not set, export `{variable}`"),
+            None => eyre::bail!("The secret `{name}` is not in the `secrets` of the configuration"),
+        }
+    }
+}
+
+/// Replace the values of the loaded secrets in `text`
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let loaded = LOADED.lock().unwrap_or_else(PoisonError::into_inner);
+
+    let mut text = Cow::Borrowed(text);
+    for value in loaded.iter() {
+        if text.contains(value.as_str()) {
+            text = Cow::Owned(text.replace(value.as_str(), REDACTED));
+        }
+    }
+    text
+}
+
+/// `println!` with the values of the loaded secrets redacted, for the warnings that may quote them
+macro_rules! println_redacted {
+    ($($arg:tt)*) => {
+        println!("{}", $crate::secrets::redact(&format!($($arg)*)))
+    };
+}
+pub(crate) use println_redacted;
+
+/// Print whether each secret of the `config` is set, without their values
+pub fn check(config: &SecretsConfig) -> Result<()> {
+    let secrets = Secrets::load(config);
+
+    let mut missing = 0;
+    for (name, variable) in config {
+        match secrets.get(name) {
+            Ok(_) => println!("{name} ({variable}): set"),
+            Err(_) => {
+                missing += 1;
+                println!("{name} ({variable}): missing");
+            }
+        }
+    }
+
+    if missing > 0 {
+        eyre::bail!("{missing} secret(s) are not set");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loaded_secrets() {
+        std::env::set_var("VERIN_TEST_TOKEN", "s3cr3t-t0k3n");
+        let config = SecretsConfig::from([
+            ("token".to_owned(), "VERIN_TEST_TOKEN".to_owned()),
+            ("unset".to_owned(), "VERIN_TEST_UNSET".to_owned()),
+        ]);
+        let secrets = Secrets::load(&config);
+
+        let token = secrets.get("token").unwrap();
+        assert_eq!(token.expose(), "s3cr3t-t0k3n");
+        assert_eq!(format!("{token:?}"), "Secret([REDACTED])");
+        assert_eq!(
+            secrets.get("unset").unwrap_err().to_string(),
+            "The secret `unset` is not set, export `VERIN_TEST_UNSET`"
+        );
+        assert_eq!(
+            secrets.get("other").unwrap_err().to_string(),
+            "The secret `other` is not in the `secrets` of the configuration"
+        );
+
+        assert_eq!(
+            redact("Invalid token s3cr3t-t0k3n"),
+            "Invalid token [REDACTED]"
+        );
+        assert!(matches!(redact("No secret here"), Cow::Borrowed(_)));
+    }
+}
@@ -0,0 +1,332 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    thread::{sleep, spawn},
+    time::{Duration, SystemTime},
+};
+
+use color_eyre::{eyre::Context, Result};
+use itertools::Itertools;
+
+use crate::{build, refresh, shutdown, svg, BuildArgs, Config, CACHE_DIR};
+
+/// Interval between two checks of the configuration files of the website
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Build the website and serve it on `port`, rebuilding it on each refresh request, and when its
+/// configuration or the SVGs inlined by its articles change.
+///
+/// The website is built without the refresh script, that is injected in the HTML responses
+/// instead, so that the pages are the same as in a release build.
+pub fn serve(args: BuildArgs, port: u16, request_port: u16) -> Result<()> {
+    let root = args.output.clone();
+    shutdown::on_shutdown(move || {
+        if let Err(e) = std::fs::remove_dir_all(&root) {
+            eprintln!("Could not remove {}: {e}", root.display());
+        }
+    });
+
+    build(args.clone())?;
+
+    let root = args.output.clone();
+    let script: Arc<str> = refresh::script(args.refresh_port).into();
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .with_context(|| format!("Could not listen on port {port}"))?;
+    println!("Serving {} on http://localhost:{port}", root.display());
+
+    spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Err(e) => eprintln!("Error in HTTP server: {e:?}"),
+                Ok(stream) => {
+                    let (root, script) = (root.clone(), script.clone());
+                    spawn(move || {
+                        if let Err(e) = handle(&root, &script, stream) {
+                            eprintln!("Error while serving request: {e:?}");
+                        }
+                    });
+                }
+            }
+        }
+    });
+
+    watch(args.input.clone(), request_port);
+
+    refresh::refresh_server(args.refresh_port, request_port, || {
+        println!("Rebuilding");
+        build(args.clone())
+    })
+}
+
+/// Files configuring the website in `input`: its `config.toml` and the theme files it uses
+fn config_files(input: &Path) -> Vec<PathBuf> {
+    let mut files = vec![input.join("config.toml")];
+    // An invalid configuration is reported by the rebuild
+    if let Ok(config) = Config::load(input) {
+        files.extend(config.highlight.theme_files(input));
+    }
+    files
+}
+
+/// Last modification time of each of the `files`, `None` if it does not exist
+fn modified<'f>(files: impl IntoIterator<Item = &'f PathBuf>) -> Vec<Option<SystemTime>> {
+    files
+        .into_iter()
+        .map(|file| {
+            std::fs::metadata(file)
+                .and_then(|meta| meta.modified())
+                .ok()
+        })
+        .collect()
+}
+
+/// Request a refresh on `request_port` whenever the configuration of the website in `input`, or
+/// an SVG inlined by its articles changes, so that the website is rebuilt
+fn watch(input: PathBuf, request_port: u16) {
+    spawn(move || {
+        let cache = input.join(CACHE_DIR);
+        // Rewritten by each build, the inlined SVGs are watched again when it changes
+        let recorded = [svg::dependencies_path(&cache)];
+        let mut last_recorded = modified(&recorded);
+
+        let mut files = config_files(&input);
+        let mut svgs = svg::dependencies(&cache);
+        let mut last = (modified(&files), modified(svgs.keys()));
+        loop {
+            sleep(WATCH_INTERVAL);
+            if modified(&recorded) != last_recorded {
+                last_recorded = modified(&recorded);
+                svgs = svg::dependencies(&cache);
+                last.1 = modified(svgs.keys());
+            }
+
+            let current = (modified(&files), modified(svgs.keys()));
+            if current == last {
+                continue;
+            }
+
+            if current.0 != last.0 {
+                println!("Configuration changed");
+            }
+            let changed = svgs
+                .iter()
+                .zip(current.1.iter().zip(&last.1))
+                .filter(|(_, (now, before))| now != before);
+            for ((file, articles), _) in changed {
+                println!(
+                    "{} changed, used by {}",
+                    file.display(),
+                    articles.iter().map(|article| article.display()).join(", ")
+                );
+            }
+            if let Err(e) = refresh::trigger_refresh(request_port) {
+                eprintln!("Could not request a rebuild: {e:?}");
+            }
+            // The theme files may have changed with the configuration
+            files = config_files(&input);
+            last = (modified(&files), current.1);
+        }
+    });
+}
+
+fn handle(root: &Path, script: &str, mut stream: TcpStream) -> Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim_end().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return respond(
+            &mut stream,
+            "400 Bad Request",
+            "text/plain",
+            b"Bad Request",
+            true,
+        );
+    };
+    let head = method == "HEAD";
+    if method != "GET" && !head {
+        return respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"Method Not Allowed",
+            true,
+        );
+    }
+
+    let Some(path) = resolve(root, target) else {
+        return respond(
+            &mut stream,
+            "403 Forbidden",
+            "text/plain",
+            b"Forbidden",
+            true,
+        );
+    };
+
+    match std::fs::read(&path) {
+        Ok(mut body) => {
+            if path.extension().and_then(|e| e.to_str()) == Some("html") {
+                inject_script(&mut body, script);
+            }
+            respond(&mut stream, "200 OK", content_type(&path), &body, !head)
+        }
+        Err(_) => match std::fs::read(root.join("404.html")) {
+            Ok(mut body) => {
+                inject_script(&mut body, script);
+                respond(&mut stream, "404 Not Found", "text/html", &body, !head)
+            }
+            Err(_) => respond(
+                &mut stream,
+                "404 Not Found",
+                "text/plain",
+                b"Not Found",
+                !head,
+            ),
+        },
+    }
+}
+
+/// Insert `script` at the end of the `<body>` of a page, or at the end of the page if it has none
+fn inject_script(page: &mut Vec<u8>, script: &str) {
+    let end = page
+        .windows(b"</body>".len())
+        .rposition(|tag| tag.eq_ignore_ascii_case(b"</body>"))
+        .unwrap_or(page.len());
+    page.splice(end..end, script.bytes());
+}
+
+/// Find the file designated by the `target` of a request, refusing to leave `root`
+fn resolve(root: &Path, target: &str) -> Option<PathBuf> {
+    let target = target.split(['?', '#']).next().unwrap_or_default();
+    let target = percent_decode(target.trim_start_matches('/'))?;
+
+    let relative = Path::new(&target);
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    let mut path = root.join(relative);
+    if path.is_dir() {
+        path.push("index.html");
+    } else if !path.exists() && path.extension().is_none() {
+        path.set_extension("html");
+    }
+
+    Some(path)
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            decoded.push(b);
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("wasm") => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+    with_body: bool,
+) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    if with_body {
+        stream.write_all(body)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets() {
+        let root = Path::new("/nonexistent/public");
+        assert_eq!(
+            resolve(root, "/assets/a%20b.css?v=1"),
+            Some(root.join("assets/a b.css"))
+        );
+        assert_eq!(
+            resolve(root, "/posts/intro#start"),
+            Some(root.join("posts/intro.html"))
+        );
+        assert_eq!(resolve(root, "/../config.toml"), None);
+        assert_eq!(resolve(root, "/%2e%2e/config.toml"), None);
+        assert_eq!(resolve(root, "/a%zz"), None);
+    }
+
+    #[test]
+    fn refresh_script() {
+        let inject = |page: &str| {
+            let mut page = page.as_bytes().to_vec();
+            inject_script(&mut page, "<script></script>");
+            String::from_utf8(page).unwrap()
+        };
+        assert_eq!(
+            inject("<body><p>a</p></BODY></html>"),
+            "<body><p>a</p><script></script></BODY></html>"
+        );
+        assert_eq!(
+            inject("<p></body></p></body>"),
+            "<p></body></p><script></script></body>"
+        );
+        assert_eq!(inject("<p>a</p>"), "<p>a</p><script></script>");
+    }
+
+    #[test]
+    fn percent_encoding() {
+        assert_eq!(percent_decode("caf%C3%A9%3F").as_deref(), Some("café?"));
+        assert_eq!(percent_decode("100%"), None);
+        assert_eq!(percent_decode("%FF"), None);
+    }
+}
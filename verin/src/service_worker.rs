@@ -0,0 +1,128 @@
+use std::path::Path;
+
+use color_eyre::{eyre::Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{assets::Manifest, url::SiteUrl};
+
+#[derive(Deserialize, Debug)]
+pub struct ServiceWorkerConfig {
+    /// Number of recent articles to precache
+    #[serde(default = "default_recent")]
+    pub recent: usize,
+    /// Assets (by logical name) to precache. All assets are precached if not specified.
+    #[serde(default)]
+    pub assets: Option<Vec<String>>,
+}
+
+fn default_recent() -> usize {
+    5
+}
+
+/// Snippet registering the service worker, to be inserted in the pages
+pub fn registration(config: Option<&ServiceWorkerConfig>) -> String {
+    match config {
+        None => "".into(),
+        Some(_) => format!(
+            r#"
+        <script>
+            if ("serviceWorker" in navigator) {{
+                navigator.serviceWorker.register("{}");
+            }}
+        </script>
+        "#,
+            SiteUrl::parse("sw.js")
+        ),
+    }
+}
+
+/// Write `sw.js` in the output directory.
+///
+/// `pages` are the URLs of the pages to precache, relative to the output directory.
+pub fn generate(
+    output: &Path,
+    config: &ServiceWorkerConfig,
+    pages: &[String],
+    assets: &Manifest,
+) -> Result<()> {
+    let mut hasher = Sha256::new();
+    let mut precache = vec![SiteUrl::root().to_string()];
+
+    for page in pages {
+        let content = std::fs::read(output.join(page))
+            .with_context(|| format!("Could not read page {page} to precache"))?;
+        hasher.update(page.as_bytes());
+        hasher.update(&content);
+        precache.push(SiteUrl::parse(page).to_string());
+    }
+
+    for (name, asset) in assets {
+        if let Some(selected) = &config.assets {
+            if !selected.contains(name) {
+                continue;
+            }
+        }
+
+        hasher.update(asset.hash.as_bytes());
+        precache.push(asset.url.clone());
+    }
+
+    let version = &format!("{:x}", hasher.finalize())[..16];
+    let precache = serde_json::to_string_pretty(&precache)?;
+
+    std::fs::write(
+        output.join("sw.js"),
+        format!(
+            r#"const CACHE = "verin-{version}";
+const PRECACHE = {precache};
+
+self.addEventListener("install", (event) => {{
+    event.waitUntil(
+        caches
+            .open(CACHE)
+            .then((cache) => cache.addAll(PRECACHE))
+            .then(() => self.skipWaiting()),
+    );
+}});
+
+self.addEventListener("activate", (event) => {{
+    event.waitUntil(
+        caches
+            .keys()
+            .then((keys) =>
+                Promise.all(keys.filter((key) => key !== CACHE).map((key) => caches.delete(key))),
+            )
+            .then(() => self.clients.claim()),
+    );
+}});
+
+self.addEventListener("fetch", (event) => {{
+    if (event.request.method !== "GET") {{
+        return;
+    }}
+
+    if (event.request.mode === "navigate") {{
+        // Pages are fetched from the network first to always display the latest content
+        event.respondWith(
+            fetch(event.request)
+                .then((response) => {{
+                    const copy = response.clone();
+                    caches.open(CACHE).then((cache) => cache.put(event.request, copy));
+                    return response;
+                }})
+                .catch(() => caches.match(event.request)),
+        );
+    }} else {{
+        event.respondWith(
+            caches.match(event.request).then((cached) => cached || fetch(event.request)),
+        );
+    }}
+}});
+"#
+        ),
+    )
+    .context("Could not write the service worker")?;
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+//! Shortcodes expanding to HTML in the articles (`{{ youtube id="dQw4w9WgXcQ" }}`).
+//!
+//! Each `shortcodes/<name>.liquid` template defines the `<name>` shortcode, rendered with the
+//! arguments of the shortcode as variables. The `svg` shortcode is builtin (see [`crate::svg`]).
+
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+use pulldown_cmark_escape::{escape_html, FmtWriter};
+use ts_highlight_html::SyntaxConfig;
+
+use crate::{filters, html::RenderOptions, svg, templates};
+
+/// Directory of the shortcode templates, relative to the input directory
+pub const SHORTCODES: &str = "shortcodes";
+
+pub struct Shortcodes {
+    svg: svg::InlineSvg,
+    templates: HashMap<String, liquid::Template>,
+}
+
+/// Parse the arguments of a shortcode (`id="dQw4w9WgXcQ" start=42`)
+fn parse_arguments(arguments: &str) -> Result<liquid::Object> {
+    let mut object = liquid::Object::new();
+
+    let mut rest = arguments.trim_start();
+    while !rest.is_empty() {
+        let (name, value) = rest
+            .split_once('=')
+            .ok_or_else(|| eyre::eyre!("Expected `name=value`, found `{rest}`"))?;
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            eyre::bail!("Invalid argument name `{name}`");
+        }
+
+        let value = value.trim_start();
+        let (value, next) = match value.strip_prefix('"') {
+            Some(quoted) => quoted
+                .split_once('"')
+                .ok_or_else(|| eyre::eyre!("Unterminated value of the argument `{name}`"))?,
+            None => value.split_once(char::is_whitespace).unwrap_or((value, "")),
+        };
+
+        object.insert(
+            name.to_owned().into(),
+            liquid::model::Value::scalar(value.to_owned()),
+        );
+        rest = next.trim_start();
+    }
+
+    Ok(object)
+}
+
+impl Shortcodes {
+    pub fn load(
+        input: &Path,
+        svg: svg::InlineSvg,
+        syntax_conf: Arc<SyntaxConfig<'static>>,
+        base_url: Option<String>,
+        options: RenderOptions,
+        diagnostics: filters::Diagnostics,
+    ) -> Result<Self> {
+        let parser = templates::liquid_parser(syntax_conf, base_url, options, diagnostics)?;
+
+        let mut shortcodes = HashMap::new();
+        let pattern = input.join(SHORTCODES).join("*.liquid");
+        for entry in glob::glob(&pattern.to_string_lossy())? {
+            let entry = entry?;
+            let source = std::fs::read_to_string(&entry)
+                .with_context(|| format!("Could not read the shortcode {}", entry.display()))?;
+            let template = parser
+                .parse(&source)
+                .with_context(|| format!("Could not parse the shortcode {}", entry.display()))?;
+            shortcodes.insert(templates::template_name(&entry)?, template);
+        }
+
+        Ok(Self {
+            svg,
+            templates: shortcodes,
+        })
+    }
+
+    /// SVGs inlined by the `svg` shortcode
+    pub fn svg(&self) -> &svg::InlineSvg {
+        &self.svg
+    }
+
+    /// HTML of the `shortcode` (`youtube id="dQw4w9WgXcQ"`) in the `article`, or `None` if there
+    /// is no shortcode with this name
+    fn render(&self, shortcode: &str, article: &Path) -> Result<Option<String>> {
+        let (name, arguments) = shortcode
+            .split_once(char::is_whitespace)
+            .unwrap_or((shortcode, ""));
+
+        if name == "svg" {
+            return self.svg.render(arguments, article).map(Some);
+        }
+
+        let Some(template) = self.templates.get(name) else {
+            return Ok(None);
+        };
+        let arguments = parse_arguments(arguments)
+            .with_context(|| format!("Invalid arguments of the shortcode `{name}`"))?;
+        let html = template
+            .render(&arguments)
+            .with_context(|| format!("Could not render the shortcode `{name}`"))?;
+        Ok(Some(html.trim_end().to_owned()))
+    }
+
+    /// Replace the shortcodes in the markdown `text` of the `article` (the path of its source) by
+    /// their HTML, returning the resulting HTML, or `None` if the text contains no shortcode
+    pub fn expand(&self, text: &str, article: &Path) -> Result<Option<String>> {
+        let mut html = String::new();
+        let mut found = false;
+
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            let Some(length) = rest[start..].find("}}") else {
+                break;
+            };
+            let end = start + length + 2;
+
+            // Unknown shortcodes are kept as text
+            match self.render(rest[start + 2..end - 2].trim(), article)? {
+                Some(shortcode) => {
+                    escape_html(FmtWriter(&mut html), &rest[..start])?;
+                    html.push_str(&shortcode);
+                    found = true;
+                }
+                None => escape_html(FmtWriter(&mut html), &rest[..end])?,
+            }
+            rest = &rest[end..];
+        }
+        escape_html(FmtWriter(&mut html), rest)?;
+
+        Ok(found.then_some(html))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments() {
+        let arguments = parse_arguments(r#"id="dQw4w9WgXcQ" start=42  title="A & B""#).unwrap();
+        let value = |value: &str| liquid::model::Value::scalar(value.to_owned());
+        assert_eq!(arguments.len(), 3);
+        assert_eq!(arguments.get("id"), Some(&value("dQw4w9WgXcQ")));
+        assert_eq!(arguments.get("start"), Some(&value("42")));
+        assert_eq!(arguments.get("title"), Some(&value("A & B")));
+
+        assert!(parse_arguments("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        for (arguments, error) in [
+            ("id", "Expected `name=value`, found `id`"),
+            ("=42", "Invalid argument name ``"),
+            ("my id=42", "Invalid argument name `my id`"),
+            (
+                "title=\"unterminated",
+                "Unterminated value of the argument `title`",
+            ),
+        ] {
+            assert_eq!(parse_arguments(arguments).unwrap_err().to_string(), error);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Graceful handling of Ctrl-C.
+//!
+//! The first Ctrl-C requests a shutdown: the running build stops at the next article or phase,
+//! releasing the lock of its output directory, then the cleanups registered by the servers are run
+//! before exiting. A second Ctrl-C exits immediately.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard, PoisonError,
+    },
+    thread::spawn,
+};
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+
+/// Exit status of a process interrupted by SIGINT
+const INTERRUPTED: i32 = 130;
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Held during builds, so that the shutdown waits for them to stop
+static BUILD: Mutex<()> = Mutex::new(());
+
+type Cleanup = Box<dyn FnOnce() + Send>;
+static CLEANUPS: Mutex<Vec<Cleanup>> = Mutex::new(Vec::new());
+
+/// Install the Ctrl-C handler
+pub fn install() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if REQUESTED.swap(true, Ordering::SeqCst) {
+            eprintln!("Exiting immediately");
+            std::process::exit(INTERRUPTED);
+        }
+
+        eprintln!("Shutting down, press Ctrl-C again to exit immediately");
+        // The handler must return to receive the second Ctrl-C
+        spawn(|| {
+            drop(BUILD.lock().unwrap_or_else(PoisonError::into_inner));
+
+            let cleanups =
+                std::mem::take(&mut *CLEANUPS.lock().unwrap_or_else(PoisonError::into_inner));
+            for cleanup in cleanups.into_iter().rev() {
+                cleanup();
+            }
+
+            std::process::exit(INTERRUPTED);
+        });
+    })
+    .context("Could not install the Ctrl-C handler")
+}
+
+/// Run `cleanup` when shutting down, after the running build has stopped.
+///
+/// Cleanups are run in the reverse order of their registration.
+pub fn on_shutdown(cleanup: impl FnOnce() + Send + 'static) {
+    CLEANUPS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(Box::new(cleanup));
+}
+
+/// Mark the start of a build, that lasts until the guard is dropped
+pub fn building() -> Result<MutexGuard<'static, ()>> {
+    check()?;
+    Ok(BUILD.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// Fail if a shutdown was requested, to abandon the running build
+pub fn check() -> Result<()> {
+    if REQUESTED.load(Ordering::SeqCst) {
+        eyre::bail!("Interrupted, the output was not changed");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,38 @@
+//! `sitemap.xml` of the website, listing the index and the articles with their last modification.
+
+use std::{fmt::Write, path::Path};
+
+use color_eyre::{eyre::Context, Result};
+use pulldown_cmark_escape::escape_html;
+
+use crate::{url::SiteUrl, Article, Config};
+
+/// Write `sitemap.xml` for the website published at `base_url`
+pub fn write(output: &Path, base_url: &str, config: &Config, articles: &[Article]) -> Result<()> {
+    let mut urls = Vec::with_capacity(articles.len() + 1);
+    for article in articles {
+        urls.push((
+            SiteUrl::from_page(&article.path),
+            article.metadata.updated(&config.date)?,
+        ));
+    }
+    if let Some(last) = urls.iter().map(|(_, updated)| *updated).max() {
+        urls.push((SiteUrl::root(), last));
+    }
+    urls.sort_unstable();
+
+    let mut sitemap = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#,
+        "\n",
+    ));
+    for (url, updated) in urls {
+        sitemap.push_str("\t<url><loc>");
+        escape_html(&mut sitemap, &url.with_base(base_url))?;
+        writeln!(sitemap, "</loc><lastmod>{updated}</lastmod></url>")?;
+    }
+    sitemap.push_str("</urlset>\n");
+
+    std::fs::write(output.join("sitemap.xml"), sitemap).context("Could not write sitemap.xml")
+}
@@ -0,0 +1,35 @@
+/// Create an URL friendly identifier from some text.
+///
+/// Letters and digits are kept (lowercased), including non-ASCII ones, and all other characters
+/// are collapsed into single dashes.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    if slug.ends_with('-') {
+        slug.pop();
+    }
+
+    slug
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slugify("Rust"), "rust");
+        assert_eq!(slugify("  C++ & Rust: FFI!  "), "c-rust-ffi");
+        assert_eq!(slugify("Écrire en français"), "écrire-en-français");
+        assert_eq!(slugify("web--dev_2024"), "web-dev-2024");
+        assert_eq!(slugify("???"), "");
+    }
+}
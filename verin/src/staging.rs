@@ -0,0 +1,69 @@
+//! Staging directory of a build, replacing the content of the output once the build succeeded.
+//!
+//! A build that fails or is interrupted leaves the output of the previous build untouched, instead
+//! of a mix of old and new pages.
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::{eyre::Context, Result};
+
+/// Directory in which a build writes its output, removed when dropped
+pub struct Staging {
+    path: PathBuf,
+}
+
+impl Staging {
+    /// Create an empty staging directory next to `output`, so that its files can be moved to it
+    pub fn new(output: &Path) -> Result<Self> {
+        let name = output.file_name().unwrap_or_default().to_string_lossy();
+        let path = output.with_file_name(format!(".{name}.staging"));
+
+        // Left by a build that was killed
+        if path.exists() {
+            std::fs::remove_dir_all(&path)
+                .with_context(|| format!("Could not remove {}", path.display()))?;
+        }
+        std::fs::create_dir_all(&path)
+            .with_context(|| format!("Could not create {}", path.display()))?;
+
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Replace the content of `output` by the staged files, except for its `kept` files
+    pub fn commit(self, output: &Path, kept: &[&str]) -> Result<()> {
+        for entry in std::fs::read_dir(output)? {
+            let entry = entry?;
+            if kept.iter().any(|kept| entry.file_name() == *kept) {
+                continue;
+            }
+
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                std::fs::remove_dir_all(&path)
+            } else {
+                std::fs::remove_file(&path)
+            }
+            .with_context(|| format!("Could not remove {}", path.display()))?;
+        }
+
+        for entry in std::fs::read_dir(&self.path)? {
+            let entry = entry?;
+            std::fs::rename(entry.path(), output.join(entry.file_name()))
+                .with_context(|| format!("Could not move {}", entry.path().display()))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            eprintln!("Could not remove {}: {e}", self.path.display());
+        }
+    }
+}
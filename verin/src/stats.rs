@@ -0,0 +1,146 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+};
+
+use chrono::Datelike;
+use color_eyre::Result;
+use serde::Serialize;
+
+use crate::{links, Article, DateConfig};
+
+/// Number of posts listed in `longest`
+const LONGEST_POSTS: usize = 10;
+
+#[derive(Serialize, Debug)]
+pub struct Count {
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PostWords<'a> {
+    pub title: &'a str,
+    pub page: String,
+    pub words: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Stats<'a> {
+    pub posts: usize,
+    pub words: usize,
+    /// Number of posts per year, most recent year first
+    pub years: Vec<Count>,
+    /// Number of posts per tag, most used first
+    pub tags: Vec<Count>,
+    pub longest: Vec<PostWords<'a>>,
+    /// Number of code blocks per language, most used first
+    pub languages: Vec<Count>,
+    /// Number of code blocks per language that was not highlighted, most used first
+    pub unhighlighted: Vec<Count>,
+    /// Number of uses of each markdown extension, most used first
+    pub features: Vec<Count>,
+}
+
+fn sorted_counts(counts: impl IntoIterator<Item = (String, usize)>) -> Vec<Count> {
+    let mut counts: Vec<_> = counts
+        .into_iter()
+        .map(|(name, count)| Count { name, count })
+        .collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    counts
+}
+
+pub fn collect<'a>(articles: &'a [Article], date: &DateConfig) -> Result<Stats<'a>> {
+    let mut years = BTreeMap::new();
+    let mut tags = HashMap::new();
+    let mut languages = HashMap::new();
+    let mut unhighlighted = HashMap::new();
+    let mut features = HashMap::new();
+
+    for article in articles {
+        let year = article.metadata.date(date)?.year();
+        *years.entry(year).or_insert(0) += 1;
+
+        for tag in &article.metadata.tags {
+            *tags.entry(tag.clone()).or_insert(0) += 1;
+        }
+
+        for language in &article.rendered.languages {
+            *languages.entry(language.clone()).or_insert(0) += 1;
+        }
+
+        for language in &article.rendered.unhighlighted {
+            *unhighlighted.entry(language.clone()).or_insert(0) += 1;
+        }
+
+        for (&feature, &count) in &article.rendered.features {
+            *features.entry(feature.to_owned()).or_insert(0) += count;
+        }
+    }
+
+    let mut longest: Vec<_> = articles
+        .iter()
+        .map(|article| PostWords {
+            title: &article.metadata.title,
+            page: links::url_path(&article.path),
+            words: article.rendered.words,
+        })
+        .collect();
+    longest.sort_by_key(|article| Reverse(article.words));
+    longest.truncate(LONGEST_POSTS);
+
+    Ok(Stats {
+        posts: articles.len(),
+        words: articles.iter().map(|article| article.rendered.words).sum(),
+        years: years
+            .into_iter()
+            .rev()
+            .map(|(year, count)| Count {
+                name: year.to_string(),
+                count,
+            })
+            .collect(),
+        tags: sorted_counts(tags),
+        longest,
+        languages: sorted_counts(languages),
+        unhighlighted: sorted_counts(unhighlighted),
+        features: sorted_counts(features),
+    })
+}
+
+/// Print the code blocks per language, the ones that were not highlighted, and the uses of the
+/// markdown extensions
+pub fn report(stats: &Stats) {
+    let sections = [
+        ("Code blocks per language", &stats.languages),
+        ("Code blocks not highlighted", &stats.unhighlighted),
+        ("Markdown extensions", &stats.features),
+    ];
+
+    for (title, counts) in sections {
+        println!("{title}:");
+        if counts.is_empty() {
+            println!("  (none)");
+        }
+        for Count { name, count } in counts {
+            println!("  {name}: {count}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn most_used_first() {
+        let counts = sorted_counts([
+            ("rust".to_owned(), 2),
+            ("c".to_owned(), 5),
+            ("python".to_owned(), 2),
+        ]);
+        let counts: Vec<_> = counts.iter().map(|c| (c.name.as_str(), c.count)).collect();
+        assert_eq!(counts, [("c", 5), ("python", 2), ("rust", 2)]);
+    }
+}
@@ -0,0 +1,182 @@
+//! Inlining of SVG files in the articles, with the `{{ svg "diagrams/arch.svg" }}` shortcode.
+//!
+//! Inlined SVGs inherit the CSS of the page (for example `currentColor`) and don't need another
+//! request. The files are read from the `assets` directory at each build.
+//!
+//! The articles inlining each SVG are recorded in the cache directory, so that `serve` rebuilds
+//! them when the SVG changes.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Component, Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+use serde::Deserialize;
+
+use crate::links;
+
+/// File of the cache directory recording the SVGs inlined by the articles
+const DEPENDENCIES: &str = "svg-dependencies.json";
+
+/// Sources of the articles inlining each SVG, by the path of the SVG
+pub type Dependencies = BTreeMap<PathBuf, BTreeSet<PathBuf>>;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct SvgConfig {
+    /// Simplify the inlined SVGs with usvg, converting their text to paths with the system fonts
+    #[serde(default)]
+    pub optimize: bool,
+}
+
+pub struct InlineSvg {
+    /// Directory of the assets, from which the SVGs are read
+    assets: PathBuf,
+    /// Options of usvg, if the SVGs are optimized
+    optimize: Option<usvg::Options<'static>>,
+    /// Articles inlining each SVG, recorded as they are rendered
+    used: Mutex<Dependencies>,
+}
+
+impl InlineSvg {
+    pub fn new(input: &Path, config: &SvgConfig) -> Self {
+        let optimize = config.optimize.then(|| {
+            let mut options = usvg::Options::default();
+            options.fontdb_mut().load_system_fonts();
+            options
+        });
+
+        Self {
+            assets: input.join("assets"),
+            optimize,
+            used: Mutex::default(),
+        }
+    }
+
+    /// File of the SVG at `path`, relative to the assets directory
+    fn resolve(&self, path: &str) -> Result<PathBuf> {
+        let relative = links::normalize(Path::new(path));
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            eyre::bail!("The SVG `{path}` is not in the assets directory");
+        }
+
+        Ok(self.assets.join(relative))
+    }
+
+    /// Content of the SVG at `path`, read from its `file`
+    fn load(&self, path: &str, file: &Path) -> Result<String> {
+        let source = std::fs::read_to_string(file)
+            .with_context(|| format!("Could not read the SVG `{path}`"))?;
+
+        let svg = match &self.optimize {
+            Some(options) => usvg::Tree::from_str(&source, options)
+                .with_context(|| format!("Invalid SVG `{path}`"))?
+                .to_string(&usvg::WriteOptions::default()),
+            None => source,
+        };
+
+        // The XML declaration, doctype and comments before the root element are not valid in HTML
+        match svg.find("<svg") {
+            Some(start) => Ok(svg[start..].trim_end().to_owned()),
+            None => eyre::bail!("`{path}` is not an SVG file"),
+        }
+    }
+
+    /// HTML of the `svg` shortcode with the `argument` (`"diagrams/arch.svg"`), in the `article`
+    /// (the path of its source)
+    pub fn render(&self, argument: &str, article: &Path) -> Result<String> {
+        let path = argument
+            .trim()
+            .strip_prefix('"')
+            .and_then(|argument| argument.strip_suffix('"'))
+            .ok_or_else(|| eyre::eyre!("Expected a quoted SVG path, found `{argument}`"))?;
+
+        let file = self.resolve(path)?;
+        // Recorded even if the SVG is missing, so that the article is rebuilt once it is created
+        self.used
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(file.clone())
+            .or_default()
+            .insert(article.to_owned());
+
+        self.load(path, &file)
+    }
+
+    /// Record the SVGs inlined by the articles rendered so far in the `cache` directory
+    pub fn write_dependencies(&self, cache: &Path) -> Result<()> {
+        let used = self.used.lock().unwrap_or_else(PoisonError::into_inner);
+        let path = cache.join(DEPENDENCIES);
+
+        std::fs::create_dir_all(cache)
+            .with_context(|| format!("Could not create {}", cache.display()))?;
+        std::fs::write(&path, serde_json::to_string_pretty(&*used)?)
+            .with_context(|| format!("Could not write {}", path.display()))
+    }
+}
+
+/// SVGs inlined by the articles of the last build, recorded in the `cache` directory
+pub fn dependencies(cache: &Path) -> Dependencies {
+    std::fs::read_to_string(cache.join(DEPENDENCIES))
+        .ok()
+        .and_then(|dependencies| serde_json::from_str(&dependencies).ok())
+        .unwrap_or_default()
+}
+
+/// Path of the file recording the SVGs inlined by the articles in the `cache` directory
+pub fn dependencies_path(cache: &Path) -> PathBuf {
+    cache.join(DEPENDENCIES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dependencies() {
+        let svg = InlineSvg::new(Path::new("site"), &SvgConfig::default());
+        // The SVGs don't exist, but the articles using them are recorded
+        assert!(svg
+            .render("\"diagrams/arch.svg\"", Path::new("a.md"))
+            .is_err());
+        assert!(svg
+            .render(r#" "./diagrams/arch.svg" "#, Path::new("b.md"))
+            .is_err());
+        assert!(svg.render("\"logo.svg\"", Path::new("a.md")).is_err());
+
+        let used = svg.used.into_inner().unwrap();
+        assert_eq!(
+            used,
+            Dependencies::from([
+                (
+                    PathBuf::from("site/assets/diagrams/arch.svg"),
+                    BTreeSet::from([PathBuf::from("a.md"), PathBuf::from("b.md")])
+                ),
+                (
+                    PathBuf::from("site/assets/logo.svg"),
+                    BTreeSet::from([PathBuf::from("a.md")])
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn outside_of_the_assets() {
+        let svg = InlineSvg::new(Path::new("site"), &SvgConfig::default());
+        let error = svg
+            .render("\"/etc/logo.svg\"", Path::new("a.md"))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The SVG `/etc/logo.svg` is not in the assets directory"
+        );
+        assert!(svg.used.into_inner().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{eyre::Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{slug::slugify, url::SiteUrl, Metadata};
+
+/// Information on a tag provided in `tags.toml`
+#[derive(Deserialize, Debug, Default)]
+pub struct TagData {
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TagInfo {
+    pub name: String,
+    pub slug: String,
+    /// URL of the page listing the articles with this tag
+    pub url: SiteUrl,
+    /// Number of articles with this tag
+    pub count: usize,
+    pub description: Option<String>,
+}
+
+/// Load the tag descriptions from `tags.toml`, if it exists
+pub fn load_data(input: &Path) -> Result<HashMap<String, TagData>> {
+    let path = input.join("tags.toml");
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    toml::from_str(&std::fs::read_to_string(&path).context("Could not read tags.toml")?)
+        .context("Invalid tags.toml")
+}
+
+/// Collect all the tags used by the articles, sorted by name
+pub fn collect<'a>(
+    articles: impl IntoIterator<Item = &'a Metadata>,
+    data: &HashMap<String, TagData>,
+) -> Vec<TagInfo> {
+    let mut counts = BTreeMap::new();
+    for metadata in articles {
+        for tag in &metadata.tags {
+            *counts.entry(tag.as_str()).or_insert(0) += 1;
+        }
+    }
+
+    counts
+        .into_iter()
+        .map(|(name, count)| {
+            let slug = slugify(name);
+            TagInfo {
+                name: name.to_owned(),
+                url: SiteUrl::from_path(&tag_path(&slug)),
+                slug,
+                count,
+                description: data.get(name).and_then(|d| d.description.clone()),
+            }
+        })
+        .collect()
+}
+
+impl TagInfo {
+    /// Path of the page listing the articles with this tag, relative to the output directory
+    pub fn path(&self) -> PathBuf {
+        tag_path(&self.slug)
+    }
+
+    /// Path of the RSS feed of the articles with this tag, relative to the output directory
+    pub fn feed_path(&self) -> PathBuf {
+        Path::new("tags").join(&self.slug).join("rss.xml")
+    }
+}
+
+fn tag_path(slug: &str) -> PathBuf {
+    Path::new("tags").join(format!("{slug}.html"))
+}
+
+/// Find the information on the tags in `names` from the tags collected on all the articles
+pub fn find<'a>(all_tags: &'a [TagInfo], names: &[String]) -> Vec<&'a TagInfo> {
+    names
+        .iter()
+        .filter_map(|name| {
+            all_tags
+                .binary_search_by(|tag| tag.name.as_str().cmp(name))
+                .ok()
+                .map(|i| &all_tags[i])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(tags: &[&str]) -> Metadata {
+        let mut metadata: Metadata = toml::from_str(
+            "title = \"Intro\"\ndate = \"2023-04-05\"\npage = \"article\"\nsummary = \"\"\n",
+        )
+        .unwrap();
+        metadata.tags = tags.iter().map(|&tag| tag.to_owned()).collect();
+        metadata
+    }
+
+    #[test]
+    fn counts() {
+        let articles = [
+            metadata(&["rust", "Web Dev"]),
+            metadata(&["rust"]),
+            metadata(&[]),
+        ];
+        let data = HashMap::from([(
+            "rust".to_owned(),
+            TagData {
+                description: Some("Posts about Rust".to_owned()),
+            },
+        )]);
+
+        let tags = collect(&articles, &data);
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].name, "Web Dev");
+        assert_eq!(tags[0].slug, "web-dev");
+        assert_eq!(tags[0].count, 1);
+        assert_eq!(tags[0].description, None);
+        assert_eq!(tags[0].path(), Path::new("tags/web-dev.html"));
+        assert_eq!(tags[1].name, "rust");
+        assert_eq!(tags[1].count, 2);
+        assert_eq!(tags[1].description.as_deref(), Some("Posts about Rust"));
+        assert_eq!(tags[1].feed_path(), Path::new("tags/rust/rss.xml"));
+
+        let found = find(&tags, &["rust".to_owned(), "unknown".to_owned()]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "rust");
+    }
+}
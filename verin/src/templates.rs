@@ -0,0 +1,304 @@
+//! Rendering of the pages with the template engine of the configuration.
+//!
+//! Liquid (`*.liquid` templates) is used by default, and minijinja (`*.jinja` templates) can be
+//! chosen with `template_engine = "jinja"`. Both engines are given the same variables and filters.
+
+use std::{collections::HashMap, fmt, io, path::Path, sync::Arc};
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+use liquid::{model::KStringRef, ValueView};
+use serde::Deserialize;
+use ts_highlight_html::SyntaxConfig;
+
+use crate::{
+    filters,
+    html::{self, RenderOptions},
+    layout, links,
+};
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EngineKind {
+    #[default]
+    Liquid,
+    Jinja,
+}
+
+impl EngineKind {
+    /// Extension of the template files
+    pub fn extension(self) -> &'static str {
+        match self {
+            EngineKind::Liquid => "liquid",
+            EngineKind::Jinja => "jinja",
+        }
+    }
+}
+
+/// Variables given to many pages (the assets, the tags, ...), converted once for the engine
+pub struct SharedContext(SharedInner);
+
+enum SharedInner {
+    Liquid(liquid::Object),
+    Jinja(minijinja::Value),
+}
+
+/// Template engine rendering the pages
+///
+/// Templates are designated by the name of their file, without the extension (`article`).
+pub trait Engine: Send + Sync {
+    fn contains(&self, name: &str) -> bool;
+
+    /// Convert the variables of `context` once, to render many pages with [`Engine::render_to`]
+    fn share(&self, context: &serde_json::Value) -> Result<SharedContext>;
+
+    /// Render the template `name` with the variables of `shared` and of `context`, the ones of
+    /// `context` taking precedence
+    fn render_to(
+        &self,
+        name: &str,
+        shared: &SharedContext,
+        context: &serde_json::Value,
+        output: &mut dyn io::Write,
+    ) -> Result<()>;
+}
+
+/// Name of the template writing the fenced code blocks of the articles, if it exists
+pub const CODE_BLOCK: &str = "codeblock";
+
+/// Fenced code blocks written by the [`CODE_BLOCK`] template, with the shared variables and a
+/// `code` variable describing the block
+pub struct CodeBlocks<'t> {
+    pub engine: &'t dyn Engine,
+    pub shared: &'t SharedContext,
+}
+
+impl fmt::Debug for CodeBlocks<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CodeBlocks").finish_non_exhaustive()
+    }
+}
+
+impl html::CodeBlockTemplate for CodeBlocks<'_> {
+    fn render(&self, block: &html::CodeBlock) -> Result<String> {
+        let mut output = Vec::new();
+        self.engine.render_to(
+            CODE_BLOCK,
+            self.shared,
+            &serde_json::json!({ "code": block }),
+            &mut output,
+        )?;
+        String::from_utf8(output).context("The code block template did not output UTF-8")
+    }
+}
+
+/// Load the templates of the website in `input` with the engine `kind`
+pub fn load(
+    input: &Path,
+    kind: EngineKind,
+    syntax_conf: Arc<SyntaxConfig<'static>>,
+    base_url: Option<String>,
+    options: RenderOptions,
+    diagnostics: filters::Diagnostics,
+) -> Result<Box<dyn Engine>> {
+    Ok(match kind {
+        EngineKind::Liquid => Box::new(Liquid::load(
+            input,
+            syntax_conf,
+            base_url,
+            options,
+            diagnostics,
+        )?),
+        EngineKind::Jinja => Box::new(Jinja::load(
+            input,
+            syntax_conf,
+            base_url,
+            options,
+            diagnostics,
+        )?),
+    })
+}
+
+pub fn template_name(path: &Path) -> Result<String> {
+    Ok(path
+        .file_stem()
+        .expect("Template has no file stem, should not be possible")
+        .to_str()
+        .ok_or(eyre::eyre!("Template name should be valid UTF-8"))?
+        .to_owned())
+}
+
+/// Liquid parser with the filters of verin
+pub fn liquid_parser(
+    syntax_conf: Arc<SyntaxConfig<'static>>,
+    base_url: Option<String>,
+    options: RenderOptions,
+    diagnostics: filters::Diagnostics,
+) -> Result<liquid::Parser> {
+    Ok(liquid::ParserBuilder::with_stdlib()
+        .filter(filters::Markdown::new(syntax_conf, options, diagnostics))
+        .filter(filters::AbsoluteUrl::new(base_url))
+        .filter(filters::RelativeUrl)
+        .build()?)
+}
+
+struct Liquid {
+    pages: HashMap<String, liquid::Template>,
+}
+
+impl Liquid {
+    fn load(
+        input: &Path,
+        syntax_conf: Arc<SyntaxConfig<'static>>,
+        base_url: Option<String>,
+        options: RenderOptions,
+        diagnostics: filters::Diagnostics,
+    ) -> Result<Self> {
+        let parser = liquid_parser(syntax_conf, base_url, options, diagnostics)?;
+
+        let mut pages = HashMap::new();
+        for entry in layout::page_templates(input, EngineKind::Liquid)? {
+            let template = parser
+                .parse(&layout::load(input, &entry)?)
+                .with_context(|| format!("Could not parse the template {}", entry.display()))?;
+            pages.insert(template_name(&entry)?, template);
+        }
+
+        Ok(Self { pages })
+    }
+}
+
+impl Engine for Liquid {
+    fn contains(&self, name: &str) -> bool {
+        self.pages.contains_key(name)
+    }
+
+    fn share(&self, context: &serde_json::Value) -> Result<SharedContext> {
+        Ok(SharedContext(SharedInner::Liquid(liquid::to_object(
+            context,
+        )?)))
+    }
+
+    fn render_to(
+        &self,
+        name: &str,
+        shared: &SharedContext,
+        context: &serde_json::Value,
+        output: &mut dyn io::Write,
+    ) -> Result<()> {
+        let SharedContext(SharedInner::Liquid(shared)) = shared else {
+            eyre::bail!("The shared context was not prepared by liquid");
+        };
+        let template = self
+            .pages
+            .get(name)
+            .ok_or_else(|| eyre::eyre!("Template `{name}` does not exist"))?;
+
+        // The shared variables are borrowed instead of being copied in the globals of each page
+        let context = liquid::to_object(context)?;
+        let globals: HashMap<KStringRef, &dyn ValueView> = shared
+            .iter()
+            .chain(context.iter())
+            .map(|(key, value)| (KStringRef::from(key.as_str()), value as &dyn ValueView))
+            .collect();
+
+        template.render_to(output, &globals)?;
+        Ok(())
+    }
+}
+
+struct Jinja {
+    env: minijinja::Environment<'static>,
+    /// Name of the templates of the pages in the environment, by page template name
+    pages: HashMap<String, String>,
+}
+
+impl Jinja {
+    fn load(
+        input: &Path,
+        syntax_conf: Arc<SyntaxConfig<'static>>,
+        base_url: Option<String>,
+        options: RenderOptions,
+        diagnostics: filters::Diagnostics,
+    ) -> Result<Self> {
+        let mut env = minijinja::Environment::new();
+        // Like liquid, the variables containing HTML are not escaped
+        env.set_auto_escape_callback(|_| minijinja::AutoEscape::None);
+        filters::register_jinja(&mut env, syntax_conf, base_url, options, diagnostics);
+
+        // All the templates are added, so that they can be extended or included by their path
+        // from the input directory (`{% extends "layouts/base.jinja" %}`)
+        let pattern = input.join("**").join("*.jinja");
+        for entry in glob::glob(&pattern.to_string_lossy())? {
+            let entry = entry?;
+            let source = std::fs::read_to_string(&entry)
+                .with_context(|| format!("Could not read the template {}", entry.display()))?;
+            env.add_template_owned(links::url_path(entry.strip_prefix(input)?), source)
+                .map_err(|e| eyre::eyre!("{e:#}"))
+                .with_context(|| format!("Could not parse the template {}", entry.display()))?;
+        }
+
+        let mut pages = HashMap::new();
+        for entry in layout::page_templates(input, EngineKind::Jinja)? {
+            pages.insert(
+                template_name(&entry)?,
+                links::url_path(entry.strip_prefix(input)?),
+            );
+        }
+
+        Ok(Self { env, pages })
+    }
+
+    fn render_value(
+        &self,
+        name: &str,
+        context: minijinja::Value,
+        output: &mut dyn io::Write,
+    ) -> Result<()> {
+        let template = self
+            .pages
+            .get(name)
+            .ok_or_else(|| eyre::eyre!("Template `{name}` does not exist"))?;
+
+        let rendered = self
+            .env
+            .get_template(template)
+            .and_then(|template| template.render(context))
+            .map_err(|e| eyre::eyre!("{e:#}"))?;
+        output.write_all(rendered.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Engine for Jinja {
+    fn contains(&self, name: &str) -> bool {
+        self.pages.contains_key(name)
+    }
+
+    fn share(&self, context: &serde_json::Value) -> Result<SharedContext> {
+        Ok(SharedContext(SharedInner::Jinja(
+            minijinja::Value::from_serialize(context),
+        )))
+    }
+
+    fn render_to(
+        &self,
+        name: &str,
+        shared: &SharedContext,
+        context: &serde_json::Value,
+        output: &mut dyn io::Write,
+    ) -> Result<()> {
+        let SharedContext(SharedInner::Jinja(shared)) = shared else {
+            eyre::bail!("The shared context was not prepared by minijinja");
+        };
+
+        // Merged maps look the variables up in each map instead of copying them
+        let context = minijinja::value::merge_maps([
+            minijinja::Value::from_serialize(context),
+            shared.clone(),
+        ]);
+        self.render_value(name, context, output)
+    }
+}
@@ -0,0 +1,210 @@
+//! URLs of the files of the website.
+//!
+//! Pages link to each other with URLs from the root of the website (`/tags/rust.html`), while
+//! feeds need absolute URLs (`https://example.org/tags/rust.html`). Building all of them from a
+//! [`SiteUrl`] keeps them consistent.
+//!
+//! A website can be published under a base path (`/preview/branch/`), for example for preview
+//! deployments. It is set once for the build with [`set_base_path`], and prefixed to all the URLs.
+
+use std::{fmt, path::Path, sync::OnceLock};
+
+use serde::{Serialize, Serializer};
+
+use crate::links;
+
+/// Path under which the website is published, without leading `/` and with a trailing `/` unless
+/// empty (`preview/branch/`)
+static BASE_PATH: OnceLock<String> = OnceLock::new();
+
+/// Publish the website under `path` (`/preview/branch/`), or at the root of the host if `None`
+///
+/// The base path is set once per run: the builds of `serve` all use the same one, and a different
+/// base path is ignored.
+pub fn set_base_path(path: Option<&str>) {
+    let path = path.unwrap_or_default().trim_matches('/');
+    let path = match path {
+        "" => String::new(),
+        path => format!("{path}/"),
+    };
+
+    if let Err(path) = BASE_PATH.set(path) {
+        if path != base_path() {
+            println!(
+                "[WARNING] The base path is already `/{}`, ignoring `/{path}`",
+                base_path()
+            );
+        }
+    }
+}
+
+fn base_path() -> &'static str {
+    BASE_PATH.get().map_or("", String::as_str)
+}
+
+/// URL of a file of the website, stored as its path from the root of the website
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SiteUrl {
+    /// `/` separated path, without leading `/`
+    path: String,
+}
+
+impl SiteUrl {
+    /// URL of the root of the website
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    /// URL of the file at `path`, relative to the output directory
+    pub fn from_path(path: &Path) -> Self {
+        Self {
+            path: links::url_path(path),
+        }
+    }
+
+    /// URL of the page at `path`, relative to the output directory
+    ///
+    /// The URL of `index.html` pages is the URL of their directory (`/posts/intro/`).
+    pub fn from_page(path: &Path) -> Self {
+        let mut url = Self::from_path(path);
+        if let Some(directory) = url.path.strip_suffix("index.html") {
+            if directory.is_empty() || directory.ends_with('/') {
+                url.path.truncate(directory.len());
+            }
+        }
+        url
+    }
+
+    /// Parse an URL from the root of the website, with or without the leading `/` and the base
+    /// path
+    pub fn parse(url: &str) -> Self {
+        let path = url.trim_start_matches('/');
+        let base = base_path();
+        Self {
+            path: path.strip_prefix(base).unwrap_or(path).to_owned(),
+        }
+    }
+
+    /// URL of `path` inside the directory designated by this URL
+    pub fn join(&self, path: &str) -> Self {
+        let path = path.trim_start_matches('/');
+        if self.path.is_empty() {
+            return Self::parse(path);
+        }
+
+        Self {
+            path: format!("{}/{path}", self.path.trim_end_matches('/')),
+        }
+    }
+
+    /// Path from the root of the website, without leading `/` (`tags/rust.html`)
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Absolute URL of the file, for a website published at `base` (`https://example.org`)
+    ///
+    /// The base path is only added once if `base` already ends with it
+    /// (`https://example.github.io/verin`).
+    pub fn with_base(&self, base: &str) -> String {
+        let base = base.trim_end_matches('/');
+        let base_path = base_path();
+        let host = match base_path.trim_end_matches('/') {
+            "" => base,
+            path => base.strip_suffix(&format!("/{path}")).unwrap_or(base),
+        };
+        format!("{host}{self}")
+    }
+
+    /// URL relative to the page at `from` (`../tags/rust.html` from `/posts/intro.html`)
+    pub fn relative_to(&self, from: &SiteUrl) -> String {
+        let mut directory: Vec<_> = from.path.split('/').collect();
+        // The page itself
+        directory.pop();
+
+        let target: Vec<_> = self.path.split('/').collect();
+        let common = directory
+            .iter()
+            .zip(&target[..target.len() - 1])
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let mut relative = "../".repeat(directory.len() - common);
+        relative.push_str(&target[common..].join("/"));
+        if relative.is_empty() {
+            relative.push_str("./");
+        }
+        relative
+    }
+}
+
+/// URL from the root of the host, without the base URL (`/tags/rust.html`, or
+/// `/preview/branch/tags/rust.html` under a base path)
+impl fmt::Display for SiteUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "/{}{}", base_path(), self.path)
+    }
+}
+
+impl Serialize for SiteUrl {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(path: &str) -> SiteUrl {
+        SiteUrl::from_page(Path::new(path))
+    }
+
+    #[test]
+    fn pages() {
+        assert_eq!(page("index.html"), SiteUrl::root());
+        assert_eq!(page("posts/index.html").path(), "posts/");
+        assert_eq!(page("posts/intro.html").path(), "posts/intro.html");
+        assert_eq!(page("posts/myindex.html").path(), "posts/myindex.html");
+
+        assert_eq!(SiteUrl::parse("/tags/rust.html").path(), "tags/rust.html");
+        assert_eq!(
+            SiteUrl::parse("tags/").join("/rust.html").path(),
+            "tags/rust.html"
+        );
+        assert_eq!(SiteUrl::root().join("rust.html").path(), "rust.html");
+    }
+
+    #[test]
+    fn absolute() {
+        let url = page("tags/rust.html");
+        assert_eq!(url.to_string(), "/tags/rust.html");
+        assert_eq!(
+            url.with_base("https://example.org/"),
+            "https://example.org/tags/rust.html"
+        );
+    }
+
+    #[test]
+    fn relative() {
+        let rust = page("tags/rust.html");
+        assert_eq!(
+            rust.relative_to(&page("posts/intro.html")),
+            "../tags/rust.html"
+        );
+        assert_eq!(
+            rust.relative_to(&page("posts/intro/index.html")),
+            "../../tags/rust.html"
+        );
+        assert_eq!(rust.relative_to(&page("index.html")), "tags/rust.html");
+        assert_eq!(rust.relative_to(&page("tags/go.html")), "rust.html");
+
+        let intro = page("posts/intro/index.html");
+        assert_eq!(intro.relative_to(&intro), "./");
+        assert_eq!(SiteUrl::root().relative_to(&SiteUrl::root()), "./");
+        assert_eq!(
+            SiteUrl::root().relative_to(&page("posts/intro.html")),
+            "../"
+        );
+    }
+}
@@ -0,0 +1,84 @@
+//! Pages written by dedicated threads while the next ones are rendered.
+//!
+//! The pages are sharded between the writer threads by path. Each thread has a bounded queue, so
+//! that the rendering threads wait for the writes instead of holding all the pages in memory when
+//! the disk is slower than them.
+
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    path::PathBuf,
+    sync::mpsc::{sync_channel, Receiver, SyncSender},
+    thread::{spawn, JoinHandle},
+};
+
+use color_eyre::{
+    eyre::{self, Context},
+    Result,
+};
+
+/// Number of writer threads
+const SHARDS: usize = 4;
+/// Number of pages waiting to be written by each writer thread
+const QUEUE: usize = 16;
+
+struct Page {
+    path: PathBuf,
+    content: Vec<u8>,
+}
+
+pub struct Writers {
+    queues: Vec<SyncSender<Page>>,
+    threads: Vec<JoinHandle<Result<()>>>,
+}
+
+/// Write the pages of `queue` until it is closed, stopping at the first error
+fn write_pages(queue: Receiver<Page>) -> Result<()> {
+    for Page { path, content } in queue {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).context("Could not create output directory")?;
+        }
+        std::fs::write(&path, content)
+            .with_context(|| format!("Could not write {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+impl Writers {
+    pub fn new() -> Self {
+        let (queues, threads) = (0..SHARDS)
+            .map(|_| {
+                let (sender, receiver) = sync_channel(QUEUE);
+                (sender, spawn(move || write_pages(receiver)))
+            })
+            .unzip();
+
+        Self { queues, threads }
+    }
+
+    /// Queue the page `content` to be written at `path`, waiting if its writer is behind
+    pub fn write(&self, path: PathBuf, content: Vec<u8>) -> Result<()> {
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        let shard = hasher.finish() as usize % self.queues.len();
+
+        // The queue is only closed when its thread stopped on an error, reported by `finish`
+        self.queues[shard]
+            .send(Page { path, content })
+            .map_err(|page| eyre::eyre!("Could not write {}", page.0.path.display()))
+    }
+
+    /// Wait for all the queued pages to be written
+    pub fn finish(self) -> Result<()> {
+        drop(self.queues);
+
+        let mut result = Ok(());
+        for thread in self.threads {
+            let written = thread
+                .join()
+                .map_err(|_| eyre::eyre!("A writer thread panicked"))?;
+            result = result.and(written);
+        }
+        result
+    }
+}
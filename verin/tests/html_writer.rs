@@ -0,0 +1,734 @@
+use proptest::prelude::*;
+use ts_highlight_html::{theme, Diagnostic, Output, SyntaxConfig};
+use verin::{
+    html::{self, MathOutput, RenderOptions},
+    html_check::check_markdown,
+};
+
+/// Inline markdown: text mixed with emphasis, code, links, math, ...
+fn inline() -> impl Strategy<Value = String> {
+    let leaf = prop_oneof![
+        "[a-zA-Z0-9 <>&\"'#]{0,12}",
+        "`[a-z<>&]{1,6}`",
+        "\\$[a-z+*/^ ]{1,6}\\$",
+        "\\[\\^[a-z]{1,3}\\]",
+        Just("<span>raw</span>".to_owned()),
+        Just("&amp; &#x27; &nope".to_owned()),
+    ];
+
+    leaf.prop_recursive(3, 16, 4, |inner| {
+        prop_oneof![
+            inner.clone().prop_map(|text| format!("*{text}*")),
+            inner.clone().prop_map(|text| format!("**{text}**")),
+            inner.clone().prop_map(|text| format!("~~{text}~~")),
+            (inner.clone(), "[a-z:/<>\"&]{0,10}")
+                .prop_map(|(text, url)| format!("[{text}]({url} \"t&<\")")),
+            (inner.clone(), "[a-z/.]{0,8}").prop_map(|(alt, url)| format!("![{alt}]({url})")),
+            (inner.clone(), "[a-z/.]{0,8}", "[a-z/.]{1,8}")
+                .prop_map(|(alt, url, dark)| format!("![{alt}]({url}){{dark={dark}}}")),
+            prop::collection::vec(inner, 1..4).prop_map(|parts| parts.join(" ")),
+        ]
+    })
+}
+
+/// Block level markdown
+fn block() -> impl Strategy<Value = String> {
+    prop_oneof![
+        inline(),
+        (1..=6usize, inline()).prop_map(|(level, text)| format!("{} {text}", "#".repeat(level))),
+        (1..=6usize, inline(), "[a-z.]{0,6}")
+            .prop_map(|(level, text, class)| format!("{} {text} {{.{class}}}", "#".repeat(level))),
+        prop::collection::vec(inline(), 1..4)
+            .prop_map(|items| items.iter().map(|item| format!("- {item}\n")).collect()),
+        (1..5u32, prop::collection::vec(inline(), 1..4)).prop_map(|(start, items)| items
+            .iter()
+            .map(|item| format!("{start}. {item}\n"))
+            .collect()),
+        prop::collection::vec(inline(), 1..4)
+            .prop_map(|items| items.iter().map(|item| format!("- [x] {item}\n")).collect()),
+        inline().prop_map(|text| format!("> {text}")),
+        inline().prop_map(|text| format!("> [!WARNING]\n> {text}")),
+        (
+            "(rust|json|nix|unknown|)(,linenos)?(,hl=[0-9]-[0-9])?",
+            "[a-z(){}<>&\"'/*\n ]{0,40}"
+        )
+            .prop_map(|(info, code)| format!("```{info}\n{code}\n```")),
+        "[a-z<>&]{1,20}".prop_map(|code| format!("    {code}")),
+        (inline(), inline())
+            .prop_map(|(head, cell)| format!("| {head} | b |\n|:-|-:|\n| {cell} |")),
+        (inline(), inline(), "[a-z<>&]{0,6}").prop_map(|(head, cell, caption)| format!(
+            "| {head} |\n|-|\n| {cell} |\n\n: {caption} {{#tbl:{caption}}}"
+        )),
+        (inline(), "(fig|tbl|lst):[a-z<>&\"]{1,4}")
+            .prop_map(|(text, label)| format!("![{text}](a.png){{#{label}}} [@{label}]")),
+        "[a-z<>&\"]{1,4}".prop_map(|label| format!("```rust {{#lst:{label}}}\nfn f() {{}}\n```")),
+        ("[a-z]{1,3}", inline()).prop_map(|(name, text)| format!("[^{name}]: {text}")),
+        (inline(), inline()).prop_map(|(term, def)| format!("{term}\n: {def}")),
+        Just("---".to_owned()),
+        "::: details[a-z<>& ]{0,8}",
+        inline().prop_map(|text| format!("::: details\n{text}\n:::")),
+        Just(":::".to_owned()),
+        Just("$$\nx^2 < y\n$$".to_owned()),
+        "[a-z<>&\"/ ]{0,20}".prop_map(|raw| format!("```{{=html}}\n{raw}\n```")),
+    ]
+}
+
+fn document() -> impl Strategy<Value = String> {
+    prop::collection::vec(block(), 0..12).prop_map(|blocks| blocks.join("\n\n"))
+}
+
+fn options() -> impl Strategy<Value = RenderOptions> {
+    (
+        any::<bool>(),
+        any::<bool>(),
+        any::<bool>(),
+        prop_oneof![Just(MathOutput::MathMl), Just(MathOutput::Source)],
+    )
+        .prop_map(
+            |(heading_anchors, sections, wrap_code_blocks, math)| RenderOptions {
+                heading_anchors,
+                sections,
+                wrap_code_blocks,
+                math,
+                ..Default::default()
+            },
+        )
+}
+
+proptest! {
+    #[test]
+    fn structured_markdown(markdown in document(), options in options()) {
+        check_markdown(&markdown, &SyntaxConfig::new(&theme::TOKYO_NIGHT), options);
+    }
+
+    #[test]
+    fn structured_markdown_classes(markdown in document(), options in options()) {
+        check_markdown(
+            &markdown,
+            &SyntaxConfig::with_output(&theme::TOKYO_NIGHT, Output::Classes),
+            options,
+        );
+    }
+
+    #[test]
+    fn arbitrary_markdown(markdown in "\\PC*", options in options()) {
+        check_markdown(&markdown, &SyntaxConfig::new(&theme::TOKYO_NIGHT), options);
+    }
+}
+
+/// HTML of the `markdown` in the `site`, parsed with the extensions of the `options`, and what was
+/// found while writing it
+fn write(markdown: &str, options: RenderOptions, site: html::Site) -> (String, html::Rendered) {
+    let mut output = Vec::new();
+    let rendered = html::write_html_in_site(
+        &mut output,
+        pulldown_cmark::Parser::new_ext(markdown, options.extensions),
+        &SyntaxConfig::new(&theme::TOKYO_NIGHT),
+        options,
+        site,
+    )
+    .unwrap();
+
+    (String::from_utf8(output).unwrap(), rendered)
+}
+
+/// HTML of the `markdown`, parsed with the extensions of the `options`
+fn render(markdown: &str, options: RenderOptions) -> String {
+    write(markdown, options, html::Site::default()).0
+}
+
+/// HTML of the `markdown` in the `site`
+fn render_in_site(markdown: &str, site: html::Site) -> String {
+    write(markdown, RenderOptions::default(), site).0
+}
+
+#[test]
+fn nested_headings() {
+    check_markdown(
+        "## a\n\n#### b\n\n# c\n\n### d `<e>`\n\n## f *g*",
+        &SyntaxConfig::new(&theme::TOKYO_NIGHT),
+        RenderOptions::default(),
+    );
+}
+
+#[test]
+fn raw_block_is_written_as_is() {
+    let markdown = "a *b*\n\n```{=html}\n<div class=\"c\">&copy; <b>d</b>\n```\n\ne\n";
+    assert_eq!(
+        render(markdown, RenderOptions::default()),
+        "<p>a <em>b</em></p>\n<div class=\"c\">&copy; <b>d</b>\n<p>e</p>\n"
+    );
+}
+
+#[test]
+fn heading_text() {
+    let (_, rendered) = write(
+        "## a `<b>` *c* [d](e) & f",
+        RenderOptions::default(),
+        html::Site::default(),
+    );
+
+    let heading = &rendered.headers[0];
+    assert_eq!(heading.text_plain, "a <b> c d & f");
+    assert_eq!(
+        heading.text_html,
+        "a <code>&lt;b&gt;</code> <em>c</em> d &amp; f"
+    );
+}
+
+#[test]
+fn heading_anchors() {
+    let options = RenderOptions {
+        heading_anchors: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        render("# a\n\n## b", options),
+        concat!(
+            r##"<h1 id="header-1" >a<a class="anchor" href="#header-1">#</a></h1>"##,
+            "\n",
+            r##"<h2 id="header-1.1" >b<a class="anchor" href="#header-1.1">#</a></h2>"##,
+            "\n",
+        )
+    );
+}
+
+#[test]
+fn table_of_contents() {
+    let (_, rendered) = write(
+        "# a\n\n## *b*\n\n### c\n\n## d\n\n# e\n\n### f",
+        RenderOptions::default(),
+        html::Site::default(),
+    );
+
+    assert_eq!(
+        rendered.toc(3),
+        concat!(
+            "<nav class=\"toc\">\n<ol>\n",
+            r##"<li><a href="#header-1">a</a>"##,
+            "<ol>\n",
+            r##"<li><a href="#header-1.1"><em>b</em></a>"##,
+            "</li>\n",
+            r##"<li><a href="#header-1.2">d</a>"##,
+            "</li>\n</ol>\n</li>\n",
+            r##"<li><a href="#header-2">e</a>"##,
+            "</li>\n</ol>\n</nav>\n",
+        )
+    );
+    assert_eq!(rendered.toc(1), "");
+}
+
+#[test]
+fn unknown_languages_and_extensions_are_counted() {
+    let markdown =
+        "| a |\n|---|\n| ~~b~~ |\n\n```rust\nfn main() {}\n```\n\n```brainfuck\n+.\n```\n";
+    let options = RenderOptions {
+        extensions: pulldown_cmark::Options::all(),
+        ..Default::default()
+    };
+    let (_, rendered) = write(markdown, options, html::Site::default());
+
+    assert_eq!(rendered.languages, ["rust", "brainfuck"]);
+    assert_eq!(rendered.unhighlighted, ["brainfuck"]);
+    assert_eq!(
+        rendered.diagnostics(),
+        [Diagnostic::UnknownLanguage("brainfuck".into())]
+    );
+    assert_eq!(
+        rendered.features.into_iter().collect::<Vec<_>>(),
+        [("strikethrough", 1), ("tables", 1)]
+    );
+}
+
+#[test]
+fn language_aliases_are_highlighted() {
+    let markdown = "```rs\nfn main() {}\n```\n\n```jsonc\n{}\n```\n\n```yaml5\na: 1\n```\n";
+    let rendered = html::write_html(
+        &mut Vec::new(),
+        pulldown_cmark::Parser::new(markdown),
+        &SyntaxConfig::new(&theme::TOKYO_NIGHT).with_aliases([("jsonc", "json")]),
+        RenderOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(rendered.unhighlighted, ["yaml5"]);
+}
+
+#[test]
+fn footnote_references_describe_the_footnote() {
+    let markdown = "a[^b]\n\n[^b]: Some `code`\n    and *text* & more.\n";
+    let output = render(markdown, RenderOptions::default());
+    assert!(
+        output.contains(concat!(
+            r##"<sup class="footnote-reference" id="fnref-b"><a href="#b" aria-describedby="b" "##,
+            r#"data-footnote="Some code and text &amp; more.">1</a></sup>"#,
+        )),
+        "{output}"
+    );
+    assert!(output.contains(r#"<div class="footnote-definition" id="b">"#));
+}
+
+#[test]
+fn footnotes_are_collected_in_a_section() {
+    let markdown = "[^late]: Never referenced\n\na[^x] b[^y] c[^x]\n\n[^y]: Why\n\n[^x]: Ex\n";
+    assert_eq!(
+        render(markdown, RenderOptions::default()),
+        concat!(
+            r##"<p>a<sup class="footnote-reference" id="fnref-x"><a href="#x" "##,
+            r##"aria-describedby="x" data-footnote="Ex">1</a></sup> "##,
+            r##"b<sup class="footnote-reference" id="fnref-y"><a href="#y" "##,
+            r##"aria-describedby="y" data-footnote="Why">2</a></sup> "##,
+            r##"c<sup class="footnote-reference" id="fnref-x-2"><a href="#x" "##,
+            r##"aria-describedby="x" data-footnote="Ex">1</a></sup></p>"##,
+            "\n",
+            r#"<section class="footnotes">"#,
+            "\n",
+            r#"<div class="footnote-definition" id="x"><sup class="footnote-definition-label">1</sup>"#,
+            "\n<p>Ex",
+            r##" <a href="#fnref-x" class="footnote-backref" aria-label="Back to reference 1">↩</a>"##,
+            r##" <a href="#fnref-x-2" class="footnote-backref" aria-label="Back to reference 2">"##,
+            "↩<sup>2</sup></a></p>\n</div>\n",
+            r#"<div class="footnote-definition" id="y"><sup class="footnote-definition-label">2</sup>"#,
+            "\n<p>Why",
+            r##" <a href="#fnref-y" class="footnote-backref" aria-label="Back to reference 1">↩</a>"##,
+            "</p>\n</div>\n",
+            r#"<div class="footnote-definition" id="late">"#,
+            r#"<sup class="footnote-definition-label">3</sup>"#,
+            "\n<p>Never referenced</p>\n</div>\n",
+            "</section>\n",
+        )
+    );
+}
+
+#[test]
+fn math_is_rendered_to_mathml() {
+    let output = render(
+        "a $x^2$ b\n\n$$\nsum_(i=1)^n i\n$$\n",
+        RenderOptions::default(),
+    );
+    assert!(
+        output.starts_with(r#"<p>a <math display="inline">"#),
+        "{output}"
+    );
+    assert!(output.contains(r#"</math> b</p>"#), "{output}");
+    assert!(output.contains(r#"<math display="block">"#), "{output}");
+    assert!(!output.contains('$'), "{output}");
+}
+
+#[test]
+fn equations_are_numbered_and_referenced() {
+    let options = RenderOptions {
+        math: MathOutput::Source,
+        ..Default::default()
+    };
+    let (output, rendered) = write(
+        "See \\ref{sum} and \\ref{nope}.\n\n$$\nx^2\n$$\n\n$$\nsum_i i \\label{sum}\n$$\n",
+        options,
+        html::Site::default(),
+    );
+    assert!(
+        output.starts_with(
+            r##"<p>See <a class="equation-ref" href="#eq-sum">(2)</a> and \ref{nope}.</p>"##
+        ),
+        "{output}"
+    );
+    assert!(
+        output.contains(r#"<span class="equation" id="eq-1"><span class="math block">"#),
+        "{output}"
+    );
+    assert!(
+        output.contains("sum_i i \n</span><span class=\"equation-number\">(2)</span></span>"),
+        "{output}"
+    );
+
+    let labels: Vec<_> = rendered
+        .equations
+        .iter()
+        .map(|equation| {
+            (
+                equation.number,
+                equation.id.as_str(),
+                equation.label.as_deref(),
+            )
+        })
+        .collect();
+    assert_eq!(labels, [(1, "eq-1", None), (2, "eq-sum", Some("sum"))]);
+}
+
+#[test]
+fn math_source_is_kept() {
+    let options = RenderOptions {
+        math: MathOutput::Source,
+        ..Default::default()
+    };
+    assert_eq!(
+        render("a $x < 2$ b", options),
+        "<p>a <span class=\"math inline\">x &lt; 2</span> b</p>\n"
+    );
+}
+
+#[test]
+fn alerts_are_admonitions() {
+    assert_eq!(
+        render(
+            "> [!WARNING]\n> Be *careful*\n\n> quote\n",
+            RenderOptions::default()
+        ),
+        concat!(
+            "<div class=\"admonition warning\">\n",
+            "<p class=\"admonition-title\">Warning</p>\n",
+            "<p>Be <em>careful</em></p>\n",
+            "</div>\n",
+            "<blockquote>\n<p>quote</p>\n</blockquote>\n",
+        )
+    );
+}
+
+#[test]
+fn paragraphs_after_tables_are_kept() {
+    let markdown = "| x |\n|---|\n| 1 |\n\n: Not a caption {#tbl:t} *with* markup\n";
+    let (output, rendered) = write(markdown, RenderOptions::default(), html::Site::default());
+    assert!(rendered.cross_references.is_empty());
+    assert!(!output.contains("tbl-t"), "{output}");
+    assert!(
+        output.ends_with("<p>: Not a caption {#tbl:t} <em>with</em> markup</p>\n"),
+        "{output}"
+    );
+}
+
+#[test]
+fn cross_references_are_numbered() {
+    let markdown = concat!(
+        "See [@fig:b], [@tbl:t], [@lst:l] and [@fig:nope].\n\n",
+        "![a](a.png){#fig:a} ![b](b.png){#fig:b}\n\n",
+        "| x |\n|---|\n| 1 |\n\n",
+        ": Results & more {#tbl:t}\n\n",
+        "```rust {#lst:l}\nfn main() {}\n```\n",
+    );
+    let (output, rendered) = write(markdown, RenderOptions::default(), html::Site::default());
+    assert!(
+        output.starts_with(concat!(
+            r##"<p>See <a class="crossref" href="#fig-b">Figure 2</a>, "##,
+            r##"<a class="crossref" href="#tbl-t">Table 1</a>, "##,
+            r##"<a class="crossref" href="#lst-l">Listing 1</a> and [@fig:nope].</p>"##,
+        )),
+        "{output}"
+    );
+    assert!(
+        output.contains(concat!(
+            r#"<span class="figure" id="fig-a"><img src="a.png" alt="a" />"#,
+            r#"<span class="figure-number">Figure 1</span></span>"#,
+        )),
+        "{output}"
+    );
+    assert!(
+        output.contains("<div class=\"table\" id=\"tbl-t\">\n<table>"),
+        "{output}"
+    );
+    assert!(
+        output.contains(concat!(
+            "</tbody></table>\n<p class=\"caption\">",
+            "<span class=\"table-number\">Table 1</span>: Results &amp; more</p>\n</div>\n",
+        )),
+        "{output}"
+    );
+    assert!(
+        output.contains("<div class=\"listing\" id=\"lst-l\">\n<pre"),
+        "{output}"
+    );
+    assert!(!output.contains("{#"), "{output}");
+
+    let labels: Vec<_> = rendered
+        .cross_references
+        .iter()
+        .map(|reference| (reference.number, reference.id.as_str()))
+        .collect();
+    assert_eq!(
+        labels,
+        [(1, "fig-a"), (2, "fig-b"), (1, "tbl-t"), (1, "lst-l")]
+    );
+}
+
+#[test]
+fn details_blocks_are_collapsible() {
+    let markdown = concat!(
+        "::: details Spoiler & more\n\n",
+        "It was *him*\n\n",
+        ":::\n\n",
+        "::: details\nShort\n:::\n\n",
+        "> ::: details Unclosed\n>\n> quoted\n\n",
+        ":::\n",
+    );
+    assert_eq!(
+        render(markdown, RenderOptions::default()),
+        concat!(
+            "<details>\n<summary>Spoiler &amp; more</summary>\n",
+            "<p>It was <em>him</em></p>\n",
+            "</details>\n",
+            "<details>\n<summary>Details</summary>\n<p>Short</p>\n</details>\n",
+            "<blockquote>\n<details>\n<summary>Unclosed</summary>\n<p>quoted</p>\n",
+            "</details>\n</blockquote>\n",
+            "<p>:::</p>\n",
+        )
+    );
+}
+
+#[test]
+fn sized_images_are_lazy() {
+    let markdown = "![a *b*](local.png \"t\") ![c](https://example.org/remote.png)\n";
+    let site = html::Site {
+        images: [(
+            "local.png".to_owned(),
+            html::LocalImage {
+                width: 640,
+                height: 480,
+                sources: Vec::new(),
+            },
+        )]
+        .into(),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        render_in_site(markdown, site),
+        concat!(
+            "<p><img src=\"local.png\" alt=\"a b\" title=\"t\" width=\"640\" height=\"480\" ",
+            "loading=\"lazy\" decoding=\"async\" /> ",
+            "<img src=\"https://example.org/remote.png\" alt=\"c\" /></p>\n",
+        )
+    );
+}
+
+#[test]
+fn image_variants_are_pictures() {
+    let markdown = "![a](a.png) ![b](b.png){dark=b-dark.png}\n";
+    let image = |name: &str| html::LocalImage {
+        width: 960,
+        height: 540,
+        sources: vec![html::ImageSource {
+            mime: "image/webp".to_owned(),
+            srcset: format!("/images/{name}-480.webp 480w, /images/{name}-960.webp 960w"),
+        }],
+    };
+    let site = html::Site {
+        images: [
+            ("a.png".to_owned(), image("a")),
+            ("b.png".to_owned(), image("b")),
+        ]
+        .into(),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        render_in_site(markdown, site),
+        concat!(
+            "<p><picture><source type=\"image/webp\" ",
+            "srcset=\"/images/a-480.webp 480w, /images/a-960.webp 960w\" />",
+            "<img src=\"a.png\" alt=\"a\" width=\"960\" height=\"540\" loading=\"lazy\" ",
+            "decoding=\"async\" /></picture> ",
+            "<picture><source srcset=\"b-dark.png\" media=\"(prefers-color-scheme: dark)\" />",
+            "<source type=\"image/webp\" ",
+            "srcset=\"/images/b-480.webp 480w, /images/b-960.webp 960w\" />",
+            "<img src=\"b.png\" alt=\"b\" width=\"960\" height=\"540\" loading=\"lazy\" ",
+            "decoding=\"async\" /></picture></p>\n",
+        )
+    );
+}
+
+#[test]
+fn external_links_are_decorated() {
+    let markdown = concat!(
+        "[a](https://Example.ORG/a.html) [b](https://other.org/b \"t\") ",
+        "[c](//cdn.org/c) [d](/d.html) [e](e.html)\n",
+    );
+    let site = html::Site {
+        host: Some("example.org".to_owned()),
+        external_links: html::ExternalLinks {
+            new_tab: true,
+            class: Some("external".to_owned()),
+        },
+        ..Default::default()
+    };
+
+    assert_eq!(
+        render_in_site(markdown, site),
+        concat!(
+            "<p><a href=\"https://Example.ORG/a.html\">a</a> ",
+            "<a href=\"https://other.org/b\" title=\"t\" class=\"external\" target=\"_blank\" ",
+            "rel=\"noopener noreferrer\">b</a> ",
+            "<a href=\"//cdn.org/c\" class=\"external\" target=\"_blank\" ",
+            "rel=\"noopener noreferrer\">c</a> ",
+            "<a href=\"/d.html\">d</a> <a href=\"e.html\">e</a></p>\n",
+        )
+    );
+}
+
+/// Code block template writing the metadata of the blocks around their code
+#[derive(Debug)]
+struct Figure;
+
+impl html::CodeBlockTemplate for Figure {
+    fn render(&self, block: &html::CodeBlock) -> color_eyre::Result<String> {
+        Ok(format!(
+            "<figure data-lang=\"{}\" data-title=\"{}\" data-lines=\"{}\" data-hl=\"{:?}\">{}</figure>\n",
+            block.language,
+            block.title.unwrap_or_default(),
+            block.lines,
+            block.highlighted,
+            block.html,
+        ))
+    }
+}
+
+#[test]
+fn code_blocks_use_the_site_template() {
+    let markdown = "```text title=notes.txt hl=2\na < b\nc\n```\n\n```\nplain\n```\n";
+    let site = html::Site {
+        code_blocks: Some(&Figure),
+        ..Default::default()
+    };
+
+    let output = render_in_site(markdown, site);
+    assert!(!output.contains("<pre"), "{output}");
+    assert!(
+        output.contains(
+            "<figure data-lang=\"text\" data-title=\"notes.txt\" data-lines=\"2\" data-hl=\"[[2, 2]]\">"
+        ),
+        "{output}"
+    );
+    assert!(output.contains("a &lt; b"), "{output}");
+    assert!(
+        output.contains("<figure data-lang=\"\" data-title=\"\" data-lines=\"1\" data-hl=\"[]\">plain\n</figure>"),
+        "{output}"
+    );
+}
+
+#[test]
+fn diff_lines_are_marked() {
+    let markdown = "```diff,hl=3\n-old\n+new\n same\n```\n";
+    let mut output = Vec::new();
+    html::write_html(
+        &mut output,
+        pulldown_cmark::Parser::new(markdown),
+        &SyntaxConfig::with_output(&theme::TOKYO_NIGHT, Output::Classes),
+        RenderOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        concat!(
+            "<pre class=\"highlight\"><code>",
+            "<span class=\"newline removed\">-old\n</span>",
+            "<span class=\"newline added\">+new\n</span>",
+            "<span class=\"newline highlighted\"> same\n</span>",
+            "</code></pre>",
+        )
+    );
+}
+
+#[test]
+fn headings_start_sections() {
+    let markdown =
+        "intro\n\n# a\n\n## b\n\n> ### quoted\n\n## c\n\n# d\n\ntext[^n]\n\n[^n]: note\n";
+    let options = RenderOptions {
+        sections: true,
+        ..Default::default()
+    };
+
+    let output = render(markdown, options);
+    let tags: Vec<_> = output
+        .lines()
+        .filter(|line| line.starts_with("<section") || line.starts_with("</section"))
+        .collect();
+    assert_eq!(
+        tags,
+        [
+            r#"<section aria-labelledby="header-1">"#,
+            r#"<section aria-labelledby="header-1.1">"#,
+            "</section>",
+            r#"<section aria-labelledby="header-1.2">"#,
+            "</section>",
+            "</section>",
+            r#"<section aria-labelledby="header-2">"#,
+            "</section>",
+            r#"<section class="footnotes">"#,
+            "</section>",
+        ],
+        "{output}"
+    );
+}
+
+#[test]
+fn written_details_contain_their_headings() {
+    let markdown = "<details open>\n\n# inside\n\n</details>\n\n</details>\n\n# after\n";
+    let options = RenderOptions {
+        sections: true,
+        ..Default::default()
+    };
+
+    let output = render(markdown, options);
+    let tags: Vec<_> = output
+        .lines()
+        .filter(|line| line.starts_with("<section") || line.starts_with("</section"))
+        .collect();
+    assert_eq!(
+        tags,
+        [r#"<section aria-labelledby="header-2">"#, "</section>"],
+        "{output}"
+    );
+}
+
+#[test]
+fn custom_heading_ids() {
+    let options = RenderOptions {
+        sections: true,
+        heading_anchors: true,
+        extensions: pulldown_cmark::Options::ENABLE_HEADING_ATTRIBUTES,
+        ..Default::default()
+    };
+    let (output, rendered) = write("# a {#intro}\n\n## b\n", options, html::Site::default());
+    assert!(
+        output.contains(r#"<section aria-labelledby="intro">"#),
+        "{output}"
+    );
+    assert!(
+        output.contains(r##"<h1 id="intro" >a<a class="anchor" href="#intro">#</a></h1>"##),
+        "{output}"
+    );
+    assert!(output.contains(r#"<h2 id="header-1.1" >"#), "{output}");
+    assert_eq!(rendered.headers[0].id, "intro");
+    assert_eq!(rendered.headers[0].number, "1");
+    assert!(rendered.toc(3).contains(r##"<a href="#intro">a</a>"##));
+}
+
+#[test]
+fn code_blocks_are_wrapped() {
+    let markdown = "```rust title=\"Hello, world.rs\",linenos\nfn main() {}\n```\n\n    indented\n";
+    let mut output = Vec::new();
+    html::write_html(
+        &mut output,
+        pulldown_cmark::Parser::new(markdown),
+        &SyntaxConfig::with_output(&theme::TOKYO_NIGHT, Output::Classes),
+        RenderOptions {
+            wrap_code_blocks: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let output = String::from_utf8(output).unwrap();
+    assert!(
+        output.starts_with(concat!(
+            "<div class=\"code-block\" data-lang=\"rust\">\n",
+            "<div class=\"code-title\">Hello, world.rs</div>\n",
+            "<pre class=\"highlight\"><code><span class=\"newline\"><span class=\"line-number\">1</span>",
+        )),
+        "{output}"
+    );
+    assert!(
+        output
+            .ends_with("<div class=\"code-block\">\n<pre><code>indented\n</code></pre>\n</div>\n"),
+        "{output}"
+    );
+}
@@ -0,0 +1,180 @@
+//! Builds the websites of `tests/fixtures` with the `verin` binary, and checks their output.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Output of the build of a fixture website
+struct Site {
+    output: PathBuf,
+}
+
+/// Copy the directory `from` to `to`, recursively
+fn copy_dir(from: &Path, to: &Path) {
+    std::fs::create_dir_all(to).expect("could not create directory");
+    for entry in std::fs::read_dir(from).expect("could not read fixture") {
+        let entry = entry.expect("could not read fixture");
+        let target = to.join(entry.file_name());
+        if entry.path().is_dir() {
+            copy_dir(&entry.path(), &target);
+        } else {
+            std::fs::copy(entry.path(), target).expect("could not copy fixture");
+        }
+    }
+}
+
+impl Site {
+    /// Build the fixture `name` in strict mode with the additional `args`, failing the test if the
+    /// build fails
+    fn build(name: &str, args: &[&str]) -> Self {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+
+        // The build writes its cache in the input directory, it runs on a copy of the fixture
+        let root = Path::new(env!("CARGO_TARGET_TMPDIR"))
+            .join("sites")
+            .join(name);
+        if root.exists() {
+            std::fs::remove_dir_all(&root).expect("could not remove previous build");
+        }
+        let input = root.join("input");
+        let output = root.join("output");
+        copy_dir(&fixture, &input);
+
+        let build = Command::new(env!("CARGO_BIN_EXE_verin"))
+            .arg("build")
+            .arg(&input)
+            .arg(&output)
+            .arg("--strict")
+            .args(args)
+            .output()
+            .expect("could not run verin");
+        assert!(
+            build.status.success(),
+            "could not build {name}:\n{}\n{}",
+            String::from_utf8_lossy(&build.stdout),
+            String::from_utf8_lossy(&build.stderr),
+        );
+
+        Self { output }
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.output.join(path).is_file()
+    }
+
+    fn read(&self, path: &str) -> String {
+        std::fs::read_to_string(self.output.join(path))
+            .unwrap_or_else(|e| panic!("could not read {path}: {e}"))
+    }
+}
+
+#[test]
+fn minimal() {
+    let site = Site::build("minimal", &[]);
+
+    let index = site.read("index.html");
+    assert!(index.contains(r#"<a href="/hello.html">Hello</a>"#));
+    assert!(index.contains(r#"<a href="/second.html">Second</a>"#));
+    assert!(index.contains("The first article"));
+
+    let hello = site.read("hello.html");
+    assert!(hello.contains(r#"href="/second.html#header-1""#));
+    assert!(hello.contains(r#"class="hl-"#), "code is not highlighted");
+    assert!(site.read("second.html").contains(r#"id="header-1""#));
+    assert!(site.exists("highlight.css"));
+    assert!(site.exists("manifest.json"));
+
+    // No feed is configured
+    assert!(!site.exists("rss.xml"));
+    assert!(!site.exists("atom.xml"));
+    assert!(!site.exists(".verin.lock"));
+}
+
+#[test]
+fn tags_and_feeds() {
+    let site = Site::build("tags-feeds", &[]);
+
+    for page in ["index.html", "rust.html", "web.html", "tags/index.html"] {
+        assert!(site.exists(page), "{page} was not written");
+    }
+    assert!(!site.exists("draft.html"), "the draft was published");
+    assert!(
+        !site.exists("future.html"),
+        "the future article was published"
+    );
+
+    let index = site.read("index.html");
+    assert!(index.contains(r#"rel="alternate""#));
+    assert!(!index.contains("Unfinished draft"));
+
+    let rust = site.read("tags/rust.html");
+    assert!(rust.contains("Posts about the Rust programming language"));
+    assert!(rust.contains("Learning Rust"));
+    assert!(!rust.contains("Building websites"));
+    assert!(!rust.contains("Unfinished draft"));
+    assert!(!rust.contains("From the future"));
+    assert!(site.exists("tags/intro.html"));
+    assert!(site.read("tags/index.html").contains("rust (1)"));
+
+    let rss = site.read("rss.xml");
+    assert!(rss.contains("https://example.org/rust.html"));
+    assert!(rss.contains("https://example.org/web.html"));
+    assert!(!rss.contains("draft.html"));
+    assert!(!rss.contains("future.html"));
+    let atom = site.read("atom.xml");
+    assert!(atom.contains("https://example.org/rust.html"));
+    assert!(atom.contains("https://example.org/web.html"));
+
+    let tag_feed = site.read("tags/web/rss.xml");
+    assert!(tag_feed.contains("https://example.org/web.html"));
+    assert!(!tag_feed.contains("https://example.org/rust.html"));
+
+    let sitemap = site.read("sitemap.xml");
+    assert!(sitemap.contains("<loc>https://example.org/rust.html</loc>"));
+    assert!(!sitemap.contains("draft.html"));
+}
+
+#[test]
+fn permalinks_under_a_base_path() {
+    let site = Site::build("permalinks", &["--base-path", "/preview/"]);
+
+    // The pages are written at their permalink, the base path only changes their URLs
+    for page in [
+        "2024/01/intro/index.html",
+        "2024/01/intro/2/index.html",
+        "2024/02/next/index.html",
+        "old-intro.html",
+    ] {
+        assert!(site.exists(page), "{page} was not written");
+    }
+    assert!(!site.exists("intro.html"));
+
+    let index = site.read("index.html");
+    assert!(index.contains(r#"<a href="/preview/2024/01/intro/">Introduction</a>"#));
+    assert!(index.contains(r#"<a href="/preview/2024/02/next/">Follow up</a>"#));
+
+    let first = site.read("2024/01/intro/index.html");
+    assert!(first.contains("The first page"));
+    assert!(!first.contains("The second page"));
+    assert!(first.contains(r#"<a href="/preview/2024/01/intro/2/">Next</a>"#));
+    let second = site.read("2024/01/intro/2/index.html");
+    assert!(second.contains("The second page"));
+    assert!(second.contains(r#"<a href="/preview/2024/01/intro/">Previous</a>"#));
+
+    // The headings and the footnotes are numbered again on each page, with the footnotes defined
+    // on another page
+    assert!(first.contains(r#"<h1 id="header-1" >Basics"#));
+    assert!(second.contains(r#"<h1 id="header-1" >Details"#));
+    assert!(second.contains(r#"data-footnote="A note of the second page">1</a>"#));
+    assert!(second.contains(r#"data-footnote="A note of the first page">2</a>"#));
+
+    assert!(site
+        .read("2024/02/next/index.html")
+        .contains(r#"href="/preview/2024/01/intro/""#));
+    assert!(site
+        .read("old-intro.html")
+        .contains("url=/preview/2024/01/intro/"));
+}